@@ -6,13 +6,33 @@
 */
 
 use clap::Parser;
-use log::{debug, info};
+use log::{debug, info, log_enabled, Level};
 use std::{path::PathBuf, process::exit};
 use timecurves_rs::{
-    exporters::{CSVExporter, Exporter, SVGExporter, TikzExporter, VegaLiteExporter},
+    alignment::{
+        AlignmentStrategy, FirstToLastAlignment, NoAlignment, PrincipalAxisAlignment,
+        ProcrustesAlignment, TimeDirectionAlignment,
+    },
+    color::{ColorScheme, CurveColorScheme, SingleColorScheme, TimeGradientColorScheme},
+    distance::Metric,
+    exporters::{CSVExporter, Exporter, OBJExporter, SVGExporter, TikzExporter, VegaLiteExporter},
     input::InputData,
-    projection::ClassicalMDS,
-    timecurve::TimecurveSet,
+    interpolation::{
+        BSplineInterpolation, BachInterpolation, CardinalInterpolation, CatmullRomInterpolation,
+        Interpolation, PolylineInterpolation,
+    },
+    normalisation::{
+        DistanceNormalisation, NoNormalisation, NormalisationStrategy, UnitSquareNormalisation,
+    },
+    patterns::PatternDetector,
+    projection::{
+        AnchoredSMACOF, ClassicalMDS, Isomap, LandmarkMDS, LandmarkSelection, NeighbourhoodGraph,
+        NonMetricMDS, ProjectionAlgorithm, Sammon, ShortestPathCompletion, TSNEMode,
+        TemporalSMACOF, PCA, SMACOF, TSNE, UMAP,
+    },
+    stability::{Perturbation, StabilityAnalysis},
+    timecurve::{Arrangement, TimecurveSet, TimecurveSetBuilder},
+    validation::{validate, Repair, Symmetrisation},
 };
 
 #[derive(Parser)]
@@ -23,7 +43,8 @@ struct CommandLine {
     /// Specifies the output file for the generated curves.
     /// The file will be in the format specified by the --format option.
     output: PathBuf,
-    /// Specifies the format of the output file.
+    /// Specifies the format of the output file : csv, tikz, svg, vegalite or obj.
+    /// obj (Wavefront OBJ) is the only format showing the third dimension of 3D curves.
     #[arg(short, long)]
     format: String,
     /// Specifies the size of the output graph, for formats that support it. Unit is cm for Tikz, px for Vega-lite
@@ -32,6 +53,109 @@ struct CommandLine {
     /// Specifies the thickness of the lines in the output graph, for formats that support it.
     #[arg(long, default_value = "1.0")]
     thickness: f64,
+    /// Specifies the projection algorithm used to place the points : mds, smacof, nmds, lmds, tsne, umap, isomap, sammon, temporal, anchored or pca.
+    /// nmds (non-metric MDS) only preserves the rank order of the distances,
+    /// lmds (landmark MDS) is a fast approximation of mds for inputs with thousands of points,
+    /// tsne and umap preserve local neighbourhoods rather than global distances,
+    /// isomap unrolls slowly drifting data by using geodesic distances,
+    /// sammon preserves small distances more accurately than large ones,
+    /// temporal (time-aware SMACOF) keeps consecutive timepoints close to each other,
+    /// anchored (SMACOF with pinned points) keeps the points listed in the anchors of the input file at their coordinates,
+    /// pca projects the feature vectors of the input file on their principal axes, without using the distances.
+    #[arg(short, long, default_value = "mds")]
+    projection: String,
+    /// Specifies the number of dimensions of the projection, 2 or 3. Only mds, smacof and pca support 3 dimensions.
+    #[arg(long, default_value = "2")]
+    dimensions: usize,
+    /// Specifies how the curves are oriented : none, first-to-last, pca, time or procrustes.
+    /// first-to-last aligns the first and last points of a curve horizontally (see --align-curve),
+    /// pca aligns the principal axis of all the points horizontally,
+    /// time makes time flow from left to right on average,
+    /// procrustes rotates, flips and scales the curves to match the ones of a reference file (see --reference).
+    /// Defaults to first-to-last, or none if the input file has anchors.
+    #[arg(long)]
+    alignment: Option<String>,
+    /// Specifies the index of the curve used by the first-to-last alignment.
+    /// Ignored by the side-by-side and grid layouts, where each curve is aligned on its own ends.
+    #[arg(long, default_value = "0")]
+    align_curve: usize,
+    /// Specifies the input file of the reference curves for the procrustes alignment.
+    /// It is projected with the same options, and the points with the same dataset name and timelabel are matched.
+    #[arg(long)]
+    reference: Option<PathBuf>,
+    /// Specifies how the datasets are projected : joint, side-by-side or grid.
+    /// joint projects all the points together, side-by-side and grid project each dataset from its own distances only,
+    /// ignoring the distances between datasets, and lay out the curves in a row or in a grid.
+    #[arg(long, default_value = "joint")]
+    layout: String,
+    /// Specifies how the points are joined : bach, catmull-rom, bspline, cardinal or polyline.
+    /// bach is the interpolation of the time curves paper (see --smoothing), catmull-rom is the centripetal Catmull-Rom spline,
+    /// bspline is the uniform B-spline going through the points, cardinal is a cardinal spline (see --tension)
+    /// and polyline draws straight lines.
+    #[arg(long, default_value = "bach", value_name = "SCHEME")]
+    interpolation: String,
+    /// Specifies how round the curves of the bach interpolation are between the points, 0 giving straight lines.
+    #[arg(long, default_value = "0.3")]
+    smoothing: f64,
+    /// Specifies the tension of the cardinal interpolation, from 0 (Catmull-Rom spline) to 1 (straight lines).
+    #[arg(long, default_value = "0.5")]
+    tension: f64,
+    /// Joins the points in the order of the timelabels of the input file, instead of sorting them chronologically.
+    #[arg(long)]
+    keep_order: bool,
+    /// Specifies how the curves are scaled after their alignment : unit, distances or none.
+    /// unit fits them in the unit square, distances keeps the unit of the distances of the input file,
+    /// none keeps the scale of the projection. The output formats other than obj expect unit.
+    #[arg(long, default_value = "unit", value_name = "MODE")]
+    normalisation: String,
+    /// Specifies how the points are coloured : curves (one colour per curve, darker over time),
+    /// time (from yellow for the oldest points to red for the newest, for all curves) or single (all blue).
+    #[arg(long, default_value = "curves")]
+    colors: String,
+    /// Circles the points where the curves cross themselves or each other, for the svg and tikz formats.
+    #[arg(long)]
+    crossings: bool,
+    /// Detects the temporal patterns of the curves (clusters, transitions, cycles, oscillations, u-turns, outliers
+    /// and stagnations), lists them and highlights them for the svg and tikz formats.
+    #[arg(long)]
+    patterns: bool,
+    /// Colours the points by the local error of the projection instead of the curve colors, for formats that support it.
+    /// Badly placed points are red, well placed ones are green.
+    #[arg(long)]
+    error_colors: bool,
+    /// Draws an uncertainty ellipse around each point, from the spread of its positions when the input is perturbed
+    /// and projected again : noise (5% noise on the distances), subsample (80% of the points are kept) or seed
+    /// (only the seed of tsne and umap changes). Only for the svg format.
+    #[arg(long, value_name = "PERTURBATION")]
+    uncertainty: Option<String>,
+    /// Specifies the number of perturbed projections used for the uncertainty ellipses.
+    #[arg(long, default_value = "20", value_name = "RUNS")]
+    uncertainty_runs: usize,
+    /// Specifies the metric used to compute the distance matrix from the feature vectors of the input file :
+    /// euclidean, cosine, manhattan, correlation or chebyshev. Replaces the distance matrix of the file, if it has one.
+    /// Defaults to the metric of the input file, or euclidean.
+    #[arg(long)]
+    metric: Option<String>,
+    /// Completes the missing distances (null in the input file) with the length of the shortest paths through the known ones.
+    /// Needed by all projection algorithms except smacof, which ignores missing distances.
+    #[arg(long)]
+    complete_missing: bool,
+    /// Checks that the distance matrix is a proper metric (symmetric, non-negative, zero diagonal, triangle inequality...)
+    /// after the repairs, prints the issues found and stops if there are any.
+    #[arg(long)]
+    validate: bool,
+    /// Makes the distance matrix symmetric by replacing both distances of each pair by their mean, min or max.
+    #[arg(long)]
+    symmetrise: Option<String>,
+    /// Sets the distance of each point to itself to 0.
+    #[arg(long)]
+    zero_diagonal: bool,
+    /// Sets the negative distances to 0.
+    #[arg(long)]
+    clamp_negatives: bool,
+    /// Replaces each distance by the shortest path between the two points, so that the triangle inequality holds.
+    #[arg(long)]
+    metric_repair: bool,
 }
 
 fn main() {
@@ -50,13 +174,239 @@ fn main() {
         }
     };
 
+    let input = match cmd.metric.as_deref().map(str::parse::<Metric>) {
+        None => input,
+        Some(Ok(metric)) => match input.get_features() {
+            Some(features) => {
+                match InputData::from_features(
+                    features.clone(),
+                    input.get_datasets().clone(),
+                    metric,
+                ) {
+                    Ok(v) => v.with_anchors(input.get_anchors().to_vec()),
+                    Err(e) => {
+                        println!("Error while computing the distance matrix :");
+                        println!("{}", e);
+                        exit(1);
+                    }
+                }
+            }
+            None => {
+                println!("The input file has no feature vectors to compute the distances from.");
+                exit(1);
+            }
+        },
+        Some(Err(e)) => {
+            println!("{}", e);
+            exit(1);
+        }
+    };
+
+    let symmetrisation = match cmd.symmetrise.as_deref().map(str::to_lowercase).as_deref() {
+        None => None,
+        Some("mean") => Some(Symmetrisation::Mean),
+        Some("min") => Some(Symmetrisation::Min),
+        Some("max") => Some(Symmetrisation::Max),
+        Some(_) => {
+            println!("Unknown symmetrisation, expected mean, min or max.");
+            exit(1);
+        }
+    };
+
+    let repair = Repair::new()
+        .with_symmetrisation(symmetrisation)
+        .with_zero_diagonal(cmd.zero_diagonal)
+        .with_clamped_negatives(cmd.clamp_negatives)
+        .with_metric_repair(cmd.metric_repair);
+
+    // without any repair the input is kept as is, so that --validate reports the issues of the original matrix
+    let repairing =
+        symmetrisation.is_some() || cmd.zero_diagonal || cmd.clamp_negatives || cmd.metric_repair;
+    let input = if repairing {
+        match repair.apply(input.get_distance_matrix()) {
+            Ok(matrix) => InputData::from(matrix, input.get_datasets().clone())
+                .with_anchors(input.get_anchors().to_vec())
+                .with_features(input.get_features().cloned()),
+            Err(e) => {
+                println!("Error while repairing the distance matrix :");
+                println!("{}", e);
+                exit(1);
+            }
+        }
+    } else {
+        input
+    };
+
+    if cmd.validate {
+        let report = validate(input.get_distance_matrix());
+        println!("{}", report);
+        if !report.is_valid() {
+            exit(1);
+        }
+    }
+
     info!("Input file <{}> read.", &cmd.input.display());
     info!("Contains {} datasets :", input.get_datasets().len());
     for dataset in input.get_datasets() {
         info!("  - {}", dataset.get_name());
     }
 
-    let timecurves = match TimecurveSet::new(&input, ClassicalMDS::new()) {
+    let projection_name = cmd.projection.to_lowercase();
+    if cmd.dimensions != 2 && !["mds", "smacof", "pca"].contains(&projection_name.as_str()) {
+        println!("Only the mds, smacof and pca projections support other dimensions than 2.");
+        exit(1);
+    }
+
+    // the reference of the procrustes alignment and the perturbed inputs of the uncertainty ellipses
+    // are projected the same way as the input
+    let build_projection = |input: &InputData, seed: u64| -> Box<dyn ProjectionAlgorithm> {
+        let projection: Box<dyn ProjectionAlgorithm> = match projection_name.as_str() {
+            "mds" => Box::new(ClassicalMDS::new().with_dimensions(cmd.dimensions)),
+            // SMACOF keeps the dimensions of its initial layout
+            "smacof" if cmd.dimensions != 2 => {
+                let initial_mds = ShortestPathCompletion::new(
+                    ClassicalMDS::new().with_dimensions(cmd.dimensions),
+                );
+                match initial_mds.project(input.get_distance_matrix()) {
+                    Ok(layout) => Box::new(SMACOF::new(300, 1e-6, Some(layout))),
+                    Err(e) => {
+                        println!("Error while computing the initial layout of SMACOF :");
+                        println!("{}", e);
+                        exit(1);
+                    }
+                }
+            }
+            "smacof" => Box::new(SMACOF::new(300, 1e-6, None)),
+            "nmds" => Box::new(NonMetricMDS::new(300, 1e-6)),
+            "lmds" => Box::new(LandmarkMDS::new(100, LandmarkSelection::MaxMin)),
+            "tsne" => Box::new(TSNE::new(30.0, 200.0, 1000, seed, TSNEMode::BarnesHut(0.5))),
+            "umap" => Box::new(UMAP::new(15, 0.1, seed)),
+            "isomap" => Box::new(Isomap::new(NeighbourhoodGraph::KNearest(10))),
+            "sammon" => Box::new(Sammon::new(500, 1e-9, 1.0)),
+            "temporal" => Box::new(TemporalSMACOF::new(300, 1e-6, 0.1)),
+            "anchored" => Box::new(AnchoredSMACOF::new(300, 1e-6)),
+            "pca" => Box::new(PCA::new().with_dimensions(cmd.dimensions)),
+            _ => {
+                println!("Unknown projection algorithm.");
+                exit(1);
+            }
+        };
+
+        if cmd.complete_missing {
+            Box::new(ShortestPathCompletion::new(projection))
+        } else {
+            projection
+        }
+    };
+
+    let arrangement = match cmd.layout.to_lowercase().as_str() {
+        "joint" => None,
+        "side-by-side" => Some(Arrangement::SideBySide),
+        "grid" => {
+            let columns = (input.get_datasets().len() as f64).sqrt().ceil() as usize;
+            Some(Arrangement::SmallMultiples(columns.max(1)))
+        }
+        _ => {
+            println!("Unknown layout, expected joint, side-by-side or grid.");
+            exit(1);
+        }
+    };
+    let default_alignment = if input.get_anchors().is_empty() {
+        "first-to-last"
+    } else {
+        "none"
+    };
+    let alignment_name = cmd.alignment.as_deref().unwrap_or(default_alignment);
+
+    let alignment: Box<dyn AlignmentStrategy> = match alignment_name.to_lowercase().as_str() {
+        "none" => Box::new(NoAlignment::new()),
+        // each curve of an independent layout is aligned on its own, as the first curve of its block
+        "first-to-last" if arrangement.is_some() => Box::new(FirstToLastAlignment::new(0)),
+        "first-to-last" => Box::new(FirstToLastAlignment::new(cmd.align_curve)),
+        "pca" => Box::new(PrincipalAxisAlignment::new()),
+        "time" => Box::new(TimeDirectionAlignment::new()),
+        "procrustes" => {
+            let reference_file = match &cmd.reference {
+                Some(file) => file.display().to_string(),
+                None => {
+                    println!("The procrustes alignment needs a reference file, see --reference.");
+                    exit(1);
+                }
+            };
+            let reference = match InputData::from_filename(&reference_file) {
+                Ok(v) => v,
+                Err(e) => {
+                    println!("Error while parsing the reference file :");
+                    println!("{}", e);
+                    exit(1);
+                }
+            };
+            match TimecurveSet::new(&reference, build_projection(&reference, 0)) {
+                Ok(reference) => Box::new(ProcrustesAlignment::new(reference)),
+                Err(e) => {
+                    println!("Error while creating the reference timecurves :");
+                    println!("{}", e);
+                    exit(1);
+                }
+            }
+        }
+        _ => {
+            println!("Unknown alignment strategy.");
+            exit(1);
+        }
+    };
+
+    if arrangement.is_some() && cmd.uncertainty.is_some() {
+        println!("The uncertainty ellipses are only available with the joint layout.");
+        exit(1);
+    }
+
+    let normalisation: Box<dyn NormalisationStrategy> =
+        match cmd.normalisation.to_lowercase().as_str() {
+            "unit" => Box::new(UnitSquareNormalisation::new()),
+            "distances" => Box::new(DistanceNormalisation::new()),
+            "none" => Box::new(NoNormalisation::new()),
+            _ => {
+                println!("Unknown normalisation, expected unit, distances or none.");
+                exit(1);
+            }
+        };
+
+    let color_scheme: Box<dyn ColorScheme> = match cmd.colors.to_lowercase().as_str() {
+        "curves" => Box::new(CurveColorScheme::new()),
+        "time" => Box::new(TimeGradientColorScheme::new((255, 221, 87), (189, 0, 38))),
+        "single" => Box::new(SingleColorScheme::new((31, 119, 180))),
+        _ => {
+            println!("Unknown colour scheme, expected curves, time or single.");
+            exit(1);
+        }
+    };
+
+    let interpolation: Box<dyn Interpolation> = match cmd.interpolation.to_lowercase().as_str() {
+        "bach" => Box::new(BachInterpolation::new(cmd.smoothing)),
+        "catmull-rom" => Box::new(CatmullRomInterpolation::new()),
+        "bspline" => Box::new(BSplineInterpolation::new()),
+        "cardinal" => Box::new(CardinalInterpolation::new(cmd.tension)),
+        "polyline" => Box::new(PolylineInterpolation::new()),
+        _ => {
+            println!(
+                "Unknown interpolation, expected bach, catmull-rom, bspline, cardinal or polyline."
+            );
+            exit(1);
+        }
+    };
+
+    let result = TimecurveSetBuilder::new(build_projection(&input, 0))
+        .with_alignment(alignment)
+        .with_normalisation(normalisation)
+        .with_color_scheme(color_scheme)
+        .with_interpolation(interpolation)
+        .with_time_sorting(!cmd.keep_order)
+        .with_arrangement(arrangement)
+        // the quality report is only worth its cost if it is logged or shown
+        .with_quality(cmd.error_colors || log_enabled!(Level::Info))
+        .build(&input);
+    let timecurves = match result {
         Ok(curves) => curves,
         Err(e) => {
             println!("Error while creating the timecurves :");
@@ -66,24 +416,99 @@ fn main() {
     };
 
     info!("Curves for datasets calculated.");
+    if let Some(quality) = timecurves.get_quality() {
+        info!(
+            "Projection quality : stress = {:.4}, trustworthiness = {:.4}, continuity = {:.4} (k = {})",
+            quality.get_stress(),
+            quality.get_trustworthiness(),
+            quality.get_continuity(),
+            quality.get_k()
+        );
+    }
     for curve in timecurves.get_curves() {
         debug!("Points for dataset '{}' :", curve.get_name());
         for (i, p) in curve.get_points().iter().enumerate() {
             debug!(
-                "  {}. - {} : ({:.2}, {:.2})",
+                "  {}. - {} : ({:.2}, {:.2}, {:.2})",
                 i,
                 p.get_label(),
                 p.get_pos_x(),
-                p.get_pos_y()
+                p.get_pos_y(),
+                p.get_pos_z()
             );
         }
     }
 
+    let perturbation = match cmd.uncertainty.as_deref().map(str::to_lowercase).as_deref() {
+        None => None,
+        Some("noise") => Some(Perturbation::Noise(0.05)),
+        Some("subsample") => Some(Perturbation::Subsample(0.8)),
+        Some("seed") => Some(Perturbation::Seed),
+        Some(_) => {
+            println!("Unknown perturbation, expected noise, subsample or seed.");
+            exit(1);
+        }
+    };
+
+    let stability = perturbation.map(|perturbation| {
+        match StabilityAnalysis::new(cmd.uncertainty_runs, perturbation).analyse(
+            &input,
+            &timecurves,
+            |perturbed, seed| build_projection(perturbed, seed),
+        ) {
+            Ok(report) => report,
+            Err(e) => {
+                println!("Error while analysing the stability of the projection :");
+                println!("{}", e);
+                exit(1);
+            }
+        }
+    });
+
+    let patterns = if cmd.patterns {
+        match PatternDetector::new().detect(&timecurves, input.get_distance_matrix()) {
+            Ok(patterns) => patterns,
+            Err(e) => {
+                println!("Error while detecting the patterns :");
+                println!("{}", e);
+                exit(1);
+            }
+        }
+    } else {
+        Vec::new()
+    };
+    for pattern in patterns.iter() {
+        let curve = &timecurves.get_curves()[pattern.get_curve()];
+        let (start, end) = pattern.get_points();
+        println!(
+            "{} : {} from {} to {} (confidence {:.2})",
+            curve.get_name(),
+            pattern.get_kind(),
+            curve.get_points()[start].get_label(),
+            curve.get_points()[end].get_label(),
+            pattern.get_confidence()
+        );
+    }
+
     let exporter: Box<dyn Exporter> = match cmd.format.to_lowercase().as_str() {
         "csv" => Box::new(CSVExporter::new()),
-        "tikz" => Box::new(TikzExporter::new(cmd.size.unwrap_or(10.0), cmd.thickness)),
-        "svg" => Box::new(SVGExporter::new(cmd.thickness)),
+        "tikz" => Box::new(
+            TikzExporter::new(cmd.size.unwrap_or(10.0), cmd.thickness)
+                .with_crossings(cmd.crossings)
+                .with_patterns(patterns),
+        ),
+        "svg" => {
+            let exporter = SVGExporter::new(cmd.thickness)
+                .with_local_error_colors(cmd.error_colors)
+                .with_crossings(cmd.crossings)
+                .with_patterns(patterns);
+            match stability {
+                Some(report) => Box::new(exporter.with_uncertainty(report)),
+                None => Box::new(exporter),
+            }
+        }
         "vegalite" => Box::new(VegaLiteExporter::new(cmd.size.unwrap_or(400.0) as u64)),
+        "obj" => Box::new(OBJExporter::new(16)),
         _ => {
             println!("Unknown output format.");
             exit(1);
@@ -0,0 +1,558 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+//! Strategies choosing the orientation of the curves of a `TimecurveSet`.
+//!
+//! The projection algorithms only preserve distances, so the orientation of their layout is arbitrary.
+//! An alignment strategy computes the transformation applied to the whole set, before its normalisation.
+
+use crate::{
+    error::{TimecurveError, TimecurveErrorKind},
+    timecurve::{rotate_point_around_origin, Position, TimecurveSet},
+};
+use nalgebra::{DMatrix, Matrix3, Vector3};
+use std::collections::HashMap;
+
+/// Trait representing a way to align the curves of a set.
+pub trait AlignmentStrategy {
+    /// Computes the transformation that aligns the set.
+    ///
+    /// ### Arguments
+    ///
+    /// * `timecurve_set` - The set to align.
+    ///
+    /// ### Returns
+    ///
+    /// The transformation to apply to all the points of the set, or an error if the set can't be aligned.
+    fn transformation(&self, timecurve_set: &TimecurveSet) -> Result<Similarity, TimecurveError>;
+
+    /// Returns `true` if the set must be normalised to \[0, 1\] after the alignment, which is the default.
+    /// Strategies that bring the set in the frame of another one return `false`,
+    /// so that the two sets can be compared directly.
+    fn normalise_after(&self) -> bool {
+        true
+    }
+}
+
+impl<T: AlignmentStrategy + ?Sized> AlignmentStrategy for Box<T> {
+    fn transformation(&self, timecurve_set: &TimecurveSet) -> Result<Similarity, TimecurveError> {
+        (**self).transformation(timecurve_set)
+    }
+
+    fn normalise_after(&self) -> bool {
+        (**self).normalise_after()
+    }
+}
+
+/// Allows aligning several sets with the same strategy, eg. one per dataset.
+impl<T: AlignmentStrategy + ?Sized> AlignmentStrategy for &T {
+    fn transformation(&self, timecurve_set: &TimecurveSet) -> Result<Similarity, TimecurveError> {
+        (**self).transformation(timecurve_set)
+    }
+
+    fn normalise_after(&self) -> bool {
+        (**self).normalise_after()
+    }
+}
+
+/// A transformation p ↦ s R p + t, where R is a rotation or a reflection, s a positive scale and t a translation.
+/// It changes neither the shape of the curves nor the relative distances between the points.
+#[derive(Clone, Copy)]
+pub struct Similarity {
+    /// The orthogonal matrix R.
+    rotation: Matrix3<f64>,
+    /// The scale s.
+    scale: f64,
+    /// The translation t.
+    translation: Vector3<f64>,
+}
+
+impl Similarity {
+    /// Creates a new similarity.
+    ///
+    /// ### Arguments
+    ///
+    /// * `rotation` - The orthogonal matrix R, applied first.
+    /// * `scale` - The scale s, applied after the rotation.
+    /// * `translation` - The translation t, applied last.
+    pub fn new(rotation: Matrix3<f64>, scale: f64, translation: Vector3<f64>) -> Self {
+        Self {
+            rotation,
+            scale,
+            translation,
+        }
+    }
+
+    /// Creates the similarity that doesn't move the points.
+    pub fn identity() -> Self {
+        Self::new(Matrix3::identity(), 1.0, Vector3::zeros())
+    }
+
+    /// Composes two similarities.
+    ///
+    /// ### Arguments
+    ///
+    /// * `other` - The similarity applied after this one.
+    ///
+    /// ### Returns
+    ///
+    /// The similarity applying this one, then `other`.
+    pub fn then(&self, other: &Similarity) -> Similarity {
+        Similarity::new(
+            other.rotation * self.rotation,
+            self.scale * other.scale,
+            other.rotation * self.translation * other.scale + other.translation,
+        )
+    }
+
+    /// Returns the scale of the similarity, ie. the factor applied to all the distances.
+    pub fn get_scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Applies the similarity to a position.
+    pub fn apply(&self, p: &Position) -> Position {
+        let v = self.rotation * to_vector(p) * self.scale + self.translation;
+        Position::new_3d(v.x, v.y, v.z)
+    }
+}
+
+/// Alignment strategy keeping the layout given by the projection algorithm.
+#[derive(Default)]
+pub struct NoAlignment;
+
+impl NoAlignment {
+    /// Creates a new instance of the strategy.
+    pub fn new() -> Self {
+        NoAlignment
+    }
+}
+
+impl AlignmentStrategy for NoAlignment {
+    fn transformation(&self, _timecurve_set: &TimecurveSet) -> Result<Similarity, TimecurveError> {
+        Ok(Similarity::identity())
+    }
+}
+
+/// Alignment strategy rotating the set so that the first and last points of a curve are aligned horizontally,
+/// the first one on the left. It is the default alignment of `TimecurveSet::new`, with the first curve.
+///
+/// If the first and last points coincide, eg. for cyclic data, the layout is kept as is.
+pub struct FirstToLastAlignment {
+    /// The index of the curve whose ends are aligned.
+    curve: usize,
+}
+
+impl FirstToLastAlignment {
+    /// Creates a new instance of the strategy.
+    ///
+    /// ### Arguments
+    ///
+    /// * `curve` - The index of the curve whose ends are aligned, in the order of the datasets.
+    pub fn new(curve: usize) -> Self {
+        Self { curve }
+    }
+}
+
+impl AlignmentStrategy for FirstToLastAlignment {
+    fn transformation(&self, timecurve_set: &TimecurveSet) -> Result<Similarity, TimecurveError> {
+        let curve = timecurve_set.get_curves().get(self.curve).ok_or_else(|| {
+            TimecurveError::new(
+                TimecurveErrorKind::InvalidParameter,
+                Some(&format!(
+                    "Can't align on curve {}, the set has {} curves",
+                    self.curve,
+                    timecurve_set.get_curves().len()
+                )),
+            )
+        })?;
+
+        // if there are no points to align
+        let (first, last) = match (curve.get_points().first(), curve.get_points().last()) {
+            (Some(first), Some(last)) => (first.get_pos(), last.get_pos()),
+            _ => return Ok(Similarity::identity()),
+        };
+
+        Ok(Similarity::new(
+            rotation_onto_x_axis(&(to_vector(last) - to_vector(first))),
+            1.0,
+            Vector3::zeros(),
+        ))
+    }
+}
+
+/// Alignment strategy rotating the set so that the direction along which all the points are the most spread out,
+/// ie. their principal axis, is horizontal. For 3D sets, the second axis is the y axis.
+///
+/// The principal axis is oriented so that time flows from left to right on average.
+#[derive(Default)]
+pub struct PrincipalAxisAlignment;
+
+impl PrincipalAxisAlignment {
+    /// Creates a new instance of the strategy.
+    pub fn new() -> Self {
+        PrincipalAxisAlignment
+    }
+}
+
+impl AlignmentStrategy for PrincipalAxisAlignment {
+    fn transformation(&self, timecurve_set: &TimecurveSet) -> Result<Similarity, TimecurveError> {
+        let points: Vec<Vector3<f64>> = timecurve_set
+            .get_curves()
+            .iter()
+            .flat_map(|c| c.get_points().iter().map(|p| to_vector(p.get_pos())))
+            .collect();
+        if points.len() < 2 {
+            return Ok(Similarity::identity());
+        }
+
+        let centre = points.iter().sum::<Vector3<f64>>() / points.len() as f64;
+        let covariance = points
+            .iter()
+            .map(|p| (p - centre) * (p - centre).transpose())
+            .sum::<Matrix3<f64>>();
+        let time = time_direction(timecurve_set);
+
+        let rotation = if timecurve_set.is_3d() {
+            let decomposition = covariance.symmetric_eigen();
+            let mut order = [0, 1, 2];
+            order.sort_by(|&a, &b| {
+                decomposition.eigenvalues[b].total_cmp(&decomposition.eigenvalues[a])
+            });
+
+            let mut first: Vector3<f64> = decomposition.eigenvectors.column(order[0]).into();
+            if first.dot(&time) < 0.0 {
+                first = -first;
+            }
+            let second: Vector3<f64> = decomposition.eigenvectors.column(order[1]).into();
+            Matrix3::from_rows(&[
+                first.transpose(),
+                second.transpose(),
+                first.cross(&second).transpose(),
+            ])
+        } else {
+            // in 2D the axis is given by a closed formula, which keeps the points exactly in the plane z = 0
+            let angle =
+                0.5 * (2.0 * covariance[(0, 1)]).atan2(covariance[(0, 0)] - covariance[(1, 1)]);
+            let mut axis = Vector3::new(angle.cos(), angle.sin(), 0.0);
+            if axis.dot(&time) < 0.0 {
+                axis = -axis;
+            }
+            rotation_onto_x_axis(&axis)
+        };
+
+        Ok(Similarity::new(rotation, 1.0, Vector3::zeros()))
+    }
+}
+
+/// Alignment strategy rotating the set so that time flows from left to right on average,
+/// ie. the direction in which the points move the most consistently over time is horizontal.
+///
+/// Unlike `FirstToLastAlignment`, it takes all the points of all the curves into account,
+/// so it also works for cyclic data as long as the curves drift over time.
+#[derive(Default)]
+pub struct TimeDirectionAlignment;
+
+impl TimeDirectionAlignment {
+    /// Creates a new instance of the strategy.
+    pub fn new() -> Self {
+        TimeDirectionAlignment
+    }
+}
+
+impl AlignmentStrategy for TimeDirectionAlignment {
+    fn transformation(&self, timecurve_set: &TimecurveSet) -> Result<Similarity, TimecurveError> {
+        Ok(Similarity::new(
+            rotation_onto_x_axis(&time_direction(timecurve_set)),
+            1.0,
+            Vector3::zeros(),
+        ))
+    }
+}
+
+/// Alignment strategy bringing the set in the frame of a reference set with a Procrustes analysis,
+/// eg. to compare the curves of an article generated with more revisions to the previous ones.
+///
+/// The points sharing the same curve name and time label in both sets are matched,
+/// and the rotation, reflection, scaling and translation bringing them the closest to their reference positions
+/// (in the least squares sense) are applied to all the points of the set.
+/// The set is not normalised afterwards, so its points may lie outside of \[0, 1\].
+pub struct ProcrustesAlignment {
+    /// The set to align to.
+    reference: TimecurveSet,
+}
+
+impl ProcrustesAlignment {
+    /// Creates a new instance of the strategy.
+    ///
+    /// ### Arguments
+    ///
+    /// * `reference` - The set to align to.
+    pub fn new(reference: TimecurveSet) -> Self {
+        Self { reference }
+    }
+}
+
+impl AlignmentStrategy for ProcrustesAlignment {
+    fn transformation(&self, timecurve_set: &TimecurveSet) -> Result<Similarity, TimecurveError> {
+        procrustes(timecurve_set, &self.reference)
+    }
+
+    fn normalise_after(&self) -> bool {
+        false
+    }
+}
+
+/// Computes the similarity bringing the points of a set the closest to the matching points of a reference set,
+/// see `ProcrustesAlignment`.
+///
+/// ### Returns
+///
+/// The similarity, or an error if the two sets share less than 2 distinct points.
+pub(crate) fn procrustes(
+    timecurve_set: &TimecurveSet,
+    reference: &TimecurveSet,
+) -> Result<Similarity, TimecurveError> {
+    let reference_positions: HashMap<(&str, &str), &Position> = reference
+        .get_curves()
+        .iter()
+        .flat_map(|c| {
+            c.get_points()
+                .iter()
+                .map(move |p| ((c.get_name(), p.get_label()), p.get_pos()))
+        })
+        .collect();
+
+    // (point of the set, point of the reference) couples
+    let pairs: Vec<(Position, Position)> = timecurve_set
+        .get_curves()
+        .iter()
+        .flat_map(|c| c.get_points().iter().map(move |p| (c.get_name(), p)))
+        .filter_map(|(name, p)| {
+            reference_positions
+                .get(&(name, p.get_label()))
+                .map(|r| (*p.get_pos(), **r))
+        })
+        .collect();
+
+    // it is done in 2D when both sets are flat so that the points stay exactly in the plane z = 0
+    let dimensions = if timecurve_set.is_3d() || reference.is_3d() {
+        3
+    } else {
+        2
+    };
+
+    fit_similarity(&pairs, dimensions).map_err(|_| {
+        TimecurveError::new(
+            TimecurveErrorKind::InvalidParameter,
+            Some(&format!(
+                "The sets share {} points, at least 2 distinct points are needed to align them",
+                pairs.len()
+            )),
+        )
+    })
+}
+
+/// Computes the similarity bringing a list of points the closest to their target positions, in the least squares sense.
+///
+/// ### Arguments
+///
+/// * `pairs` - The (point, target) couples.
+/// * `dimensions` - 2 to look for a similarity in the plane z = 0, which keeps 2D points exactly in that plane, or 3.
+///
+/// ### Returns
+///
+/// The similarity, or an `InvalidParameter` error if there are less than 2 distinct points.
+pub(crate) fn fit_similarity(
+    pairs: &[(Position, Position)],
+    dimensions: usize,
+) -> Result<Similarity, TimecurveError> {
+    let n = pairs.len() as f64;
+    let centre = pairs.iter().map(|(a, _)| to_vector(a)).sum::<Vector3<f64>>() / n;
+    let target_centre = pairs.iter().map(|(_, b)| to_vector(b)).sum::<Vector3<f64>>() / n;
+    let spread: f64 = pairs
+        .iter()
+        .map(|(a, _)| (to_vector(a) - centre).norm_squared())
+        .sum();
+
+    if pairs.len() < 2 || spread <= f64::EPSILON * centre.norm_squared().max(1.0) {
+        return Err(TimecurveError::new(
+            TimecurveErrorKind::InvalidParameter,
+            Some(&format!(
+                "Got {} points, at least 2 distinct points are needed to fit a similarity",
+                pairs.len()
+            )),
+        ));
+    }
+
+    // the SVD of the cross-covariance of the centred points gives the best orthogonal transformation
+    let mut covariance = DMatrix::<f64>::zeros(dimensions, dimensions);
+    for (a, b) in pairs {
+        let (a, b) = (to_vector(a) - centre, to_vector(b) - target_centre);
+        for i in 0..dimensions {
+            for j in 0..dimensions {
+                covariance[(i, j)] += b[i] * a[j];
+            }
+        }
+    }
+
+    let svd = covariance.svd(true, true);
+    let orthogonal = svd.u.unwrap() * svd.v_t.unwrap();
+    let mut rotation = Matrix3::identity();
+    for i in 0..dimensions {
+        for j in 0..dimensions {
+            rotation[(i, j)] = orthogonal[(i, j)];
+        }
+    }
+    let scale = svd.singular_values.sum() / spread;
+
+    Ok(Similarity::new(
+        rotation,
+        scale,
+        target_centre - rotation * centre * scale,
+    ))
+}
+
+/// Computes the direction in which the points move over time, as the sum over all the curves
+/// of the covariance between the positions of the points and their time.
+/// The time of each curve is scaled to \[0, 1\], so that all the curves count the same.
+fn time_direction(timecurve_set: &TimecurveSet) -> Vector3<f64> {
+    let mut direction = Vector3::zeros();
+
+    for curve in timecurve_set.get_curves() {
+        let points = curve.get_points();
+        let (first, last) = match (points.first(), points.last()) {
+            (Some(first), Some(last)) if last.get_t() > first.get_t() => {
+                (first.get_t(), last.get_t())
+            }
+            _ => continue,
+        };
+
+        let n = points.len() as f64;
+        let time = |t: i64| (t - first) as f64 / (last - first) as f64;
+        let mean_time = points.iter().map(|p| time(p.get_t())).sum::<f64>() / n;
+        let centre = points
+            .iter()
+            .map(|p| to_vector(p.get_pos()))
+            .sum::<Vector3<f64>>()
+            / n;
+
+        for p in points {
+            direction += (to_vector(p.get_pos()) - centre) * (time(p.get_t()) - mean_time);
+        }
+    }
+
+    direction
+}
+
+/// Computes the rotation bringing a direction onto the positive x axis : a rotation around the z axis,
+/// followed by a rotation around the y axis for 3D directions.
+/// A zero direction gives the identity.
+fn rotation_onto_x_axis(direction: &Vector3<f64>) -> Matrix3<f64> {
+    // the columns of the rotation are the images of the basis vectors
+    let angle = -direction.y.atan2(direction.x);
+    let around_z = Matrix3::from_columns(&[
+        to_vector(&rotate_point_around_origin(angle, Position::new(1.0, 0.0))),
+        to_vector(&rotate_point_around_origin(angle, Position::new(0.0, 1.0))),
+        Vector3::z(),
+    ]);
+
+    let horizontal = around_z * direction;
+    let elevation = horizontal.z.atan2(horizontal.x);
+    let (sin, cos) = elevation.sin_cos();
+    let around_y = Matrix3::new(cos, 0.0, sin, 0.0, 1.0, 0.0, -sin, 0.0, cos);
+
+    around_y * around_z
+}
+
+/// Utility function that converts a position to a vector, for linear algebra.
+fn to_vector(p: &Position) -> Vector3<f64> {
+    Vector3::new(p.get_x(), p.get_y(), p.get_z())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{input::InputData, projection::ClassicalMDS};
+
+    fn psfr() -> InputData {
+        InputData::from_filename(&format!(
+            "{}/tests/psfr100points.json",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn strategies_keep_flat_sets_flat_and_time_flows_right() {
+        let input = psfr();
+        let strategies: Vec<Box<dyn AlignmentStrategy>> = vec![
+            Box::new(FirstToLastAlignment::new(0)),
+            Box::new(PrincipalAxisAlignment::new()),
+            Box::new(TimeDirectionAlignment::new()),
+        ];
+
+        for strategy in strategies {
+            let set =
+                TimecurveSet::new_with_alignment(&input, ClassicalMDS::new(), strategy).unwrap();
+            assert!(!set.is_3d());
+
+            let points = set.get_curves()[0].get_points();
+            assert!(points.last().unwrap().get_pos_x() > points.first().unwrap().get_pos_x());
+        }
+
+        // the default alignment of TimecurveSet::new
+        let aligned = TimecurveSet::new_with_alignment(
+            &input,
+            ClassicalMDS::new(),
+            FirstToLastAlignment::new(0),
+        )
+        .unwrap();
+        let default = TimecurveSet::new(&input, ClassicalMDS::new()).unwrap();
+        for (a, b) in aligned.get_curves()[0]
+            .get_points()
+            .iter()
+            .zip(default.get_curves()[0].get_points())
+        {
+            assert!(a.get_pos().distance(b.get_pos()) < 1e-9);
+        }
+
+        assert!(TimecurveSet::new_with_alignment(
+            &input,
+            ClassicalMDS::new(),
+            FirstToLastAlignment::new(1)
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn principal_axis_is_horizontal() {
+        let input = psfr();
+        let set = TimecurveSet::new_with_alignment(
+            &input,
+            ClassicalMDS::new(),
+            PrincipalAxisAlignment::new(),
+        )
+        .unwrap();
+
+        let points = set.get_curves()[0].get_points();
+        let n = points.len() as f64;
+        let (mx, my) = (
+            points.iter().map(|p| p.get_pos_x()).sum::<f64>() / n,
+            points.iter().map(|p| p.get_pos_y()).sum::<f64>() / n,
+        );
+        let (mut cxx, mut cxy, mut cyy) = (0.0, 0.0, 0.0);
+        for p in points {
+            let (x, y) = (p.get_pos_x() - mx, p.get_pos_y() - my);
+            cxx += x * x;
+            cxy += x * y;
+            cyy += y * y;
+        }
+
+        assert!(cxy.abs() < 1e-9 * cxx);
+        assert!(cxx >= cyy);
+    }
+}
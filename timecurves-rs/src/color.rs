@@ -0,0 +1,110 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+//! Colour schemes giving the colour of each point of a `TimecurveSet`.
+
+use crate::timecurve::curve_color_lerp;
+use palette::{IntoColor, Mix, Srgb};
+
+/// Trait representing a way to colour the points of the curves.
+///
+/// The colour schemes are shared by the sets they colour, so they must be thread-safe.
+pub trait ColorScheme: Send + Sync {
+    /// Computes the colour of a point.
+    ///
+    /// ### Arguments
+    ///
+    /// * `curve` - The index of the curve of the point in the set.
+    /// * `u` - The time of the point relative to its curve, from 0.0 for the oldest point to 1.0 for the newest one.
+    ///
+    /// ### Returns
+    ///
+    /// The colour of the point, as a RGB tuple.
+    fn color(&self, curve: usize, u: f32) -> (u8, u8, u8);
+}
+
+impl<T: ColorScheme + ?Sized> ColorScheme for Box<T> {
+    fn color(&self, curve: usize, u: f32) -> (u8, u8, u8) {
+        (**self).color(curve, u)
+    }
+}
+
+/// Colour scheme giving each curve its own colour, which gets darker over time. See `curve_color_lerp`.
+#[derive(Default)]
+pub struct CurveColorScheme;
+
+impl CurveColorScheme {
+    /// Creates a new instance of the colour scheme.
+    pub fn new() -> Self {
+        CurveColorScheme
+    }
+}
+
+impl ColorScheme for CurveColorScheme {
+    fn color(&self, curve: usize, u: f32) -> (u8, u8, u8) {
+        curve_color_lerp(curve, u)
+    }
+}
+
+/// Colour scheme going from one colour for the oldest point of each curve to another one for the newest,
+/// the same for all curves, eg. to compare the progress of several curves.
+pub struct TimeGradientColorScheme {
+    /// The colour of the oldest points.
+    start: (u8, u8, u8),
+    /// The colour of the newest points.
+    end: (u8, u8, u8),
+}
+
+impl TimeGradientColorScheme {
+    /// Creates a new instance of the colour scheme.
+    ///
+    /// ### Arguments
+    ///
+    /// * `start` - The colour of the oldest points, as a RGB tuple.
+    /// * `end` - The colour of the newest points, as a RGB tuple.
+    pub fn new(start: (u8, u8, u8), end: (u8, u8, u8)) -> Self {
+        Self { start, end }
+    }
+}
+
+impl ColorScheme for TimeGradientColorScheme {
+    fn color(&self, _curve: usize, u: f32) -> (u8, u8, u8) {
+        let to_srgb =
+            |c: (u8, u8, u8)| Srgb::new(c.0 as f32 / 255.0, c.1 as f32 / 255.0, c.2 as f32 / 255.0);
+        let start: palette::Lab = to_srgb(self.start).into_color();
+        let end: palette::Lab = to_srgb(self.end).into_color();
+
+        let srgb: Srgb = start.mix(end, u.clamp(0.0, 1.0)).into_color();
+        (
+            (srgb.red.clamp(0.0, 1.0) * 255.0).round() as u8,
+            (srgb.green.clamp(0.0, 1.0) * 255.0).round() as u8,
+            (srgb.blue.clamp(0.0, 1.0) * 255.0).round() as u8,
+        )
+    }
+}
+
+/// Colour scheme giving the same colour to all the points.
+pub struct SingleColorScheme {
+    /// The colour of the points.
+    color: (u8, u8, u8),
+}
+
+impl SingleColorScheme {
+    /// Creates a new instance of the colour scheme.
+    ///
+    /// ### Arguments
+    ///
+    /// * `color` - The colour of all the points, as a RGB tuple.
+    pub fn new(color: (u8, u8, u8)) -> Self {
+        Self { color }
+    }
+}
+
+impl ColorScheme for SingleColorScheme {
+    fn color(&self, _curve: usize, _u: f32) -> (u8, u8, u8) {
+        self.color
+    }
+}
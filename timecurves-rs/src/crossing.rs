@@ -0,0 +1,521 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+//! Detection of the crossings between the bezier segments of the curves.
+//!
+//! In a time curve, a curve crossing itself means that the data came back close to an earlier state,
+//! and two curves crossing each other mean that two datasets went through similar states.
+//! The crossings are computed in the (x, y) plane, so the z coordinate of 3D curves is ignored.
+
+use crate::timecurve::{Position, Timecurve};
+use nalgebra::Vector2;
+
+/// The relative size under which a bezier segment is considered straight, compared to the size of the curves.
+const FLATNESS: f64 = 1e-6;
+
+/// The maximum number of subdivisions of a bezier segment, in case of numerical issues.
+const MAX_DEPTH: usize = 48;
+
+/// Structure representing a crossing between two bezier segments of a `TimecurveSet`.
+/// The segment `i` of a curve goes from its point `i` to its point `i + 1`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Crossing {
+    /// The indices in the set of the two curves, equal for a self-crossing.
+    curves: (usize, usize),
+    /// The indices of the two segments in their curves, the first one being the earliest for a self-crossing.
+    segments: (usize, usize),
+    /// The parameters of the crossing along the two segments, in \[0, 1\].
+    parameters: (f64, f64),
+    /// The position of the crossing.
+    position: (f64, f64),
+}
+
+impl Crossing {
+    /// Returns the indices in the set of the two curves crossing each other.
+    /// Both are 0 for the crossings of `Timecurve::compute_self_crossings`.
+    pub fn get_curves(&self) -> (usize, usize) {
+        self.curves
+    }
+
+    /// Returns the indices of the two segments in their curves.
+    /// For a self-crossing, the first segment is the earliest one.
+    pub fn get_segments(&self) -> (usize, usize) {
+        self.segments
+    }
+
+    /// Returns the parameters of the crossing along the two segments, from 0.0 at the start of a segment to 1.0 at its end.
+    /// `Timecurve::evaluate(segment + parameter)` gives the position of the crossing on each curve.
+    pub fn get_parameters(&self) -> (f64, f64) {
+        self.parameters
+    }
+
+    /// Returns the (x, y) position of the crossing.
+    pub fn get_position(&self) -> (f64, f64) {
+        self.position
+    }
+
+    /// Returns `true` if the crossing is between two segments of the same curve.
+    pub fn is_self_crossing(&self) -> bool {
+        self.curves.0 == self.curves.1
+    }
+}
+
+/// A cubic bezier segment in the (x, y) plane, with the range of parameters it covers in the original segment.
+#[derive(Clone, Copy)]
+struct Bezier {
+    points: [Vector2<f64>; 4],
+    start: f64,
+    end: f64,
+}
+
+impl Bezier {
+    /// Returns the bezier segments of a curve, straight if control points are missing.
+    fn segments(curve: &Timecurve) -> Vec<Bezier> {
+        let to_vector = |p: &Position| Vector2::new(p.get_x(), p.get_y());
+
+        curve
+            .get_points()
+            .windows(2)
+            .map(|pair| {
+                let (a, b) = (&pair[0], &pair[1]);
+                Bezier {
+                    points: [
+                        to_vector(a.get_pos()),
+                        to_vector(a.get_c_next().unwrap_or(a.get_pos())),
+                        to_vector(b.get_c_prev().unwrap_or(b.get_pos())),
+                        to_vector(b.get_pos()),
+                    ],
+                    start: 0.0,
+                    end: 1.0,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the bounding box (min, max) of the control polygon, which contains the segment.
+    fn bounds(&self) -> (Vector2<f64>, Vector2<f64>) {
+        let min = self.points.iter().fold(self.points[0], |acc, p| acc.inf(p));
+        let max = self.points.iter().fold(self.points[0], |acc, p| acc.sup(p));
+        (min, max)
+    }
+
+    /// Returns the largest distance of the control points to the chord of the segment.
+    fn flatness(&self) -> f64 {
+        let chord = self.points[3] - self.points[0];
+        let length = chord.norm();
+        let distance = |p: &Vector2<f64>| {
+            let v = p - self.points[0];
+            if length > 0.0 {
+                (chord.x * v.y - chord.y * v.x).abs() / length
+            } else {
+                v.norm()
+            }
+        };
+
+        distance(&self.points[1]).max(distance(&self.points[2]))
+    }
+
+    /// Returns the point of the segment at a parameter between 0.0 and 1.0.
+    fn point(&self, t: f64) -> Vector2<f64> {
+        let [p0, p1, p2, p3] = self.points;
+        let s = 1.0 - t;
+        p0 * (s * s * s) + p1 * (3.0 * s * s * t) + p2 * (3.0 * s * t * t) + p3 * (t * t * t)
+    }
+
+    /// Returns the derivative of the segment at a parameter between 0.0 and 1.0.
+    fn tangent(&self, t: f64) -> Vector2<f64> {
+        let [p0, p1, p2, p3] = self.points;
+        let s = 1.0 - t;
+        ((p1 - p0) * (s * s) + (p2 - p1) * (2.0 * s * t) + (p3 - p2) * (t * t)) * 3.0
+    }
+
+    /// Splits the segment in two halves with the algorithm of de Casteljau.
+    fn split(&self) -> (Bezier, Bezier) {
+        let [p0, p1, p2, p3] = self.points;
+        let a = (p0 + p1) / 2.0;
+        let b = (p1 + p2) / 2.0;
+        let c = (p2 + p3) / 2.0;
+        let d = (a + b) / 2.0;
+        let e = (b + c) / 2.0;
+        let f = (d + e) / 2.0;
+        let middle = (self.start + self.end) / 2.0;
+
+        (
+            Bezier {
+                points: [p0, a, d, f],
+                start: self.start,
+                end: middle,
+            },
+            Bezier {
+                points: [f, e, c, p3],
+                start: middle,
+                end: self.end,
+            },
+        )
+    }
+}
+
+/// Computes the crossings between the segments of two curves, or of a curve with itself.
+///
+/// ### Arguments
+///
+/// * `first` - The first curve and its index in the set.
+/// * `second` - The second curve and its index in the set. If both indices are equal, the self-crossings of the curve
+///   are computed, including those of a segment with itself or with the next one,
+///   but not the common point where consecutive segments always meet.
+///
+/// ### Returns
+///
+/// The crossings, ordered by segments.
+pub(crate) fn curve_crossings(
+    first: (&Timecurve, usize),
+    second: (&Timecurve, usize),
+) -> Vec<Crossing> {
+    let is_self = first.1 == second.1;
+    let a = Bezier::segments(first.0);
+    let b = Bezier::segments(second.0);
+
+    // the tolerance is relative to the size of the curves
+    let (min, max) = a
+        .iter()
+        .chain(b.iter())
+        .map(|s| s.bounds())
+        .fold(
+            None,
+            |acc: Option<(Vector2<f64>, Vector2<f64>)>, (min, max)| {
+                Some(match acc {
+                    Some((lo, hi)) => (lo.inf(&min), hi.sup(&max)),
+                    None => (min, max),
+                })
+            },
+        )
+        .unwrap_or((Vector2::zeros(), Vector2::zeros()));
+    let tolerance = FLATNESS * (max - min).norm().max(f64::MIN_POSITIVE);
+
+    let mut crossings = Vec::new();
+    for (i, s) in a.iter().enumerate() {
+        let others = if is_self { i } else { 0 };
+        for (j, t) in b.iter().enumerate().skip(others) {
+            let mut found = Vec::new();
+            if is_self && j == i {
+                self_intersect(s, tolerance, 0, &mut found);
+            } else if is_self && j == i + 1 {
+                intersect_apart(s, t, tolerance, 0, &mut found);
+            } else {
+                intersect(s, t, tolerance, 0, &mut found);
+            }
+
+            for (u, v, position) in found {
+                let (u, v, position) = refine(s, t, (u, v, position));
+                // the same crossing can be found at the boundary of two subdivisions,
+                // or of two consecutive segments if it is at their common point
+                let duplicate = crossings.iter().any(|c: &Crossing| {
+                    c.segments.0.abs_diff(i) <= 1
+                        && c.segments.1.abs_diff(j) <= 1
+                        && Vector2::new(c.position.0, c.position.1).metric_distance(&position)
+                            <= 10.0 * tolerance
+                });
+                if !duplicate {
+                    crossings.push(Crossing {
+                        curves: (first.1, second.1),
+                        segments: (i, j),
+                        parameters: (u, v),
+                        position: (position.x, position.y),
+                    });
+                }
+            }
+        }
+    }
+
+    crossings
+}
+
+/// Finds the intersections of two bezier segments by subdividing them until they are straight.
+///
+/// ### Arguments
+///
+/// * `a`, `b` - The bezier segments.
+/// * `tolerance` - The flatness under which a segment is considered straight.
+/// * `depth` - The number of subdivisions so far.
+/// * `found` - The parameters of the intersections along both original segments, and their positions.
+fn intersect(
+    a: &Bezier,
+    b: &Bezier,
+    tolerance: f64,
+    depth: usize,
+    found: &mut Vec<(f64, f64, Vector2<f64>)>,
+) {
+    let (a_min, a_max) = a.bounds();
+    let (b_min, b_max) = b.bounds();
+    let overlap = a_min.x <= b_max.x + tolerance
+        && b_min.x <= a_max.x + tolerance
+        && a_min.y <= b_max.y + tolerance
+        && b_min.y <= a_max.y + tolerance;
+    if !overlap {
+        return;
+    }
+
+    let a_flat = a.flatness() <= tolerance;
+    let b_flat = b.flatness() <= tolerance;
+    if (a_flat && b_flat) || depth >= MAX_DEPTH {
+        if let Some((u, v, position)) = chord_intersection(a, b) {
+            found.push((
+                a.start + u * (a.end - a.start),
+                b.start + v * (b.end - b.start),
+                position,
+            ));
+        }
+        return;
+    }
+
+    // only the curved segments are split
+    let a_halves = if a_flat {
+        vec![*a]
+    } else {
+        vec![a.split().0, a.split().1]
+    };
+    let b_halves = if b_flat {
+        vec![*b]
+    } else {
+        vec![b.split().0, b.split().1]
+    };
+    for a_half in &a_halves {
+        for b_half in &b_halves {
+            intersect(a_half, b_half, tolerance, depth + 1, found);
+        }
+    }
+}
+
+/// Same as `intersect`, for two segments that follow each other : the end of `a` is the start of `b`,
+/// where they meet without crossing, so the intersections at this point are dropped.
+fn intersect_apart(
+    a: &Bezier,
+    b: &Bezier,
+    tolerance: f64,
+    depth: usize,
+    found: &mut Vec<(f64, f64, Vector2<f64>)>,
+) {
+    let common = a.points[3];
+    let mut all = Vec::new();
+    intersect(a, b, tolerance, depth, &mut all);
+    found.extend(
+        all.into_iter()
+            .filter(|(_, _, position)| position.metric_distance(&common) > 10.0 * tolerance),
+    );
+}
+
+/// Finds the points where a bezier segment crosses itself, ie. where it makes a loop,
+/// by intersecting its halves with each other.
+///
+/// ### Arguments
+///
+/// * `a` - The bezier segment.
+/// * `tolerance` - The flatness under which a segment is considered straight, and can't cross itself.
+/// * `depth` - The number of subdivisions so far.
+/// * `found` - The two parameters of the intersections along the original segment, in increasing order, and their positions.
+fn self_intersect(
+    a: &Bezier,
+    tolerance: f64,
+    depth: usize,
+    found: &mut Vec<(f64, f64, Vector2<f64>)>,
+) {
+    if a.flatness() <= tolerance || depth >= MAX_DEPTH {
+        return;
+    }
+
+    let (first, second) = a.split();
+    self_intersect(&first, tolerance, depth + 1, found);
+    self_intersect(&second, tolerance, depth + 1, found);
+    intersect_apart(&first, &second, tolerance, depth + 1, found);
+}
+
+/// Improves the parameters of an intersection with Newton's method, as the chords of the flat pieces
+/// found by the subdivision give an accurate position but only approximate parameters.
+///
+/// ### Arguments
+///
+/// * `a`, `b` - The original bezier segments.
+/// * `intersection` - The parameters along both segments and the position of the intersection.
+///
+/// ### Returns
+///
+/// The refined intersection, or the given one if the method doesn't converge close to it.
+fn refine(
+    a: &Bezier,
+    b: &Bezier,
+    intersection: (f64, f64, Vector2<f64>),
+) -> (f64, f64, Vector2<f64>) {
+    const ITERATIONS: usize = 8;
+    // larger steps would jump to another intersection, or to u = v for a segment crossing itself
+    const MAX_STEP: f64 = 0.05;
+
+    let (u0, v0, _) = intersection;
+    let residual = |u: f64, v: f64| a.point(u) - b.point(v);
+    let (mut u, mut v) = (u0, v0);
+    for _ in 0..ITERATIONS {
+        let f = residual(u, v);
+        let (du, dv) = (a.tangent(u), -b.tangent(v));
+        let determinant = du.x * dv.y - du.y * dv.x;
+        if determinant == 0.0 {
+            break;
+        }
+        u -= (f.x * dv.y - f.y * dv.x) / determinant;
+        v -= (du.x * f.y - du.y * f.x) / determinant;
+    }
+
+    let converged = (0.0..=1.0).contains(&u)
+        && (0.0..=1.0).contains(&v)
+        && (u - u0).abs() <= MAX_STEP
+        && (v - v0).abs() <= MAX_STEP
+        && residual(u, v).norm() < residual(u0, v0).norm();
+    if converged {
+        (u, v, a.point(u))
+    } else {
+        intersection
+    }
+}
+
+/// Computes the intersection of the chords of two straight bezier segments.
+///
+/// ### Returns
+///
+/// The parameters of the intersection along both chords and its position,
+/// or `None` if the chords don't cross or are parallel, eg. if the segments overlap.
+fn chord_intersection(a: &Bezier, b: &Bezier) -> Option<(f64, f64, Vector2<f64>)> {
+    let (p, r) = (a.points[0], a.points[3] - a.points[0]);
+    let (q, s) = (b.points[0], b.points[3] - b.points[0]);
+
+    let cross = |v: Vector2<f64>, w: Vector2<f64>| v.x * w.y - v.y * w.x;
+    let denominator = cross(r, s);
+    if denominator == 0.0 {
+        return None;
+    }
+
+    let u = cross(q - p, s) / denominator;
+    let v = cross(q - p, r) / denominator;
+    if (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v) {
+        Some((u, v, p + r * u))
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        alignment::NoAlignment,
+        input::InputData,
+        interpolation::PolylineInterpolation,
+        normalisation::NoNormalisation,
+        projection::PCA,
+        timecurve::{TimecurveSet, TimecurveSetBuilder},
+    };
+
+    /// Creates a set whose points are at the given coordinates, up to a rotation and a reflection.
+    fn set_from_coordinates(json: &str, polyline: bool) -> TimecurveSet {
+        let input_data = InputData::from_str(json).unwrap();
+        let builder = TimecurveSetBuilder::new(PCA::new())
+            .with_alignment(NoAlignment::new())
+            .with_normalisation(NoNormalisation::new());
+        let builder = if polyline {
+            builder.with_interpolation(PolylineInterpolation::new())
+        } else {
+            builder
+        };
+        builder.build(&input_data).unwrap()
+    }
+
+    #[test]
+    fn self_crossing_of_a_loop() {
+        // (0, 0) -> (2, 2) -> (2, 0) -> (0, 2) crosses itself at (1, 1)
+        let json = r#"{
+            "features": [[0, 0], [2, 2], [2, 0], [0, 2]],
+            "data": [{ "name": "loop", "timelabels": ["1", "2", "3", "4"] }]
+        }"#;
+
+        let set = set_from_coordinates(json, true);
+        let crossings = set.get_curves()[0].compute_self_crossings();
+        assert_eq!(crossings.len(), 1);
+
+        let crossing = crossings[0];
+        assert!(crossing.is_self_crossing());
+        assert_eq!(crossing.get_segments(), (0, 2));
+        let (u, v) = crossing.get_parameters();
+        assert!((u - 0.5).abs() < 1e-9 && (v - 0.5).abs() < 1e-9);
+
+        // the position is the same on both segments
+        let curve = &set.get_curves()[0];
+        let on_first = curve.evaluate(u).unwrap();
+        let on_second = curve.evaluate(2.0 + v).unwrap();
+        assert!(on_first.distance(&on_second) < 1e-9);
+        assert!((crossing.get_position().0 - on_first.get_x()).abs() < 1e-9);
+
+        // the smooth curve crosses itself too
+        let set = set_from_coordinates(json, false);
+        assert_eq!(set.compute_crossings().len(), 1);
+    }
+
+    #[test]
+    fn self_crossings_of_consecutive_segments() {
+        // the smooth curve turns back sharply : the second segment crosses the first one, and loops over itself
+        let json = r#"{
+            "features": [[8.0, 0.2], [-4.3, -1.9], [4.9, -0.4], [4.0, -1.2]],
+            "data": [{ "name": "turn", "timelabels": ["1", "2", "3", "4"] }]
+        }"#;
+
+        let set = set_from_coordinates(json, false);
+        let curve = &set.get_curves()[0];
+        let crossings = curve.compute_self_crossings();
+        let segments: Vec<(usize, usize)> = crossings.iter().map(|c| c.get_segments()).collect();
+        assert!(segments.contains(&(0, 1)));
+        assert!(segments.contains(&(1, 1)));
+
+        for crossing in crossings {
+            let (i, j) = crossing.get_segments();
+            let (u, v) = crossing.get_parameters();
+            let on_first = curve.evaluate(i as f64 + u).unwrap();
+            let on_second = curve.evaluate(j as f64 + v).unwrap();
+            assert!(on_first.distance(&on_second) < 1e-9);
+            // the common point of consecutive segments isn't a crossing
+            assert!(on_first.distance(curve.get_points()[i + 1].get_pos()) > 1e-3);
+        }
+
+        // a crossing at a point of the curve is only reported once, not for both segments around the point
+        let json = r#"{
+            "features": [[0, 1], [2, 1], [1, 0], [1, 1], [0.5, 2]],
+            "data": [{ "name": "through", "timelabels": ["1", "2", "3", "4", "5"] }]
+        }"#;
+        let set = set_from_coordinates(json, true);
+        assert_eq!(set.get_curves()[0].compute_self_crossings().len(), 1);
+    }
+
+    #[test]
+    fn crossings_between_curves() {
+        // a horizontal line crossed by a vertical one and by a zigzag
+        let json = r#"{
+            "features": [[0, 0], [4, 0], [1, -1], [1, 1], [2, -1], [3, 1], [3.5, -1]],
+            "data": [
+                { "name": "horizontal", "timelabels": ["1", "2"] },
+                { "name": "vertical", "timelabels": ["1", "2"] },
+                { "name": "zigzag", "timelabels": ["1", "2", "3"] }
+            ]
+        }"#;
+
+        let set = set_from_coordinates(json, true);
+        let crossings = set.compute_crossings();
+
+        let between = |a: usize, b: usize| {
+            crossings
+                .iter()
+                .filter(|c| c.get_curves() == (a, b))
+                .count()
+        };
+        assert_eq!(between(0, 1), 1);
+        assert_eq!(between(0, 2), 2);
+        assert_eq!(between(1, 2), 0);
+        assert!(crossings.iter().all(|c| !c.is_self_crossing()));
+    }
+}
@@ -0,0 +1,193 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+//! Distance metrics between feature vectors, to build a distance matrix from numeric features.
+
+use crate::error::{TimecurveError, TimecurveErrorKind};
+use serde::{Deserialize, Serialize};
+
+/// The metrics that can be used to compare two feature vectors.
+///
+/// In the input file, the metric is given by its lowercase name, eg. `"metric": "cosine"`.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Metric {
+    /// The euclidean distance, √Σ(ai - bi)².
+    #[default]
+    Euclidean,
+    /// The cosine distance, 1 - a.b / (|a| |b|), between 0.0 and 2.0.
+    /// It only depends on the direction of the vectors, eg. the relative frequencies of words.
+    Cosine,
+    /// The manhattan distance, Σ|ai - bi|.
+    Manhattan,
+    /// The correlation distance, 1 - r where r is the Pearson correlation of the two vectors, between 0.0 and 2.0.
+    /// It is the cosine distance of the vectors centred on their mean.
+    Correlation,
+    /// The Chebyshev distance, max|ai - bi|.
+    Chebyshev,
+}
+
+impl Metric {
+    /// Computes the distance between two feature vectors of the same length.
+    ///
+    /// ### Arguments
+    ///
+    /// * `a` - The first vector.
+    /// * `b` - The second vector.
+    ///
+    /// ### Returns
+    ///
+    /// The distance between the two vectors.
+    /// For the cosine and correlation distances, a null (or constant) vector is at distance 0.0 of another null vector,
+    /// and at distance 1.0 of any other vector.
+    pub fn distance(&self, a: &[f64], b: &[f64]) -> f64 {
+        let differences = a.iter().zip(b).map(|(x, y)| (x - y).abs());
+
+        match self {
+            Metric::Euclidean => differences.map(|d| d * d).sum::<f64>().sqrt(),
+            Metric::Manhattan => differences.sum(),
+            Metric::Chebyshev => differences.fold(0.0, f64::max),
+            Metric::Cosine => cosine_distance(a, b),
+            Metric::Correlation => {
+                let centred = |v: &[f64]| {
+                    let mean = v.iter().sum::<f64>() / v.len().max(1) as f64;
+                    v.iter().map(|x| x - mean).collect::<Vec<f64>>()
+                };
+                cosine_distance(&centred(a), &centred(b))
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for Metric {
+    type Err = TimecurveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "euclidean" => Ok(Metric::Euclidean),
+            "cosine" => Ok(Metric::Cosine),
+            "manhattan" => Ok(Metric::Manhattan),
+            "correlation" => Ok(Metric::Correlation),
+            "chebyshev" => Ok(Metric::Chebyshev),
+            _ => Err(TimecurveError::new(
+                TimecurveErrorKind::InvalidParameter,
+                Some(&format!(
+                    "Unknown metric \"{}\", expected euclidean, cosine, manhattan, correlation or chebyshev",
+                    s
+                )),
+            )),
+        }
+    }
+}
+
+/// Computes the distance matrix of a list of feature vectors.
+///
+/// ### Arguments
+///
+/// * `features` - The feature vectors, one per timepoint.
+/// * `metric` - The metric comparing two vectors.
+///
+/// ### Returns
+///
+/// The symmetric distance matrix, or an `InvalidParameter` error if the vectors don't all have the same length
+/// or contain non-finite values.
+pub fn distance_matrix(
+    features: &[Vec<f64>],
+    metric: Metric,
+) -> Result<Vec<Vec<f64>>, TimecurveError> {
+    check_features(features)?;
+
+    let n = features.len();
+    let mut matrix = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let d = metric.distance(&features[i], &features[j]);
+            matrix[i][j] = d;
+            matrix[j][i] = d;
+        }
+    }
+
+    Ok(matrix)
+}
+
+/// Checks that the feature vectors all have the same length and only contain finite values.
+///
+/// ### Returns
+///
+/// The length of the vectors, or an `InvalidParameter` error.
+pub(crate) fn check_features(features: &[Vec<f64>]) -> Result<usize, TimecurveError> {
+    let length = features.first().map_or(0, |v| v.len());
+
+    for (i, vector) in features.iter().enumerate() {
+        if vector.len() != length {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::InvalidParameter,
+                Some(&format!(
+                    "Feature vector {} has {} values, the first one has {}",
+                    i,
+                    vector.len(),
+                    length
+                )),
+            ));
+        }
+        if vector.iter().any(|x| !x.is_finite()) {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::InvalidParameter,
+                Some(&format!("Feature vector {} has non-finite values", i)),
+            ));
+        }
+    }
+
+    Ok(length)
+}
+
+/// Computes 1 - a.b / (|a| |b|), see `Metric::Cosine`.
+fn cosine_distance(a: &[f64], b: &[f64]) -> f64 {
+    let dot: f64 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f64>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f64>().sqrt();
+
+    match (norm_a > 0.0, norm_b > 0.0) {
+        (true, true) => (1.0 - dot / (norm_a * norm_b)).clamp(0.0, 2.0),
+        (false, false) => 0.0,
+        _ => 1.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn metrics_give_expected_distances() {
+        let a = [1.0, 2.0, 3.0];
+        let b = [4.0, 6.0, 3.0];
+
+        assert_eq!(Metric::Euclidean.distance(&a, &b), 5.0);
+        assert_eq!(Metric::Manhattan.distance(&a, &b), 7.0);
+        assert_eq!(Metric::Chebyshev.distance(&a, &b), 4.0);
+        // same direction, different scale
+        assert!(Metric::Cosine.distance(&a, &[2.0, 4.0, 6.0]).abs() < 1e-12);
+        assert!((Metric::Cosine.distance(&[1.0, 0.0], &[0.0, 1.0]) - 1.0).abs() < 1e-12);
+        // perfectly anti-correlated
+        assert!((Metric::Correlation.distance(&a, &[3.0, 2.0, 1.0]) - 2.0).abs() < 1e-12);
+        assert_eq!(Metric::Cosine.distance(&[0.0, 0.0], &[0.0, 0.0]), 0.0);
+        assert_eq!("Chebyshev".parse::<Metric>().unwrap(), Metric::Chebyshev);
+    }
+
+    #[test]
+    fn distance_matrix_checks_features() {
+        let features = vec![vec![0.0, 0.0], vec![3.0, 4.0], vec![0.0, 1.0]];
+        let matrix = distance_matrix(&features, Metric::Euclidean).unwrap();
+
+        assert_eq!(matrix[0][1], 5.0);
+        assert_eq!(matrix[2][0], 1.0);
+        assert_eq!(matrix[1][1], 0.0);
+
+        assert!(distance_matrix(&[vec![0.0], vec![0.0, 1.0]], Metric::Euclidean).is_err());
+        assert!(distance_matrix(&[vec![f64::NAN]], Metric::Euclidean).is_err());
+    }
+}
@@ -24,6 +24,10 @@ pub enum TimecurveErrorKind {
     InvalidTimeLabel,
     /// An error occured in a python function passed to the library.
     PythonError,
+    /// A parameter given to an algorithm is invalid. Eg. an initial layout of the wrong size.
+    InvalidParameter,
+    /// A neighbourhood graph built from the distance matrix is not connected, so some distances can't be computed.
+    DisconnectedGraph,
 }
 
 impl std::fmt::Display for TimecurveError {
@@ -39,6 +43,9 @@ impl std::fmt::Display for TimecurveError {
                     TimecurveErrorKind::InvalidTimeLabel => "Timelabel is invalid !",
                     TimecurveErrorKind::PythonError =>
                         "/// An error occured in a python function passed to the library !",
+                    TimecurveErrorKind::InvalidParameter => "Parameter is invalid !",
+                    TimecurveErrorKind::DisconnectedGraph =>
+                        "Neighbourhood graph is disconnected !",
                 },
                 info
             )?;
@@ -47,6 +54,8 @@ impl std::fmt::Display for TimecurveError {
     }
 }
 
+impl std::error::Error for TimecurveError {}
+
 impl TimecurveError {
     /// Creates a new `TimecurveError` instance with the specified error kind and optional additional information.
     ///
@@ -5,7 +5,7 @@
 */
 
 use super::exporter::Exporter;
-use crate::timecurve::TimecurveSet;
+use crate::timecurve::{Position, TimecurveSet};
 
 /// An exporter to CSV format.
 pub struct CSVExporter {}
@@ -29,21 +29,32 @@ impl Exporter for CSVExporter {
     /// - `label`: The label of the point.
     /// - `x`: The x-coordinate of the point.
     /// - `y`: The y-coordinate of the point.
+    /// - `c_prev_x`, `c_prev_y`: The coordinates of the control point before the point, empty for the first point.
+    /// - `c_next_x`, `c_next_y`: The coordinates of the control point after the point, empty for the last point.
+    ///
+    /// Each pair of consecutive points is joined by the cubic bezier curve going through the control points between them.
     fn export(&self, timecurve_set: &TimecurveSet) -> String {
         let mut output = String::new();
 
         // CSV header
-        output.push_str("curve,label,x,y\n");
+        output.push_str("curve,label,x,y,c_prev_x,c_prev_y,c_next_x,c_next_y\n");
 
         // points values
         for curve in timecurve_set.get_curves() {
             for point in curve.get_points() {
+                let coordinates = |c: Option<&Position>| match c {
+                    Some(c) => format!("{},{}", c.get_x(), c.get_y()),
+                    None => String::from(","),
+                };
+
                 output.push_str(&format!(
-                    "{},{},{},{}\n",
+                    "{},{},{},{},{},{}\n",
                     curve.get_name(),
                     point.get_label(),
                     point.get_pos_x(),
                     point.get_pos_y(),
+                    coordinates(point.get_c_prev()),
+                    coordinates(point.get_c_next()),
                 ));
             }
         }
@@ -1,5 +1,6 @@
 mod csv_exporter;
 mod exporter;
+mod obj_exporter;
 mod svg_exporter;
 mod tikz_exporter;
 mod vega_lite_exporter;
@@ -7,6 +8,7 @@ mod vega_lite_exporter;
 // use pour pouvoir écrire importer avec exporters::Struct au lieu de exporters::fichier::Struct
 pub use csv_exporter::CSVExporter;
 pub use exporter::Exporter;
+pub use obj_exporter::OBJExporter;
 pub use svg_exporter::SVGExporter;
 pub use tikz_exporter::TikzExporter;
 pub use vega_lite_exporter::VegaLiteExporter;
@@ -0,0 +1,112 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+use super::exporter::Exporter;
+use crate::timecurve::TimecurveSet;
+
+/// An exporter to Wavefront OBJ format, to look at 3D timecurves in a 3D viewer like Blender or MeshLab.
+///
+/// Each curve is exported as an object made of a polyline sampling its bezier segments,
+/// and of the points of the curve. The vertices carry the color of the curve as an (r, g, b) extension,
+/// which most viewers support.
+/// 2D timecurves can also be exported, they are then drawn in the plane z = 0.
+pub struct OBJExporter {
+    /// The number of line segments used to draw each bezier segment of the curves.
+    samples: usize,
+}
+
+impl OBJExporter {
+    /// Creates a new instance of the OBJ exporter.
+    ///
+    /// ### Arguments
+    ///
+    /// * `samples` - The number of line segments used to draw each bezier segment of the curves.
+    ///   16 is a reasonable value, 1 draws straight lines between the points.
+    pub fn new(samples: usize) -> Self {
+        Self {
+            samples: samples.max(1),
+        }
+    }
+}
+
+impl Exporter for OBJExporter {
+    /// Exports the timecurve set to an OBJ string.
+    ///
+    /// ### Arguments
+    ///
+    /// * `timecurve_set` - The timecurve set to be exported.
+    ///
+    /// ### Returns
+    ///
+    /// The exported data as a string in OBJ format, with one object per curve.
+    fn export(&self, timecurve_set: &TimecurveSet) -> String {
+        let mut output = String::new();
+        output.push_str("# timecurves\n");
+
+        // OBJ indices are global to the file and start at 1
+        let mut vertex_count = 0;
+
+        for curve in timecurve_set.get_curves() {
+            let points = curve.get_points();
+            output.push_str(&format!(
+                "o {}\n",
+                curve
+                    .get_name()
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join("_")
+            ));
+
+            let mut line = Vec::new();
+            let mut timepoints = Vec::new();
+
+            for i in 0..points.len() {
+                // the last point has no segment after it
+                let steps = if i + 1 < points.len() {
+                    self.samples
+                } else {
+                    1
+                };
+
+                for step in 0..steps {
+                    let t = step as f64 / self.samples as f64;
+                    let position = match curve.evaluate(i as f64 + t) {
+                        Ok(p) => p,
+                        Err(_) => continue,
+                    };
+
+                    // the color goes from the color of the point to the one of the next point
+                    let from = points[i].get_color();
+                    let to = points[(i + 1).min(points.len() - 1)].get_color();
+                    let channel = |a: u8, b: u8| ((1.0 - t) * a as f64 + t * b as f64) / 255.0;
+
+                    output.push_str(&format!(
+                        "v {} {} {} {:.4} {:.4} {:.4}\n",
+                        position.get_x(),
+                        position.get_y(),
+                        position.get_z(),
+                        channel(from.0, to.0),
+                        channel(from.1, to.1),
+                        channel(from.2, to.2),
+                    ));
+
+                    vertex_count += 1;
+                    line.push(vertex_count.to_string());
+                    if step == 0 {
+                        timepoints.push(vertex_count.to_string());
+                    }
+                }
+            }
+
+            if line.len() > 1 {
+                output.push_str(&format!("l {}\n", line.join(" ")));
+            }
+            output.push_str(&format!("p {}\n", timepoints.join(" ")));
+        }
+
+        output
+    }
+}
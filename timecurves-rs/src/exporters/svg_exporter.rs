@@ -5,10 +5,19 @@
 */
 
 use super::Exporter;
+use crate::{patterns::Pattern, stability::StabilityReport};
 
 /// An exporter to SVG format.
 pub struct SVGExporter {
     thickness: f64,
+    /// If true, the points are coloured by the local error of the projection instead of the curve colors.
+    local_error_colors: bool,
+    /// If set, an uncertainty ellipse is drawn around each point from the spread of its positions.
+    uncertainty: Option<StabilityReport>,
+    /// If true, the crossings between the segments of the curves are circled.
+    crossings: bool,
+    /// The patterns highlighted on the curves.
+    patterns: Vec<Pattern>,
 }
 
 impl SVGExporter {
@@ -18,7 +27,62 @@ impl SVGExporter {
     ///
     /// * `thickness` - The thickness of the lines and points in the SVG. 1.0 is the default value.
     pub fn new(thickness: f64) -> Self {
-        return Self { thickness };
+        return Self {
+            thickness,
+            local_error_colors: false,
+            uncertainty: None,
+            crossings: false,
+            patterns: Vec::new(),
+        };
+    }
+
+    /// Colours the points by the local error of the projection, from green for the best placed points
+    /// to red for the worst placed ones, so that the unreliable parts of the curves stand out.
+    /// Each SVG circle element then also has a `data-local-error` attribute.
+    /// The set must be built with `TimecurveSetBuilder::with_quality` for the errors to be known.
+    ///
+    /// ### Arguments
+    ///
+    /// * `enabled` - Whether the points are coloured by local error.
+    pub fn with_local_error_colors(mut self, enabled: bool) -> Self {
+        self.local_error_colors = enabled;
+        self
+    }
+
+    /// Draws an uncertainty ellipse behind each point, whose axes are two standard deviations
+    /// of the positions of the point in a stability analysis, so that the unstable parts of the curves stand out.
+    /// Each SVG ellipse element also has a `data-variance` attribute.
+    ///
+    /// ### Arguments
+    ///
+    /// * `report` - The result of a `StabilityAnalysis` whose reference is the exported set.
+    pub fn with_uncertainty(mut self, report: StabilityReport) -> Self {
+        self.uncertainty = Some(report);
+        self
+    }
+
+    /// Circles the points where the curves cross themselves or each other, see `TimecurveSet::compute_crossings`.
+    /// Each SVG circle element of a crossing has a `data-crossing` attribute giving the indices of the curves
+    /// and segments crossing, as `curve:segment curve:segment`.
+    ///
+    /// ### Arguments
+    ///
+    /// * `enabled` - Whether the crossings are marked.
+    pub fn with_crossings(mut self, enabled: bool) -> Self {
+        self.crossings = enabled;
+        self
+    }
+
+    /// Highlights the given patterns behind the curves, each with a translucent stroke over its range of points
+    /// and a label with its kind, see `PatternDetector::detect`.
+    /// Each pattern is an SVG group element with `data-pattern` and `data-confidence` attributes.
+    ///
+    /// ### Arguments
+    ///
+    /// * `patterns` - The patterns detected on the exported set.
+    pub fn with_patterns(mut self, patterns: Vec<Pattern>) -> Self {
+        self.patterns = patterns;
+        self
     }
 }
 
@@ -42,12 +106,119 @@ impl Exporter for SVGExporter {
         1.0 + PADDING * 2.0,
         1.0 + PADDING * 2.0));
 
+        let max_error = timecurve_set
+            .get_curves()
+            .iter()
+            .flat_map(|c| c.get_points().iter())
+            .fold(0.0, |acc: f64, p| acc.max(p.get_local_error()));
+
+        // draw the uncertainty ellipses first so they are behind the curves
+        if let Some(report) = &self.uncertainty {
+            for curve in timecurve_set.get_curves().iter() {
+                for point in curve.get_points().iter() {
+                    let stability = match report.get(curve.get_name(), point.get_label()) {
+                        Some(stability) if stability.get_samples() > 1 => stability,
+                        _ => continue,
+                    };
+
+                    // axes of the ellipse from the eigendecomposition of the 2x2 covariance of x and y
+                    let covariance = stability.get_covariance();
+                    let (a, b, c) = (covariance[(0, 0)], covariance[(0, 1)], covariance[(1, 1)]);
+                    let mean = (a + c) / 2.0;
+                    let delta = (((a - c) / 2.0).powi(2) + b * b).sqrt();
+                    let angle = 0.5 * (2.0 * b).atan2(a - c);
+                    let color = point.get_color();
+
+                    output.push_str(&format!(
+                        "<ellipse cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\" transform=\"rotate({} {} {})\" fill=\"rgb({},{},{})\" fill-opacity=\"0.25\" data-variance=\"{}\"/>\n",
+                        point.get_pos_x() + PADDING,
+                        1.0 - point.get_pos_y() + PADDING,
+                        2.0 * (mean + delta).max(0.0).sqrt(),
+                        2.0 * (mean - delta).max(0.0).sqrt(),
+                        // the y axis is flipped, so the rotation is reversed
+                        -angle.to_degrees(),
+                        point.get_pos_x() + PADDING,
+                        1.0 - point.get_pos_y() + PADDING,
+                        color.0,
+                        color.1,
+                        color.2,
+                        stability.get_variance(),
+                    ));
+                }
+            }
+        }
+
+        // highlight the patterns behind the curves
+        for pattern in self.patterns.iter() {
+            let points = match timecurve_set.get_curves().get(pattern.get_curve()) {
+                Some(curve) => curve.get_points(),
+                None => continue,
+            };
+            let (start, end) = pattern.get_points();
+            if end >= points.len() || start > end {
+                continue;
+            }
+            let color = pattern.get_kind().get_color();
+
+            output.push_str(&format!(
+                "<g data-pattern=\"{}\" data-confidence=\"{}\">\n",
+                pattern.get_kind(),
+                pattern.get_confidence()
+            ));
+            if start == end {
+                output.push_str(&format!(
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"rgb({},{},{})\" fill-opacity=\"0.4\"/>\n",
+                    points[start].get_pos_x() + PADDING,
+                    1.0 - points[start].get_pos_y() + PADDING,
+                    self.thickness / 40.0,
+                    color.0,
+                    color.1,
+                    color.2,
+                ));
+            } else {
+                let mut path = format!(
+                    "M {} {}",
+                    points[start].get_pos_x() + PADDING,
+                    1.0 - points[start].get_pos_y() + PADDING
+                );
+                for pair in points[start..=end].windows(2) {
+                    let (p1, p2) = (&pair[0], &pair[1]);
+                    path.push_str(&format!(
+                        " C {} {} {} {} {} {}",
+                        p1.get_c_next().unwrap().get_x() + PADDING,
+                        1.0 - p1.get_c_next().unwrap().get_y() + PADDING,
+                        p2.get_c_prev().unwrap().get_x() + PADDING,
+                        1.0 - p2.get_c_prev().unwrap().get_y() + PADDING,
+                        p2.get_pos_x() + PADDING,
+                        1.0 - p2.get_pos_y() + PADDING,
+                    ));
+                }
+                output.push_str(&format!(
+                    "<path d=\"{}\" fill=\"none\" stroke=\"rgb({},{},{})\" stroke-opacity=\"0.4\" stroke-width=\"{}\" stroke-linecap=\"round\" stroke-linejoin=\"round\"/>\n",
+                    path,
+                    color.0,
+                    color.1,
+                    color.2,
+                    self.thickness / 30.0,
+                ));
+            }
+            output.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" font-size=\"{}\" fill=\"rgb({},{},{})\">{}</text>\n</g>\n",
+                points[start].get_pos_x() + PADDING,
+                1.0 - points[start].get_pos_y() + PADDING - self.thickness / 40.0,
+                self.thickness / 40.0,
+                color.0,
+                color.1,
+                color.2,
+                pattern.get_kind(),
+            ));
+        }
+
         // draw the lines first so they are in the background
         for curve in timecurve_set.get_curves().iter() {
-            // for each overlapping couple of 2 points
-            for i in 0..curve.get_points().len() - 1 {
-                let p1 = &curve.get_points()[i];
-                let p2 = &curve.get_points()[i + 1];
+            // for each overlapping couple of 2 points, none for curves of a single point
+            for pair in curve.get_points().windows(2) {
+                let (p1, p2) = (&pair[0], &pair[1]);
 
                 // draw the spline between the two points
                 output.push_str(&format!(
@@ -69,15 +240,30 @@ impl Exporter for SVGExporter {
 
             // draw the points last so they sit on top of the lines
             for point in curve.get_points().iter() {
+                let (color, error_attribute) = if self.local_error_colors {
+                    let u = if max_error > 0.0 {
+                        point.get_local_error() / max_error
+                    } else {
+                        0.0
+                    };
+                    (
+                        error_color(u),
+                        format!(" data-local-error=\"{}\"", point.get_local_error()),
+                    )
+                } else {
+                    (point.get_color(), String::new())
+                };
+
                 output.push_str(&format!(
-                    "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"rgb({},{},{})\" data-timelabel=\"{}\"/>\n",
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"rgb({},{},{})\" data-timelabel=\"{}\"{}/>\n",
                     point.get_pos_x() + PADDING,
                     1.0 - point.get_pos_y() + PADDING,
                     self.thickness / 120.0,
-                    point.get_color().0,
-                    point.get_color().1,
-                    point.get_color().2,
+                    color.0,
+                    color.1,
+                    color.2,
                     point.get_label(),
+                    error_attribute,
                 ));
             }
 
@@ -103,9 +289,46 @@ impl Exporter for SVGExporter {
             } */
         }
 
+        // circle the crossings on top of everything
+        if self.crossings {
+            for crossing in timecurve_set.compute_crossings() {
+                let (x, y) = crossing.get_position();
+                let (first, second) = crossing.get_curves();
+                let (i, j) = crossing.get_segments();
+                output.push_str(&format!(
+                    "<circle cx=\"{}\" cy=\"{}\" r=\"{}\" fill=\"none\" stroke=\"black\" stroke-width=\"{}\" data-crossing=\"{}:{} {}:{}\"/>\n",
+                    x + PADDING,
+                    1.0 - y + PADDING,
+                    self.thickness / 80.0,
+                    self.thickness / 400.0,
+                    first,
+                    i,
+                    second,
+                    j,
+                ));
+            }
+        }
+
         // svg closing tags
         output.push_str("</svg>");
 
         return output;
     }
 }
+
+/// Utility function that gives the color of a point from its local error.
+///
+/// ### Arguments
+///
+/// * `u` - The local error divided by the largest one. Should be between 0.0 and 1.0.
+///
+/// ### Returns
+///
+/// A RGB tuple going from green for 0.0 to red for 1.0, through yellow.
+fn error_color(u: f64) -> (u8, u8, u8) {
+    let u = u.clamp(0.0, 1.0);
+    let red = (2.0 * u).min(1.0);
+    let green = (2.0 * (1.0 - u)).min(1.0);
+
+    ((red * 220.0) as u8, (green * 200.0) as u8, 40)
+}
@@ -6,7 +6,7 @@
 * SPDX-License-Identifier: BSD-3-Clause
 */
 
-use crate::timecurve::TimecurveSet;
+use crate::{patterns::Pattern, timecurve::TimecurveSet};
 
 use super::Exporter;
 
@@ -14,6 +14,10 @@ use super::Exporter;
 pub struct TikzExporter {
     drawing_size: f64,
     thickness: f64,
+    /// If true, the crossings between the segments of the curves are circled.
+    crossings: bool,
+    /// The patterns highlighted on the curves.
+    patterns: Vec<Pattern>,
 }
 
 impl TikzExporter {
@@ -27,8 +31,31 @@ impl TikzExporter {
         return Self {
             drawing_size,
             thickness,
+            crossings: false,
+            patterns: Vec::new(),
         };
     }
+
+    /// Circles the points where the curves cross themselves or each other, see `TimecurveSet::compute_crossings`.
+    ///
+    /// ### Arguments
+    ///
+    /// * `enabled` - Whether the crossings are marked.
+    pub fn with_crossings(mut self, enabled: bool) -> Self {
+        self.crossings = enabled;
+        self
+    }
+
+    /// Highlights the given patterns behind the curves, each with a translucent stroke over its range of points
+    /// and a label with its kind, see `PatternDetector::detect`.
+    ///
+    /// ### Arguments
+    ///
+    /// * `patterns` - The patterns detected on the exported set.
+    pub fn with_patterns(mut self, patterns: Vec<Pattern>) -> Self {
+        self.patterns = patterns;
+        self
+    }
 }
 
 impl Exporter for TikzExporter {
@@ -55,12 +82,69 @@ impl Exporter for TikzExporter {
             self.drawing_size
         ));
 
+        // highlight the patterns behind the curves
+        for pattern in self.patterns.iter() {
+            let points = match timecurve_set.get_curves().get(pattern.get_curve()) {
+                Some(curve) => curve.get_points(),
+                None => continue,
+            };
+            let (start, end) = pattern.get_points();
+            if end >= points.len() || start > end {
+                continue;
+            }
+            let color = pattern.get_kind().get_color();
+            let color = format!(
+                "{{rgb, 255:red, {}; green, {}; blue, {}}}",
+                color.0, color.1, color.2
+            );
+
+            if start == end {
+                output.push_str(&format!(
+                    "\\fill[color={}, opacity=0.4] ({},{}) circle ({});\n",
+                    color,
+                    points[start].get_pos_x() * self.drawing_size,
+                    points[start].get_pos_y() * self.drawing_size,
+                    point_width * 3.0
+                ));
+            } else {
+                let mut path = format!(
+                    "({},{})",
+                    points[start].get_pos_x() * self.drawing_size,
+                    points[start].get_pos_y() * self.drawing_size
+                );
+                for pair in points[start..=end].windows(2) {
+                    let (p1, p2) = (&pair[0], &pair[1]);
+                    path.push_str(&format!(
+                        " .. controls ({},{}) and ({},{}) .. ({},{})",
+                        p1.get_c_next().unwrap().get_x() * self.drawing_size,
+                        p1.get_c_next().unwrap().get_y() * self.drawing_size,
+                        p2.get_c_prev().unwrap().get_x() * self.drawing_size,
+                        p2.get_c_prev().unwrap().get_y() * self.drawing_size,
+                        p2.get_pos_x() * self.drawing_size,
+                        p2.get_pos_y() * self.drawing_size,
+                    ));
+                }
+                output.push_str(&format!(
+                    "\\draw[line width={:.4}cm, color={}, opacity=0.4, line cap=round, line join=round] {};\n",
+                    line_width * 5.0,
+                    color,
+                    path
+                ));
+            }
+            output.push_str(&format!(
+                "\\node[above, color={}, font=\\scriptsize] at ({},{}) {{{}}};\n",
+                color,
+                points[start].get_pos_x() * self.drawing_size,
+                points[start].get_pos_y() * self.drawing_size + point_width * 2.0,
+                pattern.get_kind()
+            ));
+        }
+
         // draw the lines first so they are in the background
         for curve in timecurve_set.get_curves().iter() {
-            // for each overlapping couple of 2 points
-            for i in 0..curve.get_points().len() - 1 {
-                let p1 = &curve.get_points()[i];
-                let p2 = &curve.get_points()[i + 1];
+            // for each overlapping couple of 2 points, none for curves of a single point
+            for pair in curve.get_points().windows(2) {
+                let (p1, p2) = (&pair[0], &pair[1]);
 
                 // draw the spline between the two points
                 output.push_str(&format!(
@@ -96,6 +180,19 @@ impl Exporter for TikzExporter {
             }
         }
 
+        // circle the crossings on top of everything
+        if self.crossings {
+            for crossing in timecurve_set.compute_crossings() {
+                let (x, y) = crossing.get_position();
+                output.push_str(&format!(
+                    "\\draw[black, thick] ({},{}) circle ({});\n",
+                    x * self.drawing_size,
+                    y * self.drawing_size,
+                    point_width * 1.5
+                ));
+            }
+        }
+
         // end of file
         output.push_str("\\end{tikzpicture}\n");
 
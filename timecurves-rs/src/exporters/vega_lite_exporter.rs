@@ -10,7 +10,12 @@ use crate::timecurve::TimecurveSet;
 
 use super::Exporter;
 
+/// The number of line segments used to draw each bezier segment of the curves,
+/// as Vega-Lite can't draw bezier curves.
+const SAMPLES: usize = 16;
+
 /// An exporter to Vega-Lite format.
+/// The curves are drawn by sampling their bezier segments, so they match the other exporters.
 pub struct VegaLiteExporter {
     size: u64,
 }
@@ -39,7 +44,7 @@ impl Exporter for VegaLiteExporter {
     fn export(&self, timecurve_set: &TimecurveSet) -> String {
         let mut output = String::new();
 
-        let data = json!({
+        let points = json!({
             "values": timecurve_set.get_curves().iter().flat_map(|curve| {
                 curve.get_points().iter().map(|point| {
                     json!({"curve" : curve.get_name(), "x": point.get_pos_x(), "y": point.get_pos_y(), "label": point.get_label(), "t": point.get_t()})
@@ -47,34 +52,64 @@ impl Exporter for VegaLiteExporter {
             }).collect::<Vec<serde_json::Value>>()
         });
 
+        // the samples of the curves, in the order they are joined
+        let lines = json!({
+            "values": timecurve_set.get_curves().iter().flat_map(|curve| {
+                let segments = curve.get_points().len().saturating_sub(1);
+                (0..=segments * SAMPLES).filter_map(move |step| {
+                    let position = curve.evaluate(step as f64 / SAMPLES as f64).ok()?;
+                    Some(json!({"curve" : curve.get_name(), "x": position.get_x(), "y": position.get_y(), "order": step}))
+                })
+            }).collect::<Vec<serde_json::Value>>()
+        });
+
+        let x = json!({
+            "field": "x", "type": "quantitative",
+            "scale": {"domain": [0, 1]}
+        });
+        let y = json!({
+            "field": "y", "type": "quantitative",
+            "scale": {"domain": [0, 1]}
+        });
+
         let vega_object = json!({
             "$schema" : "https://vega.github.io/schema/vega-lite/v5.json",
             "width": self.size,
             "height": self.size,
-            "data" : data,
-            "mark": {
-                "type": "line",
-                "point": {"size" : 50},
-                "interpolate":"catmull-rom"
-            },
-            "params": [{
-                "name": "grid",
-                "select": "interval",
-                "bind": "scales"
-            }],
-            "encoding": {
-                "x": {
-                  "field": "x", "type": "quantitative",
-                  "scale": {"domain": [0, 1]}
-                },
-                "y": {
-                  "field": "y", "type": "quantitative",
-                  "scale": {"domain": [0, 1]}
+            "layer": [
+                {
+                    "data" : lines,
+                    "mark": {
+                        "type": "line",
+                        "interpolate":"linear"
+                    },
+                    "params": [{
+                        "name": "grid",
+                        "select": "interval",
+                        "bind": "scales"
+                    }],
+                    "encoding": {
+                        "x": x,
+                        "y": y,
+                        "color": {"field": "curve", "type": "nominal"},
+                        "order": {"field": "order"}
+                    }
                 },
-                "color": {"field": "curve", "type": "nominal"},
-                "order": {"field": "t"},
-                "tooltip": {"field" : "label"}
-            },
+                {
+                    "data" : points,
+                    "mark": {
+                        "type": "point",
+                        "filled": true,
+                        "size" : 50
+                    },
+                    "encoding": {
+                        "x": x,
+                        "y": y,
+                        "color": {"field": "curve", "type": "nominal"},
+                        "tooltip": {"field" : "label"}
+                    }
+                }
+            ],
         });
 
         output.push_str(&serde_json::to_string_pretty(&vega_object).unwrap());
@@ -4,7 +4,11 @@
 * SPDX-License-Identifier: BSD-3-Clause
 */
 
-use serde::{Deserialize, Serialize};
+use crate::{
+    distance::{distance_matrix, Metric},
+    error::TimecurveError,
+};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::error::Error;
 
 /// Structure representing a single dataset.
@@ -43,20 +47,117 @@ impl Dataset {
     }
 }
 
+/// Structure representing a point pinned to fixed coordinates, for anchored projection algorithms.
+///
+/// The point is given either by its row in the distance matrix (`index`),
+/// or by its timelabel (`label`) and the name of its dataset (`dataset`), which can be omitted if the label is unique.
+/// The coordinates are in the unit of the distances of the matrix.
+///
+///  The structure of `Anchor` directly corresponds to the JSON structure of the entries of the optional `anchors` list
+/// of the input file, eg. `{ "dataset": "name1", "label": "2014-02-21 00:00:00.0", "x": 0.0, "y": 0.0 }`.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Anchor {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    index: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dataset: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    x: f64,
+    y: f64,
+}
+
+impl Anchor {
+    /// Creates a new `Anchor` pinning the point of a row of the distance matrix.
+    ///
+    /// ### Arguments
+    ///
+    /// * `index` - The row of the distance matrix.
+    /// * `x` - The x coordinate of the point.
+    /// * `y` - The y coordinate of the point.
+    pub fn at_index(index: usize, x: f64, y: f64) -> Self {
+        Self {
+            index: Some(index),
+            dataset: None,
+            label: None,
+            x,
+            y,
+        }
+    }
+
+    /// Creates a new `Anchor` pinning the point with the given timelabel.
+    ///
+    /// ### Arguments
+    ///
+    /// * `dataset` - The name of the dataset of the point, or `None` if the label is unique among all datasets.
+    /// * `label` - The timelabel of the point.
+    /// * `x` - The x coordinate of the point.
+    /// * `y` - The y coordinate of the point.
+    pub fn at_label(dataset: Option<&str>, label: &str, x: f64, y: f64) -> Self {
+        Self {
+            index: None,
+            dataset: dataset.map(str::to_string),
+            label: Some(label.to_string()),
+            x,
+            y,
+        }
+    }
+
+    /// Returns the row of the distance matrix of the pinned point, if it is given by its index.
+    pub fn get_index(&self) -> Option<usize> {
+        self.index
+    }
+
+    /// Returns the name of the dataset of the pinned point, if it is given.
+    pub fn get_dataset(&self) -> Option<&str> {
+        self.dataset.as_deref()
+    }
+
+    /// Returns the timelabel of the pinned point, if it is given by its label.
+    pub fn get_label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// Returns the x coordinate of the pinned point.
+    pub fn get_x(&self) -> f64 {
+        self.x
+    }
+
+    /// Returns the y coordinate of the pinned point.
+    pub fn get_y(&self) -> f64 {
+        self.y
+    }
+}
+
 /// Structure representing the parsed input file.
 ///
-/// It is composed of a distance matrix and a list of datasets. The structure of `Input`
+/// It is composed of a distance matrix, or of feature vectors from which the matrix is computed, and a list of datasets.
+/// The structure of `Input`
 /// directly corresponds to the JSON structure of the input file, which allows for easy parsing
 /// thanks to the `serde_json` library.
 ///
 /// # Structure
 /// - `distance_matrix`: A two-dimensional array representing the distances between all points of all datasets.
+///   Distances that are unknown, eg. too expensive to compute, can be `null` in the JSON file.
+///   They are stored as NaN in the matrix, see `get_missing_distances`.
+///   It can be omitted if `features` is given.
+/// - `features`: An optional array of numeric vectors, one per point, in the same order as the rows of the matrix.
+///   If the distance matrix is omitted, it is computed from the vectors with `metric`.
+/// - `metric`: The metric comparing the feature vectors, eg. `"cosine"`, see `Metric`. Euclidean if omitted.
 /// - `datasets`: A list of datasets. Each dataset is represented as a separate entity.
+/// - `anchors`: An optional list of points pinned to fixed coordinates, see `Anchor`.
 ///
 #[derive(Serialize, Deserialize)]
 pub struct InputData {
+    #[serde(default, deserialize_with = "deserialize_distance_matrix")]
     distancematrix: Vec<Vec<f64>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    features: Option<Vec<Vec<f64>>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    metric: Option<Metric>,
     data: Vec<Dataset>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    anchors: Vec<Anchor>,
 }
 
 impl InputData {
@@ -71,7 +172,7 @@ impl InputData {
     /// Returns a `Result` containing the parsed `InputData` object or an error if parsing fails.
     pub fn from_str(string: &str) -> Result<Self, Box<dyn Error>> {
         let input: Self = serde_json::from_str(string)?;
-        Ok(input)
+        Ok(input.with_computed_distances()?)
     }
 
     /// Creates a new `InputData` object by parsing the contents of a JSON file.
@@ -86,7 +187,7 @@ impl InputData {
     pub fn from_filename(filename: &str) -> Result<Self, Box<dyn Error>> {
         let file = std::fs::read_to_string(filename)?;
         let input: Self = serde_json::from_str(&file)?;
-        Ok(input)
+        Ok(input.with_computed_distances()?)
     }
 
     /// Creates a new `InputData` object from a distance matrix and a list of datasets.
@@ -102,17 +203,129 @@ impl InputData {
     pub fn from(dmatrix: Vec<Vec<f64>>, datasets: Vec<Dataset>) -> Self {
         InputData {
             distancematrix: dmatrix,
+            features: None,
+            metric: None,
             data: datasets,
+            anchors: Vec::new(),
         }
     }
 
+    /// Creates a new `InputData` object from feature vectors and a list of datasets.
+    ///
+    /// ### Arguments
+    ///
+    /// * `features` - One numeric vector per point, in the order of the timelabels of the datasets.
+    /// * `datasets` - A vector of `Dataset` objects.
+    /// * `metric` - The metric used to compute the distance matrix from the vectors.
+    ///
+    /// ### Returns
+    ///
+    /// Returns a new `InputData` object with the distance matrix of the vectors,
+    /// or an error if the vectors don't all have the same length or contain non-finite values.
+    pub fn from_features(
+        features: Vec<Vec<f64>>,
+        datasets: Vec<Dataset>,
+        metric: Metric,
+    ) -> Result<Self, TimecurveError> {
+        Ok(InputData {
+            distancematrix: distance_matrix(&features, metric)?,
+            features: Some(features),
+            metric: Some(metric),
+            data: datasets,
+            anchors: Vec::new(),
+        })
+    }
+
+    /// Sets the feature vectors of the points, eg. to keep them after repairing the distance matrix.
+    /// The distance matrix is left unchanged.
+    ///
+    /// ### Arguments
+    ///
+    /// * `features` - One numeric vector per point, or `None`.
+    pub fn with_features(mut self, features: Option<Vec<Vec<f64>>>) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Computes the distance matrix from the feature vectors if the input file only has the vectors.
+    fn with_computed_distances(mut self) -> Result<Self, TimecurveError> {
+        if let Some(features) = &self.features {
+            if self.distancematrix.is_empty() {
+                self.distancematrix = distance_matrix(features, self.get_metric())?;
+            }
+        }
+        Ok(self)
+    }
+
+    /// Sets the points pinned to fixed coordinates.
+    ///
+    /// ### Arguments
+    ///
+    /// * `anchors` - The anchors, see `Anchor`.
+    pub fn with_anchors(mut self, anchors: Vec<Anchor>) -> Self {
+        self.anchors = anchors;
+        self
+    }
+
     /// Returns a reference to the distance matrix.
     pub fn get_distance_matrix(&self) -> &Vec<Vec<f64>> {
         &self.distancematrix
     }
 
+    /// Returns the feature vectors of the points, if the input has some.
+    pub fn get_features(&self) -> Option<&Vec<Vec<f64>>> {
+        self.features.as_ref()
+    }
+
+    /// Returns the metric comparing the feature vectors, euclidean if the input file doesn't give any.
+    pub fn get_metric(&self) -> Metric {
+        self.metric.unwrap_or_default()
+    }
+
     /// Returns a reference to the list of datasets.
     pub fn get_datasets(&self) -> &Vec<Dataset> {
         &self.data
     }
+
+    /// Returns the points pinned to fixed coordinates. Empty if the input file has no `anchors` list.
+    pub fn get_anchors(&self) -> &[Anchor] {
+        &self.anchors
+    }
+
+    /// Returns the cells of the distance matrix whose distance is unknown, ie. `null` in the JSON file or NaN.
+    ///
+    /// ### Returns
+    ///
+    /// The list of (row, column) of the missing distances, in reading order.
+    pub fn get_missing_distances(&self) -> Vec<(usize, usize)> {
+        crate::projection::missing_distances(&self.distancematrix)
+    }
+
+    /// Returns the weight of each cell of the distance matrix, for weighted projection algorithms.
+    ///
+    /// ### Returns
+    ///
+    /// A matrix of the same size as the distance matrix, with 0.0 for the missing distances and 1.0 for the others.
+    pub fn get_weights(&self) -> Vec<Vec<f64>> {
+        self.distancematrix
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|d| if d.is_nan() { 0.0 } else { 1.0 })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// Deserializes the distance matrix, replacing the `null` entries by NaN.
+fn deserialize_distance_matrix<'de, D>(deserializer: D) -> Result<Vec<Vec<f64>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let matrix: Vec<Vec<Option<f64>>> = Deserialize::deserialize(deserializer)?;
+    Ok(matrix
+        .into_iter()
+        .map(|row| row.into_iter().map(|d| d.unwrap_or(f64::NAN)).collect())
+        .collect())
 }
@@ -0,0 +1,427 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+//! Interpolation schemes computing the control points of the curves of a `TimecurveSet`.
+//!
+//! Whatever the scheme, a curve is drawn as one cubic Bezier segment between each pair of consecutive points,
+//! so all the exporters render the same geometry from the control points of the points.
+
+use crate::timecurve::Position;
+use nalgebra::Vector3;
+
+/// Trait representing a way to join the points of a curve by cubic Bezier segments.
+///
+/// The interpolation schemes are shared by the sets they are used for, so they must be thread-safe.
+pub trait Interpolation: Send + Sync {
+    /// Computes the control points of the Bezier segments joining the points.
+    ///
+    /// ### Arguments
+    ///
+    /// * `points` - The positions of the points of the curve, in the order they are joined.
+    ///
+    /// ### Returns
+    ///
+    /// The two control points of each segment, one segment less than there are points.
+    /// The first one is the control point after the start of the segment, the second one the control point before its end.
+    fn control_points(&self, points: &[Position]) -> Vec<(Position, Position)>;
+
+    /// Returns how many segments on each side of a point depend on its position,
+    /// so that only these are recomputed when a point is added to a curve.
+    ///
+    /// ### Returns
+    ///
+    /// The number of segments, or `None` if all the segments depend on all the points, which is the default.
+    fn locality(&self) -> Option<usize> {
+        None
+    }
+}
+
+impl<T: Interpolation + ?Sized> Interpolation for Box<T> {
+    fn control_points(&self, points: &[Position]) -> Vec<(Position, Position)> {
+        (**self).control_points(points)
+    }
+
+    fn locality(&self) -> Option<usize> {
+        (**self).locality()
+    }
+}
+
+/// Interpolation scheme of the time curves paper (Bach et al., 2016), which is the default one.
+/// The control points of a point are on a line parallel to the one joining its neighbours,
+/// at a distance proportional to the distance to each neighbour.
+/// Curves of 2 points are straight segments.
+pub struct BachInterpolation {
+    /// The smoothing parameter of the control points.
+    smoothing: f64,
+}
+
+impl BachInterpolation {
+    /// Creates a new instance of the scheme.
+    ///
+    /// ### Arguments
+    ///
+    /// * `smoothing` - 0.0 gives straight lines between the points, larger values give rounder curves.
+    ///   The paper uses 0.3.
+    pub fn new(smoothing: f64) -> Self {
+        Self { smoothing }
+    }
+
+    /// Returns the smoothing parameter of the control points.
+    pub fn get_smoothing(&self) -> f64 {
+        self.smoothing
+    }
+}
+
+impl Interpolation for BachInterpolation {
+    fn control_points(&self, points: &[Position]) -> Vec<(Position, Position)> {
+        if points.len() < 3 {
+            return PolylineInterpolation::new().control_points(points);
+        }
+
+        let sigma = self.smoothing;
+        let p: Vec<Vector3<f64>> = points.iter().map(to_vector).collect();
+        let last = p.len() - 1;
+
+        // previous and next control points of each point
+        let mut c_prev = p.clone();
+        let mut c_next = p.clone();
+
+        for i in 1..last {
+            // These control points are positioned so that the line joining them is parallel to (pi−1, pi+1).
+            // When the neighbours coincide, both control points fall back to the point itself.
+            let line = (p[i - 1] - p[i + 1])
+                .try_normalize(f64::EPSILON)
+                .unwrap_or_else(Vector3::zeros);
+
+            // The distance of ci,1 (resp. ci+1,0) to pi is set to the distance
+            // between pi and pi−1 (resp. pi+1) multiplied by a a smoothing parameter σ .
+            c_prev[i] = p[i] + line * (p[i - 1] - p[i]).norm() * sigma;
+            c_next[i] = p[i] - line * (p[i] - p[i + 1]).norm() * sigma;
+        }
+
+        // special case for the first and last points
+        // the cpoint for the first/last point is calculated based on the line between
+        // the first/last point and the next/previous control point
+        // or the point itself when it coincides with that control point
+        let towards = |from: &Vector3<f64>, to: &Vector3<f64>| {
+            (from - to)
+                .try_normalize(f64::EPSILON)
+                .unwrap_or_else(Vector3::zeros)
+        };
+        c_next[0] = p[0] - towards(&p[0], &c_prev[1]) * sigma;
+        c_prev[last] = p[last] - towards(&p[last], &c_next[last - 1]) * sigma;
+
+        (0..last)
+            .map(|i| (to_position(&c_next[i]), to_position(&c_prev[i + 1])))
+            .collect()
+    }
+
+    fn locality(&self) -> Option<usize> {
+        // the control points of a point depend on its neighbours
+        Some(2)
+    }
+}
+
+/// Interpolation scheme of the centripetal Catmull-Rom spline, converted to Bezier segments.
+/// Unlike the uniform Catmull-Rom spline, it never makes loops or cusps inside a segment,
+/// and it stays close to the points when they are unevenly spaced.
+/// The ends of the curve are extended by mirroring the second and second to last points.
+#[derive(Default)]
+pub struct CatmullRomInterpolation;
+
+impl CatmullRomInterpolation {
+    /// Creates a new instance of the scheme.
+    pub fn new() -> Self {
+        CatmullRomInterpolation
+    }
+}
+
+impl Interpolation for CatmullRomInterpolation {
+    fn control_points(&self, points: &[Position]) -> Vec<(Position, Position)> {
+        if points.len() < 2 {
+            return Vec::new();
+        }
+
+        let p: Vec<Vector3<f64>> = points.iter().map(to_vector).collect();
+        let last = p.len() - 1;
+        let before = 2.0 * p[0] - p[1];
+        let after = 2.0 * p[last] - p[last - 1];
+
+        (0..last)
+            .map(|i| {
+                let p0 = if i > 0 { p[i - 1] } else { before };
+                let (p1, p2) = (p[i], p[i + 1]);
+                let p3 = if i + 2 <= last { p[i + 2] } else { after };
+
+                // knot intervals of the centripetal parameterisation, ie. the square roots of the distances
+                let d1 = (p1 - p0).norm().sqrt();
+                let d2 = (p2 - p1).norm().sqrt();
+                let d3 = (p3 - p2).norm().sqrt();
+
+                // conversion to Bezier of Yuksel et al., 2011, with the segment parameterised on [0, 1]
+                let c1 = if d1 > 0.0 {
+                    (d1 * d1 * p2 - d2 * d2 * p0 + (2.0 * d1 * d1 + 3.0 * d1 * d2 + d2 * d2) * p1)
+                        / (3.0 * d1 * (d1 + d2))
+                } else {
+                    p1
+                };
+                let c2 = if d3 > 0.0 {
+                    (d3 * d3 * p1 - d2 * d2 * p3 + (2.0 * d3 * d3 + 3.0 * d3 * d2 + d2 * d2) * p2)
+                        / (3.0 * d3 * (d3 + d2))
+                } else {
+                    p2
+                };
+
+                (to_position(&c1), to_position(&c2))
+            })
+            .collect()
+    }
+
+    fn locality(&self) -> Option<usize> {
+        Some(2)
+    }
+}
+
+/// Interpolation scheme of the uniform cubic B-spline passing through the points, with natural ends,
+/// ie. the natural cubic spline. It is the smoothest scheme, as the curvature is continuous at the points,
+/// but moving a point moves the whole curve.
+#[derive(Default)]
+pub struct BSplineInterpolation;
+
+impl BSplineInterpolation {
+    /// Creates a new instance of the scheme.
+    pub fn new() -> Self {
+        BSplineInterpolation
+    }
+}
+
+impl Interpolation for BSplineInterpolation {
+    fn control_points(&self, points: &[Position]) -> Vec<(Position, Position)> {
+        if points.len() < 2 {
+            return Vec::new();
+        }
+
+        let p: Vec<Vector3<f64>> = points.iter().map(to_vector).collect();
+        let n = p.len();
+
+        // the derivatives d at the points are the solution of the tridiagonal system
+        //   2 d0 + d1 = 3 (p1 - p0)
+        //   di-1 + 4 di + di+1 = 3 (pi+1 - pi-1)
+        //   dn-2 + 2 dn-1 = 3 (pn-1 - pn-2)
+        // solved with the Thomas algorithm
+        let diagonal = |i: usize| if i == 0 || i == n - 1 { 2.0 } else { 4.0 };
+        let rhs = |i: usize| 3.0 * (p[(i + 1).min(n - 1)] - p[i.saturating_sub(1)]);
+
+        let mut upper = vec![0.0; n];
+        let mut d = vec![Vector3::zeros(); n];
+        upper[0] = 1.0 / diagonal(0);
+        d[0] = rhs(0) / diagonal(0);
+        for i in 1..n {
+            let m = diagonal(i) - upper[i - 1];
+            upper[i] = 1.0 / m;
+            d[i] = (rhs(i) - d[i - 1]) / m;
+        }
+        for i in (0..n - 1).rev() {
+            d[i] = d[i] - upper[i] * d[i + 1];
+        }
+
+        (0..n - 1)
+            .map(|i| {
+                (
+                    to_position(&(p[i] + d[i] / 3.0)),
+                    to_position(&(p[i + 1] - d[i + 1] / 3.0)),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Interpolation scheme of the cardinal spline, whose tension sets how tight the curve is around the points.
+/// The tangent at each point is parallel to the line joining its neighbours, like for the Catmull-Rom spline.
+pub struct CardinalInterpolation {
+    /// The tension of the spline, between 0.0 and 1.0.
+    tension: f64,
+}
+
+impl CardinalInterpolation {
+    /// Creates a new instance of the scheme.
+    ///
+    /// ### Arguments
+    ///
+    /// * `tension` - 0.0 gives the uniform Catmull-Rom spline, 1.0 gives straight lines between the points.
+    ///   Clamped to \[0, 1\].
+    pub fn new(tension: f64) -> Self {
+        Self {
+            tension: tension.clamp(0.0, 1.0),
+        }
+    }
+
+    /// Returns the tension of the spline.
+    pub fn get_tension(&self) -> f64 {
+        self.tension
+    }
+}
+
+impl Interpolation for CardinalInterpolation {
+    fn control_points(&self, points: &[Position]) -> Vec<(Position, Position)> {
+        if points.len() < 2 {
+            return Vec::new();
+        }
+
+        let p: Vec<Vector3<f64>> = points.iter().map(to_vector).collect();
+        let last = p.len() - 1;
+
+        // the tangents at the ends only depend on the segment they start
+        let tangents: Vec<Vector3<f64>> = (0..=last)
+            .map(|i| {
+                let previous = p[i.saturating_sub(1)];
+                let next = p[(i + 1).min(last)];
+                let span = if i == 0 || i == last { 1.0 } else { 2.0 };
+                (1.0 - self.tension) * (next - previous) / span
+            })
+            .collect();
+
+        (0..last)
+            .map(|i| {
+                (
+                    to_position(&(p[i] + tangents[i] / 3.0)),
+                    to_position(&(p[i + 1] - tangents[i + 1] / 3.0)),
+                )
+            })
+            .collect()
+    }
+
+    fn locality(&self) -> Option<usize> {
+        // the tangent at a point depends on its neighbours
+        Some(2)
+    }
+}
+
+/// Interpolation scheme joining the points by straight segments.
+/// The curve never overshoots the points, eg. to read the exact path between the timepoints.
+#[derive(Default)]
+pub struct PolylineInterpolation;
+
+impl PolylineInterpolation {
+    /// Creates a new instance of the scheme.
+    pub fn new() -> Self {
+        PolylineInterpolation
+    }
+}
+
+impl Interpolation for PolylineInterpolation {
+    fn control_points(&self, points: &[Position]) -> Vec<(Position, Position)> {
+        points
+            .windows(2)
+            .map(|w| {
+                let (a, b) = (to_vector(&w[0]), to_vector(&w[1]));
+                (
+                    to_position(&(a + (b - a) / 3.0)),
+                    to_position(&(a + (b - a) * 2.0 / 3.0)),
+                )
+            })
+            .collect()
+    }
+
+    fn locality(&self) -> Option<usize> {
+        Some(1)
+    }
+}
+
+/// Utility function that converts a position to a vector, for linear algebra.
+fn to_vector(p: &Position) -> Vector3<f64> {
+    Vector3::new(p.get_x(), p.get_y(), p.get_z())
+}
+
+/// Utility function that converts a vector back to a position.
+fn to_position(v: &Vector3<f64>) -> Position {
+    Position::new_3d(v.x, v.y, v.z)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Evaluates a Bezier segment at a given parameter.
+    fn bezier(a: &Position, segment: &(Position, Position), b: &Position, t: f64) -> Vector3<f64> {
+        let s = 1.0 - t;
+        s.powi(3) * to_vector(a)
+            + 3.0 * s * s * t * to_vector(&segment.0)
+            + 3.0 * s * t * t * to_vector(&segment.1)
+            + t.powi(3) * to_vector(b)
+    }
+
+    #[test]
+    fn schemes_are_continuous_at_the_points() {
+        let points = vec![
+            Position::new(0.0, 0.0),
+            Position::new(1.0, 2.0),
+            Position::new(3.0, 1.0),
+            Position::new(4.0, 4.0),
+            Position::new(2.0, 5.0),
+        ];
+        // all the schemes but the polyline
+        let schemes: Vec<Box<dyn Interpolation>> = vec![
+            Box::new(BachInterpolation::new(0.3)),
+            Box::new(CatmullRomInterpolation::new()),
+            Box::new(BSplineInterpolation::new()),
+            Box::new(CardinalInterpolation::new(0.5)),
+        ];
+
+        for scheme in schemes {
+            let segments = scheme.control_points(&points);
+            assert_eq!(segments.len(), points.len() - 1);
+
+            // the tangents before and after each inner point have the same direction
+            for i in 1..points.len() - 1 {
+                let before = (to_vector(&points[i]) - to_vector(&segments[i - 1].1)).normalize();
+                let after = (to_vector(&segments[i].0) - to_vector(&points[i])).normalize();
+                assert!((before - after).norm() < 1e-9);
+            }
+        }
+
+        assert_eq!(
+            PolylineInterpolation::new()
+                .control_points(&points[..1])
+                .len(),
+            0
+        );
+    }
+
+    #[test]
+    fn bspline_has_continuous_curvature() {
+        let points = vec![
+            Position::new(0.0, 0.0),
+            Position::new(1.0, 2.0),
+            Position::new(3.0, 1.0),
+            Position::new(4.0, 4.0),
+        ];
+        let segments = BSplineInterpolation::new().control_points(&points);
+
+        // second derivative of a Bezier segment at its ends
+        for i in 0..segments.len() - 1 {
+            let end = 6.0
+                * (to_vector(&segments[i].0) - 2.0 * to_vector(&segments[i].1)
+                    + to_vector(&points[i + 1]));
+            let start = 6.0
+                * (to_vector(&points[i + 1]) - 2.0 * to_vector(&segments[i + 1].0)
+                    + to_vector(&segments[i + 1].1));
+            assert!((end - start).norm() < 1e-9);
+        }
+
+        // a polyline stays on the segments, a cardinal spline of tension 1 too
+        let polyline = PolylineInterpolation::new().control_points(&points);
+        let tight = CardinalInterpolation::new(1.0).control_points(&points);
+        for t in [0.25, 0.5, 0.75] {
+            let on_line = to_vector(&points[0]) * (1.0 - t) + to_vector(&points[1]) * t;
+            assert!((bezier(&points[0], &polyline[0], &points[1], t) - on_line).norm() < 1e-9);
+            let tight_point = bezier(&points[0], &tight[0], &points[1], t);
+            let cross = (tight_point - to_vector(&points[0]))
+                .cross(&(to_vector(&points[1]) - to_vector(&points[0])));
+            assert!(cross.norm() < 1e-9);
+        }
+    }
+}
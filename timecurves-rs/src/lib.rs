@@ -1,5 +1,14 @@
+pub mod alignment;
+pub mod color;
+pub mod crossing;
+pub mod distance;
 pub mod error;
 pub mod exporters;
 pub mod input;
+pub mod interpolation;
+pub mod normalisation;
+pub mod patterns;
 pub mod projection;
+pub mod stability;
 pub mod timecurve;
+pub mod validation;
@@ -0,0 +1,133 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+//! Strategies choosing the scale and position of the curves of a `TimecurveSet`, after their alignment.
+
+use crate::{
+    alignment::Similarity,
+    error::{TimecurveError, TimecurveErrorKind},
+    timecurve::TimecurveSet,
+};
+use nalgebra::{Matrix3, Vector3};
+
+/// Trait representing a way to scale and move the curves of a set, eg. to fit them in the drawing area of the exporters.
+pub trait NormalisationStrategy {
+    /// Computes the transformation that normalises the set.
+    ///
+    /// ### Arguments
+    ///
+    /// * `timecurve_set` - The set to normalise, already aligned.
+    ///
+    /// ### Returns
+    ///
+    /// The transformation to apply to all the points of the set, or an error if the set can't be normalised.
+    fn transformation(&self, timecurve_set: &TimecurveSet) -> Result<Similarity, TimecurveError>;
+}
+
+impl<T: NormalisationStrategy + ?Sized> NormalisationStrategy for Box<T> {
+    fn transformation(&self, timecurve_set: &TimecurveSet) -> Result<Similarity, TimecurveError> {
+        (**self).transformation(timecurve_set)
+    }
+}
+
+/// Normalisation strategy bringing the points in the range \[0, 1\], which is what the exporters expect.
+/// All axes are scaled by the same factor to keep the aspect ratio, so the points only span \[0, 1\] along their widest axis.
+/// The z axis is only taken into account for 3D sets.
+#[derive(Default)]
+pub struct UnitSquareNormalisation;
+
+impl UnitSquareNormalisation {
+    /// Creates a new instance of the strategy.
+    pub fn new() -> Self {
+        UnitSquareNormalisation
+    }
+}
+
+impl NormalisationStrategy for UnitSquareNormalisation {
+    fn transformation(&self, timecurve_set: &TimecurveSet) -> Result<Similarity, TimecurveError> {
+        let mut min = Vector3::repeat(f64::INFINITY);
+        let mut max = Vector3::repeat(f64::NEG_INFINITY);
+
+        for point in timecurve_set
+            .get_curves()
+            .iter()
+            .flat_map(|c| c.get_points())
+        {
+            let p = Vector3::new(point.get_pos_x(), point.get_pos_y(), point.get_pos_z());
+            min = min.inf(&p);
+            max = max.sup(&p);
+        }
+
+        // we want to scale all points by the same factor
+        // on the x and y axis in order to keep the aspect ratio
+        // and not distort distances between points
+        let mut upper = max.x.max(max.y);
+        let mut lower = min.x.min(min.y);
+
+        // the z axis is only taken into account for 3D curves,
+        // so that 2D curves keep the same normalisation
+        if timecurve_set.is_3d() {
+            upper = upper.max(max.z);
+            lower = lower.min(min.z);
+        } else {
+            min.z = 0.0;
+        }
+
+        let range = upper - lower;
+        if !range.is_finite() {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::InvalidParameter,
+                Some("Overflow in normalisation, range is infinite"),
+            ));
+        }
+        // all the points are at the same position, they are only moved to the origin
+        let range = if range > 0.0 { range } else { 1.0 };
+
+        // the points are scaled before being moved, so the scale is slightly reduced
+        // for the rounding errors not to push the farthest points out of [0, 1]
+        let scale = (1.0 - 4.0 * f64::EPSILON) / range;
+        Ok(Similarity::new(Matrix3::identity(), scale, -(min * scale)))
+    }
+}
+
+/// Normalisation strategy scaling the set so that the distances between its points are in the unit of the input,
+/// eg. to draw several sets with the same scale. The set is not moved, so its points may lie outside of \[0, 1\].
+#[derive(Default)]
+pub struct DistanceNormalisation;
+
+impl DistanceNormalisation {
+    /// Creates a new instance of the strategy.
+    pub fn new() -> Self {
+        DistanceNormalisation
+    }
+}
+
+impl NormalisationStrategy for DistanceNormalisation {
+    fn transformation(&self, timecurve_set: &TimecurveSet) -> Result<Similarity, TimecurveError> {
+        Ok(Similarity::new(
+            Matrix3::identity(),
+            1.0 / timecurve_set.get_distance_scale(),
+            Vector3::zeros(),
+        ))
+    }
+}
+
+/// Normalisation strategy keeping the set as it is after the alignment.
+#[derive(Default)]
+pub struct NoNormalisation;
+
+impl NoNormalisation {
+    /// Creates a new instance of the strategy.
+    pub fn new() -> Self {
+        NoNormalisation
+    }
+}
+
+impl NormalisationStrategy for NoNormalisation {
+    fn transformation(&self, _timecurve_set: &TimecurveSet) -> Result<Similarity, TimecurveError> {
+        Ok(Similarity::identity())
+    }
+}
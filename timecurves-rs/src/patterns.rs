@@ -0,0 +1,600 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+//! Automatic detection of the visual patterns of time curves described in the time curves paper (Bach et al., 2016),
+//! eg. to triage many curves without looking at each of them.
+//!
+//! The patterns are detected from the original distances between the points, which don't depend on the projection,
+//! except for the U-turns, which are detected from the shape of the curves.
+
+use crate::{
+    error::{TimecurveError, TimecurveErrorKind},
+    timecurve::TimecurveSet,
+};
+use nalgebra::Vector2;
+
+/// The largest step, relative to the mean distance between the points of a curve, of a stagnation.
+const STAGNATION: f64 = 0.05;
+
+/// The largest diameter, relative to the mean distance between the points of a curve, of a cluster.
+const CLUSTER: f64 = 0.25;
+
+/// The smallest number of points of a cluster.
+const MIN_CLUSTER: usize = 4;
+
+/// The smallest step, relative to the median step of a curve, of a transition.
+const TRANSITION: f64 = 3.0;
+
+/// The smallest distance to the nearest point, relative to the median one of a curve, of an outlier.
+const OUTLIER: f64 = 3.0;
+
+/// The largest cosine of the angle between the two segments around a U-turn.
+const U_TURN: f64 = -0.8;
+
+/// The largest ratio between the distance of a point to the point two steps later
+/// and the length of the steps between them, for the points of an oscillation.
+const OSCILLATION: f64 = 0.5;
+
+/// The largest distance, relative to the mean distance between the points of a curve, between the ends of a cycle.
+const CYCLE_CLOSE: f64 = 0.1;
+
+/// The smallest distance, relative to the mean distance between the points of a curve, reached during a cycle.
+const CYCLE_AWAY: f64 = 0.5;
+
+/// The smallest number of steps of a cycle.
+const MIN_CYCLE: usize = 4;
+
+/// The visual patterns of time curves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PatternKind {
+    /// Several consecutive points close to each other, ie. a period of small changes around a state.
+    Cluster,
+    /// A large step between two consecutive points, ie. a sudden change.
+    Transition,
+    /// The curve comes back close to an earlier point after going away, ie. the data went back to an earlier state.
+    Cycle,
+    /// The curve goes back and forth between two states, eg. an edit war.
+    Oscillation,
+    /// The curve turns back on itself, ie. a change was undone.
+    UTurn,
+    /// A point far from all the other points of its curve.
+    Outlier,
+    /// Several consecutive points almost at the same position, ie. a period without changes.
+    Stagnation,
+}
+
+impl std::fmt::Display for PatternKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "{}",
+            match self {
+                PatternKind::Cluster => "cluster",
+                PatternKind::Transition => "transition",
+                PatternKind::Cycle => "cycle",
+                PatternKind::Oscillation => "oscillation",
+                PatternKind::UTurn => "u-turn",
+                PatternKind::Outlier => "outlier",
+                PatternKind::Stagnation => "stagnation",
+            }
+        )
+    }
+}
+
+impl PatternKind {
+    /// Returns the color used by the exporters to highlight the patterns of this kind.
+    ///
+    /// ### Returns
+    ///
+    /// A RGB tuple.
+    pub fn get_color(&self) -> (u8, u8, u8) {
+        match self {
+            PatternKind::Cluster => (31, 119, 180),
+            PatternKind::Transition => (214, 39, 40),
+            PatternKind::Cycle => (44, 160, 44),
+            PatternKind::Oscillation => (255, 127, 14),
+            PatternKind::UTurn => (148, 103, 189),
+            PatternKind::Outlier => (227, 119, 194),
+            PatternKind::Stagnation => (127, 127, 127),
+        }
+    }
+}
+
+/// Structure representing a pattern detected on a range of points of a curve.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    /// The kind of pattern.
+    kind: PatternKind,
+    /// The index of the curve in the set.
+    curve: usize,
+    /// The indices in the curve of the first and last points of the pattern.
+    points: (usize, usize),
+    /// The times of the first and last points of the pattern.
+    times: (i64, i64),
+    /// How clearly the pattern stands out, between 0.0 and 1.0.
+    confidence: f64,
+}
+
+impl Pattern {
+    /// Returns the kind of pattern.
+    pub fn get_kind(&self) -> PatternKind {
+        self.kind
+    }
+
+    /// Returns the index of the curve of the pattern in the set.
+    pub fn get_curve(&self) -> usize {
+        self.curve
+    }
+
+    /// Returns the indices in the curve of the first and last points of the pattern, both included.
+    pub fn get_points(&self) -> (usize, usize) {
+        self.points
+    }
+
+    /// Returns the times of the first and last points of the pattern, as unix timestamps.
+    pub fn get_time_range(&self) -> (i64, i64) {
+        self.times
+    }
+
+    /// Returns how clearly the pattern stands out, from 0.0 for a pattern at the limit of the detection thresholds
+    /// to 1.0 for an ideal one, eg. a stagnation without any change.
+    pub fn get_confidence(&self) -> f64 {
+        self.confidence
+    }
+}
+
+/// Structure detecting the patterns of the curves of a set.
+///
+/// The thresholds of the detection are relative to each curve, eg. to the mean distance between its points,
+/// so that curves of different scales can be compared.
+#[derive(Default)]
+pub struct PatternDetector {
+    /// The confidence under which the patterns are not reported.
+    min_confidence: f64,
+}
+
+impl PatternDetector {
+    /// Creates a new detector reporting all the patterns found.
+    pub fn new() -> Self {
+        Self {
+            min_confidence: 0.0,
+        }
+    }
+
+    /// Sets the confidence under which the patterns are not reported, eg. 0.5 to only keep the clearest ones.
+    ///
+    /// ### Arguments
+    ///
+    /// * `min_confidence` - The smallest confidence of the reported patterns, between 0.0 and 1.0.
+    pub fn with_min_confidence(mut self, min_confidence: f64) -> Self {
+        self.min_confidence = min_confidence;
+        self
+    }
+
+    /// Detects the patterns of the curves of a set.
+    ///
+    /// ### Arguments
+    ///
+    /// * `timecurve_set` - The set whose curves are analysed.
+    /// * `distance_matrix` - The distance matrix the set was created from. Missing distances (NaN) are ignored.
+    ///
+    /// ### Returns
+    ///
+    /// The patterns, ordered by curve then by first point, or a `MalformedDistanceMatrix` error
+    /// if the matrix doesn't have a row for each point of the set.
+    pub fn detect(
+        &self,
+        timecurve_set: &TimecurveSet,
+        distance_matrix: &[Vec<f64>],
+    ) -> Result<Vec<Pattern>, TimecurveError> {
+        let mut patterns = Vec::new();
+
+        for (c, curve) in timecurve_set.get_curves().iter().enumerate() {
+            let points = curve.get_points();
+            let indices: Vec<usize> = points.iter().map(|p| p.get_index()).collect();
+            let size = indices.iter().max().map_or(0, |i| i + 1);
+            if distance_matrix.len() < size
+                || indices.iter().any(|&i| distance_matrix[i].len() < size)
+            {
+                return Err(TimecurveError::new(
+                    TimecurveErrorKind::MalformedDistanceMatrix,
+                    Some(&format!(
+                        "The matrix has no distances for some points of curve '{}'",
+                        curve.get_name()
+                    )),
+                ));
+            }
+
+            let analysis = CurveAnalysis {
+                distances: indices
+                    .iter()
+                    .map(|&i| indices.iter().map(|&j| distance_matrix[i][j]).collect())
+                    .collect(),
+                positions: points
+                    .iter()
+                    .map(|p| Vector2::new(p.get_pos_x(), p.get_pos_y()))
+                    .collect(),
+            };
+
+            let mut found = analysis.detect();
+            found.sort_by_key(|(_, (start, end), _)| (*start, *end));
+            for (kind, (start, end), confidence) in found {
+                let confidence = confidence.clamp(0.0, 1.0);
+                if confidence >= self.min_confidence {
+                    patterns.push(Pattern {
+                        kind,
+                        curve: c,
+                        points: (start, end),
+                        times: (points[start].get_t(), points[end].get_t()),
+                        confidence,
+                    });
+                }
+            }
+        }
+
+        Ok(patterns)
+    }
+}
+
+/// A pattern found on a curve : its kind, the range of its points and its confidence.
+type Found = (PatternKind, (usize, usize), f64);
+
+/// The data of a curve needed to detect its patterns.
+struct CurveAnalysis {
+    /// The distances between the points of the curve, in the order of the curve.
+    distances: Vec<Vec<f64>>,
+    /// The projected positions of the points of the curve.
+    positions: Vec<Vector2<f64>>,
+}
+
+impl CurveAnalysis {
+    /// Detects all the patterns of the curve.
+    fn detect(&self) -> Vec<Found> {
+        let n = self.positions.len();
+        let pairs: Vec<f64> = (0..n)
+            .flat_map(|i| (i + 1..n).map(move |j| (i, j)))
+            .map(|(i, j)| self.distances[i][j])
+            .filter(|d| d.is_finite())
+            .collect();
+        if n < 3 || pairs.is_empty() {
+            return Vec::new();
+        }
+
+        // the reference scale of the thresholds
+        let scale = pairs.iter().sum::<f64>() / pairs.len() as f64;
+        if scale <= 0.0 {
+            // all the points are at the same position
+            return vec![(PatternKind::Stagnation, (0, n - 1), 1.0)];
+        }
+
+        let mut found = self.stagnations(scale);
+        found.extend(self.clusters(scale, &found.clone()));
+        found.extend(self.transitions(scale));
+        let outliers = self.outliers(scale);
+        let oscillations = self.oscillations(scale);
+        found.extend(self.u_turns(&outliers, &oscillations));
+        found.extend(self.cycles(scale, &oscillations));
+        found.extend(outliers);
+        found.extend(oscillations);
+        found
+    }
+
+    /// Returns the distance between two points of the curve, NaN if it is missing.
+    fn distance(&self, i: usize, j: usize) -> f64 {
+        self.distances[i][j]
+    }
+
+    /// Detects the runs of at least 2 steps that are all smaller than a fraction of the scale.
+    fn stagnations(&self, scale: f64) -> Vec<Found> {
+        let threshold = STAGNATION * scale;
+        let steps: Vec<f64> = (0..self.positions.len() - 1)
+            .map(|i| self.distance(i, i + 1))
+            .collect();
+
+        runs(&steps, |d| d <= threshold)
+            .into_iter()
+            .filter(|(start, end)| end - start >= 2)
+            .map(|(start, end)| {
+                let mean = steps[start..end].iter().sum::<f64>() / (end - start) as f64;
+                (
+                    PatternKind::Stagnation,
+                    (start, end),
+                    1.0 - mean / threshold,
+                )
+            })
+            .collect()
+    }
+
+    /// Detects the runs of consecutive points whose diameter is small, unless they are a stagnation.
+    fn clusters(&self, scale: f64, stagnations: &[Found]) -> Vec<Found> {
+        let threshold = CLUSTER * scale;
+        let n = self.positions.len();
+        let mut found = Vec::new();
+
+        let mut start = 0;
+        while start < n {
+            // grow the cluster while the next point is close to all of its points
+            let mut end = start;
+            while end + 1 < n && (start..=end).all(|k| self.distance(k, end + 1) <= threshold) {
+                end += 1;
+            }
+
+            let in_stagnation = stagnations
+                .iter()
+                .any(|(_, (s, e), _)| *s <= start && end <= *e);
+            if end + 1 - start >= MIN_CLUSTER && !in_stagnation {
+                let diameter = (start..=end)
+                    .flat_map(|i| (i + 1..=end).map(move |j| (i, j)))
+                    .fold(0.0, |acc: f64, (i, j)| acc.max(self.distance(i, j)));
+                found.push((
+                    PatternKind::Cluster,
+                    (start, end),
+                    1.0 - diameter / threshold,
+                ));
+            }
+
+            start = end + 1;
+        }
+
+        found
+    }
+
+    /// Detects the steps much larger than the median one.
+    fn transitions(&self, scale: f64) -> Vec<Found> {
+        let steps: Vec<f64> = (0..self.positions.len() - 1)
+            .map(|i| self.distance(i, i + 1))
+            .collect();
+        let median = match median(&steps) {
+            Some(median) => median,
+            None => return Vec::new(),
+        };
+        // a transition leaves the current cluster, if any
+        let threshold = (TRANSITION * median).max(CLUSTER * scale);
+
+        steps
+            .iter()
+            .enumerate()
+            .filter(|(_, &d)| d >= threshold)
+            .map(|(i, &d)| (PatternKind::Transition, (i, i + 1), 1.0 - threshold / d))
+            .collect()
+    }
+
+    /// Detects the points whose nearest point is much further than for the other points.
+    fn outliers(&self, scale: f64) -> Vec<Found> {
+        let n = self.positions.len();
+        let nearest: Vec<f64> = (0..n)
+            .map(|i| {
+                (0..n)
+                    .filter(|&j| j != i)
+                    .map(|j| self.distance(i, j))
+                    .filter(|d| d.is_finite())
+                    .fold(f64::NAN, f64::min)
+            })
+            .collect();
+        let median = match median(&nearest) {
+            Some(median) if n >= 4 => median,
+            _ => return Vec::new(),
+        };
+        let threshold = (OUTLIER * median).max(CLUSTER * scale);
+
+        nearest
+            .iter()
+            .enumerate()
+            .filter(|(_, &d)| d >= threshold)
+            .map(|(i, &d)| (PatternKind::Outlier, (i, i), 1.0 - threshold / d))
+            .collect()
+    }
+
+    /// Detects the runs of points going back and forth, ie. each close to the point two steps later.
+    fn oscillations(&self, scale: f64) -> Vec<Found> {
+        let n = self.positions.len();
+        let moving = STAGNATION * scale;
+        let ratios: Vec<f64> = (0..n.saturating_sub(2))
+            .map(|i| {
+                let step = self.distance(i, i + 1).max(self.distance(i + 1, i + 2));
+                if step > moving {
+                    self.distance(i, i + 2) / step
+                } else {
+                    f64::NAN
+                }
+            })
+            .collect();
+
+        // at least 2 returns, ie. 4 points
+        runs(&ratios, |r| r <= OSCILLATION)
+            .into_iter()
+            .filter(|(start, end)| end - start >= 2)
+            .map(|(start, end)| {
+                let mean = ratios[start..end].iter().sum::<f64>() / (end - start) as f64;
+                (
+                    PatternKind::Oscillation,
+                    (start, end + 1),
+                    1.0 - mean / OSCILLATION,
+                )
+            })
+            .collect()
+    }
+
+    /// Detects the points where the curve turns back on itself, except for the outliers and the oscillations.
+    fn u_turns(&self, outliers: &[Found], oscillations: &[Found]) -> Vec<Found> {
+        let n = self.positions.len();
+        let steps: Vec<f64> = (0..n - 1)
+            .map(|i| (self.positions[i + 1] - self.positions[i]).norm())
+            .collect();
+        let median = match median(&steps) {
+            Some(median) => median,
+            None => return Vec::new(),
+        };
+
+        (1..n - 1)
+            .filter(|&i| !outliers.iter().any(|(_, (s, _), _)| *s == i))
+            .filter(|&i| !oscillations.iter().any(|(_, (s, e), _)| *s < i && i < *e))
+            .filter_map(|i| {
+                let before = self.positions[i] - self.positions[i - 1];
+                let after = self.positions[i + 1] - self.positions[i];
+                // the small steps have an unreliable direction
+                if before.norm() < 0.5 * median || after.norm() < 0.5 * median {
+                    return None;
+                }
+
+                let cos = before.dot(&after) / (before.norm() * after.norm());
+                // the curve also turns back in the original space
+                let back = self.distance(i - 1, i + 1)
+                    <= 0.5 * self.distance(i - 1, i).max(self.distance(i, i + 1));
+                if cos <= U_TURN && back {
+                    Some((
+                        PatternKind::UTurn,
+                        (i - 1, i + 1),
+                        (U_TURN - cos) / (1.0 + U_TURN),
+                    ))
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Detects the points where the curve comes back close to an earlier point after going away,
+    /// except for the oscillations.
+    fn cycles(&self, scale: f64, oscillations: &[Found]) -> Vec<Found> {
+        let n = self.positions.len();
+        let close = CYCLE_CLOSE * scale;
+        let away = CYCLE_AWAY * scale;
+        let mut found = Vec::new();
+
+        let mut start = 0;
+        while start + MIN_CYCLE < n {
+            let end = (start + MIN_CYCLE..n).find(|&end| {
+                let excursion = (start + 1..end)
+                    .map(|k| self.distance(start, k))
+                    .fold(0.0, f64::max);
+                self.distance(start, end) <= close && excursion >= away
+            });
+
+            match end {
+                Some(end) => {
+                    let in_oscillation = oscillations
+                        .iter()
+                        .any(|(_, (s, e), _)| *s <= start && end <= *e);
+                    if !in_oscillation {
+                        let confidence = 1.0 - self.distance(start, end) / close;
+                        found.push((PatternKind::Cycle, (start, end), confidence));
+                    }
+                    start = end;
+                }
+                None => start += 1,
+            }
+        }
+
+        found
+    }
+}
+
+/// Utility function that finds the runs of consecutive values satisfying a condition.
+///
+/// ### Returns
+///
+/// The ranges of the runs, as (start, end) with `end` excluded. Missing values (NaN) end a run.
+fn runs(values: &[f64], condition: impl Fn(f64) -> bool) -> Vec<(usize, usize)> {
+    let mut runs = Vec::new();
+    let mut start = None;
+    for (i, &value) in values.iter().enumerate() {
+        match (start, !value.is_nan() && condition(value)) {
+            (None, true) => start = Some(i),
+            (Some(s), false) => {
+                runs.push((s, i));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        runs.push((s, values.len()));
+    }
+
+    runs
+}
+
+/// Utility function that computes the median of the values that aren't missing (NaN).
+fn median(values: &[f64]) -> Option<f64> {
+    let mut sorted: Vec<f64> = values.iter().copied().filter(|v| !v.is_nan()).collect();
+    if sorted.is_empty() {
+        return None;
+    }
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    Some(sorted[sorted.len() / 2])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{input::InputData, projection::PCA, timecurve::TimecurveSet};
+
+    /// Detects the patterns of a single curve whose points are given as feature vectors.
+    fn detect(features: &str) -> Vec<Pattern> {
+        let labels: Vec<String> = (0..features.matches('[').count() - 1)
+            .map(|i| format!("\"{}\"", i))
+            .collect();
+        let input_data = InputData::from_str(&format!(
+            r#"{{ "features": {}, "data": [{{ "name": "test", "timelabels": [{}] }}] }}"#,
+            features,
+            labels.join(", ")
+        ))
+        .unwrap();
+        let set = TimecurveSet::new(&input_data, PCA::new()).unwrap();
+        PatternDetector::new()
+            .detect(&set, input_data.get_distance_matrix())
+            .unwrap()
+    }
+
+    /// Returns the ranges of the patterns of a given kind.
+    fn ranges(patterns: &[Pattern], kind: PatternKind) -> Vec<(usize, usize)> {
+        patterns
+            .iter()
+            .filter(|p| p.get_kind() == kind)
+            .map(|p| p.get_points())
+            .collect()
+    }
+
+    #[test]
+    fn stagnation_cluster_and_transition() {
+        // no change, then small changes, then a jump to another state
+        let patterns = detect(
+            "[[0, 0], [0, 0], [0, 0.001], [0, 0.001],
+              [5, 0], [5, 0.5], [5.3, 0.2], [5.2, 0], [5.4, 0.4]]",
+        );
+
+        assert_eq!(ranges(&patterns, PatternKind::Stagnation), vec![(0, 3)]);
+        assert_eq!(ranges(&patterns, PatternKind::Transition), vec![(3, 4)]);
+        assert_eq!(ranges(&patterns, PatternKind::Cluster), vec![(4, 8)]);
+        assert!(patterns
+            .iter()
+            .all(|p| (0.0..=1.0).contains(&p.get_confidence())));
+        assert_eq!(patterns[0].get_time_range(), (0, 3));
+    }
+
+    #[test]
+    fn oscillation_cycle_and_outlier() {
+        // an edit war between two versions
+        let patterns = detect("[[0, 0], [5, 0], [0, 0.1], [5, 0.1], [0, 0.2], [5, 0.2]]");
+        assert_eq!(ranges(&patterns, PatternKind::Oscillation), vec![(0, 5)]);
+        assert!(ranges(&patterns, PatternKind::Cycle).is_empty());
+        assert!(ranges(&patterns, PatternKind::UTurn).is_empty());
+
+        // a loop around a square
+        let patterns = detect(
+            "[[0, 0], [3, 0], [6, 0], [6, 3], [6, 6], [3, 6], [0, 6], [0, 3], [0, 0.1], [0, -3]]",
+        );
+        assert_eq!(ranges(&patterns, PatternKind::Cycle), vec![(0, 8)]);
+
+        // a line with a point far away, and a change undone at its end
+        let patterns = detect(
+            "[[0, 0], [1, 0], [2, 0], [3, 0], [20, 20], [4, 0], [5, 0], [6, 0], [7, 0], [8, 0], [7, 0.1]]",
+        );
+        assert_eq!(ranges(&patterns, PatternKind::Outlier), vec![(4, 4)]);
+        assert_eq!(ranges(&patterns, PatternKind::UTurn), vec![(8, 10)]);
+    }
+}
@@ -0,0 +1,258 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+use super::{
+    projection_algorithm::{check_square_matrix, ProjectionAlgorithm, ProjectionContext},
+    smacof::{normalised_stress, SMACOF},
+};
+use crate::{
+    alignment::fit_similarity,
+    error::{TimecurveError, TimecurveErrorKind},
+    timecurve::Position,
+};
+use log::info;
+use nalgebra::DMatrix;
+
+/// Structure representing a variant of the SMACOF algorithm where some points are pinned to fixed coordinates,
+/// eg. a baseline version or known milestones, and the other points are laid out around them.
+///
+/// The anchors are read from the `ProjectionContext`, ie. from the `anchors` list of the input file,
+/// so this algorithm only makes sense through `project_with_context`, eg. from `TimecurveSet::new`.
+/// Called with `project` alone or without anchors, it behaves like the plain SMACOF algorithm.
+///
+/// The coordinates of the anchors are in the unit of the distances, and the free points are placed
+/// by minimising the stress with the anchors fixed. Missing distances (NaN) are ignored,
+/// but each free point must be connected to an anchor through the known distances.
+pub struct AnchoredSMACOF {
+    /// The maximum number of iterations.
+    max_iterations: usize,
+    /// The algorithm stops when the stress improves by less than this value between two iterations.
+    tolerance: f64,
+}
+
+impl AnchoredSMACOF {
+    /// Creates a new instance of the anchored SMACOF algorithm.
+    ///
+    /// ### Arguments
+    ///
+    /// * `max_iterations` - The maximum number of iterations. 300 is a reasonable value.
+    /// * `tolerance` - The algorithm stops when the normalised stress improves by less than this value. 1e-6 is a reasonable value.
+    pub fn new(max_iterations: usize, tolerance: f64) -> Self {
+        Self {
+            max_iterations,
+            tolerance,
+        }
+    }
+
+    /// Projects the points with some of them pinned, and also returns the final stress of the layout.
+    ///
+    /// ### Arguments
+    ///
+    /// * `distance_matrix` - A reference to a vector of rows representing the distance matrix.
+    /// * `anchors` - The (row of the distance matrix, position) couples of the pinned points.
+    ///
+    /// ### Returns
+    ///
+    /// A tuple containing the projected points, in which the anchors are exactly at their positions,
+    /// and the normalised stress. An error is returned if an anchor is invalid,
+    /// or if some points are not connected to any anchor through the known distances.
+    pub fn project_with_stress(
+        &self,
+        distance_matrix: &[Vec<f64>],
+        anchors: &[(usize, Position)],
+    ) -> Result<(Vec<Position>, f64), TimecurveError> {
+        let n = check_square_matrix(distance_matrix)?;
+        let unconstrained = SMACOF::new(self.max_iterations, self.tolerance, None);
+        if anchors.is_empty() {
+            return unconstrained.project_with_stress(distance_matrix);
+        }
+
+        let mut pinned = vec![None; n];
+        for &(index, position) in anchors {
+            if index >= n || pinned[index].is_some() {
+                return Err(TimecurveError::new(
+                    TimecurveErrorKind::InvalidParameter,
+                    Some(&format!(
+                        "Anchor on row {} is out of the matrix or duplicated",
+                        index
+                    )),
+                ));
+            }
+            if !position.get_x().is_finite() || !position.get_y().is_finite() {
+                return Err(TimecurveError::new(
+                    TimecurveErrorKind::InvalidParameter,
+                    Some(&format!("Anchor on row {} has invalid coordinates", index)),
+                ));
+            }
+            pinned[index] = Some(position);
+        }
+
+        let free: Vec<usize> = (0..n).filter(|&i| pinned[i].is_none()).collect();
+        let known = |i: usize, j: usize| i != j && !distance_matrix[i][j].is_nan();
+
+        // X_f = V_ff⁻¹ ((B(Z) Z)_f - V_fa X_a), where V is the laplacian of the known distances
+        // and V_ff its block for the free points, which can be inverted if they are all connected to an anchor
+        let laplacian = DMatrix::from_fn(free.len(), free.len(), |a, b| {
+            if a == b {
+                (0..n).filter(|&j| known(free[a], j)).count() as f64
+            } else if known(free[a], free[b]) {
+                -1.0
+            } else {
+                0.0
+            }
+        });
+        let inverse = laplacian.try_inverse().ok_or_else(|| {
+            TimecurveError::new(
+                TimecurveErrorKind::DisconnectedGraph,
+                Some("Some points are not connected to any anchor through the known distances"),
+            )
+        })?;
+
+        // start from the unconstrained layout, moved as close as possible to the anchors
+        let mut layout = unconstrained.project_with_stress(distance_matrix)?.0;
+        let pairs: Vec<(Position, Position)> =
+            anchors.iter().map(|&(i, p)| (layout[i], p)).collect();
+        match fit_similarity(&pairs, 2) {
+            Ok(similarity) => layout = layout.iter().map(|p| similarity.apply(p)).collect(),
+            Err(_) => {
+                // a single anchor (or anchors at the same position) only fixes the translation
+                let (x, y) = (
+                    pairs[0].1.get_x() - pairs[0].0.get_x(),
+                    pairs[0].1.get_y() - pairs[0].0.get_y(),
+                );
+                layout = layout
+                    .iter()
+                    .map(|p| Position::new(p.get_x() + x, p.get_y() + y))
+                    .collect();
+            }
+        }
+        for (position, pin) in layout.iter_mut().zip(&pinned) {
+            if let Some(pin) = pin {
+                *position = *pin;
+            }
+        }
+
+        let mut stress = normalised_stress(distance_matrix, &layout);
+        let mut iterations = 0;
+
+        while iterations < self.max_iterations {
+            let mut rhs = DMatrix::zeros(free.len(), 2);
+            for (a, &i) in free.iter().enumerate() {
+                for j in (0..n).filter(|&j| known(i, j)) {
+                    if let Some(pin) = pinned[j] {
+                        rhs[(a, 0)] += pin.get_x();
+                        rhs[(a, 1)] += pin.get_y();
+                    }
+
+                    let d = layout[i].distance(&layout[j]);
+                    if d == 0.0 {
+                        continue;
+                    }
+                    let ratio = distance_matrix[i][j] / d;
+                    rhs[(a, 0)] += ratio * (layout[i].get_x() - layout[j].get_x());
+                    rhs[(a, 1)] += ratio * (layout[i].get_y() - layout[j].get_y());
+                }
+            }
+
+            let positions = &inverse * rhs;
+            for (a, &i) in free.iter().enumerate() {
+                layout[i] = Position::new(positions[(a, 0)], positions[(a, 1)]);
+            }
+            iterations += 1;
+
+            let new_stress = normalised_stress(distance_matrix, &layout);
+            let improvement = stress - new_stress;
+            stress = new_stress;
+
+            if improvement < self.tolerance {
+                break;
+            }
+        }
+
+        info!(
+            "Anchored SMACOF stopped after {} iterations with {} anchors, normalised stress = {:.6}",
+            iterations,
+            anchors.len(),
+            stress
+        );
+
+        Ok((layout, stress))
+    }
+}
+
+impl ProjectionAlgorithm for AnchoredSMACOF {
+    fn project(&self, distance_matrix: &Vec<Vec<f64>>) -> Result<Vec<Position>, TimecurveError> {
+        Ok(self.project_with_stress(distance_matrix, &[])?.0)
+    }
+
+    fn project_with_context(
+        &self,
+        distance_matrix: &Vec<Vec<f64>>,
+        context: &ProjectionContext,
+    ) -> Result<Vec<Position>, TimecurveError> {
+        Ok(self
+            .project_with_stress(distance_matrix, context.get_anchors())?
+            .0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rectangle() -> Vec<Vec<f64>> {
+        let points = [
+            Position::new(0.0, 0.0),
+            Position::new(3.0, 0.0),
+            Position::new(3.0, 4.0),
+            Position::new(0.0, 4.0),
+            Position::new(1.5, 2.0),
+        ];
+        points
+            .iter()
+            .map(|a| points.iter().map(|b| a.distance(b)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn anchors_are_kept_and_distances_preserved() {
+        let distance_matrix = rectangle();
+        // the rectangle is moved : the anchors are not where the unconstrained layout would put them
+        let anchors = [
+            (0, Position::new(10.0, 10.0)),
+            (2, Position::new(13.0, 14.0)),
+        ];
+
+        let (layout, stress) = AnchoredSMACOF::new(500, 1e-12)
+            .project_with_stress(&distance_matrix, &anchors)
+            .unwrap();
+
+        assert_eq!(layout[0].get_x(), 10.0);
+        assert_eq!(layout[2].get_y(), 14.0);
+        assert!(stress < 1e-8);
+        assert!(layout[4].distance(&Position::new(11.5, 12.0)) < 1e-4);
+    }
+
+    #[test]
+    fn free_points_must_reach_an_anchor() {
+        let nan = f64::NAN;
+        let distance_matrix = vec![
+            vec![0.0, 1.0, nan],
+            vec![1.0, 0.0, nan],
+            vec![nan, nan, 0.0],
+        ];
+        let smacof = AnchoredSMACOF::new(100, 1e-6);
+
+        let result = smacof.project_with_stress(&distance_matrix, &[(0, Position::new(0.0, 0.0))]);
+        assert!(matches!(
+            result.map_err(|e| e.kind),
+            Err(TimecurveErrorKind::DisconnectedGraph)
+        ));
+
+        let result = smacof.project_with_stress(&rectangle(), &[(7, Position::new(0.0, 0.0))]);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,462 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+use super::projection_algorithm::{check_complete_matrix, ProjectionAlgorithm};
+use crate::{
+    error::{TimecurveError, TimecurveErrorKind},
+    timecurve::Position,
+};
+use nalgebra::{DMatrix, DVector};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Structure representing the classical Multidimensional Scaling (MDS) algorithm.
+pub struct ClassicalMDS {
+    /// The number of dimensions of the projection, 2 or 3.
+    dimensions: usize,
+}
+
+impl ClassicalMDS {
+    /// Creates a new instance of the classical MDS algorithm, projecting in 2 dimensions.
+    /// Takes no arguments.
+    pub fn new() -> Self {
+        return ClassicalMDS { dimensions: 2 };
+    }
+
+    /// Sets the number of dimensions of the projection.
+    /// With 3 dimensions, the third eigenvector gives the z coordinate of the points.
+    ///
+    /// ### Arguments
+    ///
+    /// * `dimensions` - The number of dimensions, 2 or 3. Other values make the projection fail.
+    pub fn with_dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = dimensions;
+        self
+    }
+
+    /// Same as `project`, but takes a slice of rows so that other projection algorithms
+    /// can use the classical MDS without copying their distance matrix.
+    pub(crate) fn project_rows(
+        &self,
+        distance_matrix: &[Vec<f64>],
+    ) -> Result<Vec<Position>, TimecurveError> {
+        let n = check_complete_matrix(distance_matrix)?;
+
+        if !(2..=3).contains(&self.dimensions) {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::InvalidParameter,
+                Some(&format!(
+                    "The classical MDS projects in 2 or 3 dimensions, got {}",
+                    self.dimensions
+                )),
+            ));
+        }
+
+        // https://rich-d-wilkinson.github.io/MATH3030/6-1-classical-mds.html
+        // https://en.wikipedia.org/wiki/Multidimensional_scaling
+        // http://www.normalesup.org/~carpenti/Notes/MDS/MDS-simple.html
+        // TODO : à modifier selon la méthode du livre Modern Multidimensional Scaling
+
+        // Given a distance matrix D, the centred inner-product matrix
+        // (also called the centred-Gram matrix) is B = HAH 🤯
+        // where A is the matrix of negative square distances divided by two,
+        // and H = In - (1/n) * (1n * 1nT) is the centering matrix.
+
+        // Multiplying by H on both sides only substracts the row and column means of A
+        // and adds back its total mean, so B is computed in place without building H
+        let mut b = DMatrix::from_fn(n, n, |i, j| {
+            let v = distance_matrix[i][j];
+            -0.5 * v * v
+        });
+
+        let row_means: Vec<f64> = (0..n).map(|i| b.row(i).mean()).collect();
+        let col_means: Vec<f64> = (0..n).map(|j| b.column(j).mean()).collect();
+        let total_mean = b.mean();
+
+        for j in 0..n {
+            for i in 0..n {
+                b[(i, j)] += total_mean - row_means[i] - col_means[j];
+            }
+        }
+
+        // Determine the m largest eigenvalues λ 1 , λ 2 , . . . , λ m
+        // and corresponding eigenvectors e 1 , e 2 , . . . , e m of B
+        // (where m is the number of dimensions desired for the output)
+        // Only the m = 2 or 3 largest ones are needed, so they are computed iteratively
+        // instead of decomposing the whole matrix
+        let couples = top_eigenpairs(&b, self.dimensions);
+
+        // X = Em * Lm^.5 fournit une solution du problème posé.
+        // Les coordonnées des n points dans l'espace de dimension m sont les lignes de la matrice solution X
+        // (matrice à n lignes et m colonnes).
+        let coordinate = |k: usize, i: usize| match couples.get(k) {
+            Some((lambda, e)) => e[i] * lambda.sqrt(),
+            None => 0.0,
+        };
+
+        let mut points = Vec::new();
+        for i in 0..n {
+            points.push(if self.dimensions == 3 {
+                Position::new_3d(coordinate(0, i), coordinate(1, i), coordinate(2, i))
+            } else {
+                Position::new(coordinate(0, i), coordinate(1, i))
+            });
+        }
+
+        return Ok(points);
+    }
+}
+
+impl ProjectionAlgorithm for ClassicalMDS {
+    fn project(&self, distance_matrix: &Vec<Vec<f64>>) -> Result<Vec<Position>, TimecurveError> {
+        self.project_rows(distance_matrix)
+    }
+}
+
+/// Computes the largest eigenvalues and the corresponding eigenvectors of a symmetric matrix,
+/// using the Lanczos algorithm with full reorthogonalisation.
+///
+/// Each iteration only costs a matrix-vector product, and a few dozen iterations are usually enough
+/// for the largest eigenpairs to converge, which is much cheaper than a full eigendecomposition.
+///
+/// ### Arguments
+///
+/// * `matrix` - The symmetric matrix.
+/// * `count` - The number of eigenpairs to compute.
+///
+/// ### Returns
+///
+/// At most `count` couples (eigenvalue, unit eigenvector), sorted by decreasing eigenvalue.
+/// Less couples are returned if the matrix has less than `count` rows.
+pub(crate) fn top_eigenpairs(matrix: &DMatrix<f64>, count: usize) -> Vec<(f64, DVector<f64>)> {
+    const TOLERANCE: f64 = 1e-10;
+    const CHECK_EVERY: usize = 5;
+
+    let n = matrix.nrows();
+    let count = count.min(n);
+    if count == 0 {
+        return Vec::new();
+    }
+
+    // the starting vector must not be orthogonal to the eigenvectors we are looking for
+    // (a vector of ones is in the kernel of a centred matrix), so a random one is used
+    // with a fixed seed to keep the projection deterministic
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut q = DVector::from_fn(n, |_, _| rng.gen::<f64>() - 0.5);
+    q.normalize_mut();
+
+    // orthonormal basis of the Krylov subspace
+    let mut basis: Vec<DVector<f64>> = Vec::new();
+    // diagonal and sub-diagonal of the tridiagonal matrix T = Qt A Q
+    let mut alphas: Vec<f64> = Vec::new();
+    let mut betas: Vec<f64> = Vec::new();
+    // whether q was drawn at random after a breakdown
+    let mut restarted = false;
+
+    loop {
+        let mut w = matrix * &q;
+        let alpha = q.dot(&w);
+        basis.push(q);
+        alphas.push(alpha);
+
+        // full reorthogonalisation against the whole basis, done twice for numerical stability
+        for _ in 0..2 {
+            for v in &basis {
+                let projection = v.dot(&w);
+                w.axpy(-projection, v, 1.0);
+            }
+        }
+        let beta = w.norm();
+
+        let m = basis.len();
+        let exhausted = m == n;
+        let breakdown = beta <= TOLERANCE * alphas.iter().fold(1.0_f64, |a, b| a.max(b.abs()));
+
+        // a breakdown right after a restart means that the random restart vector is an eigenvector,
+        // so the whole orthogonal complement of the basis is an eigenspace of eigenvalue alpha :
+        // the spectrum is then fully known, eg. for the low-rank matrices of euclidean distances
+        let settled = breakdown && restarted;
+
+        // right after another breakdown the residuals are all zero, so convergence can't be checked
+        if m >= count && (exhausted || settled || (!breakdown && m % CHECK_EVERY == 0)) {
+            // Ritz pairs of the subspace
+            let t = DMatrix::from_fn(m, m, |i, j| {
+                if i == j {
+                    alphas[i]
+                } else if i + 1 == j {
+                    betas[i]
+                } else if j + 1 == i {
+                    betas[j]
+                } else {
+                    0.0
+                }
+            });
+            let decomposition = t.symmetric_eigen();
+
+            let mut order: Vec<usize> = (0..m).collect();
+            order.sort_by(|&a, &b| {
+                decomposition.eigenvalues[b]
+                    .partial_cmp(&decomposition.eigenvalues[a])
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            let top = &order[..count];
+
+            // the residual of a Ritz pair is β * |last component of its eigenvector in T|
+            let scale = decomposition.eigenvalues.amax().max(f64::EPSILON);
+            let converged = if settled {
+                // the Ritz pairs are exact, but the complement may hold more copies of alpha
+                decomposition.eigenvalues[top[count - 1]] >= alpha - TOLERANCE * scale
+            } else {
+                top.iter().all(|&k| {
+                    (beta * decomposition.eigenvectors[(m - 1, k)]).abs() <= TOLERANCE * scale
+                })
+            };
+
+            if exhausted || converged {
+                return top
+                    .iter()
+                    .map(|&k| {
+                        let mut vector = DVector::zeros(n);
+                        for (i, v) in basis.iter().enumerate() {
+                            vector.axpy(decomposition.eigenvectors[(i, k)], v, 1.0);
+                        }
+                        vector.normalize_mut();
+                        (decomposition.eigenvalues[k], vector)
+                    })
+                    .collect();
+            }
+        }
+
+        if breakdown {
+            // the basis spans an invariant subspace, which misses the other eigenvectors of repeated eigenvalues :
+            // the iteration is restarted from a random vector orthogonal to the basis
+            w = DVector::from_fn(n, |_, _| rng.gen::<f64>() - 0.5);
+            for _ in 0..2 {
+                for v in &basis {
+                    let projection = v.dot(&w);
+                    w.axpy(-projection, v, 1.0);
+                }
+            }
+            betas.push(0.0);
+            q = w.normalize();
+        } else {
+            betas.push(beta);
+            q = w / beta;
+        }
+        restarted = breakdown;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::input::InputData;
+
+    use super::*;
+
+    #[test]
+    fn classical_mds_preserves_distances() {
+        let distance_matrix: Vec<Vec<f64>> = vec![
+            vec![0.0, 1.0, 2.0],
+            vec![1.0, 0.0, 3.0],
+            vec![2.0, 3.0, 0.0],
+        ];
+
+        let classical_mds = ClassicalMDS::new();
+
+        let points = classical_mds.project(&distance_matrix).unwrap();
+
+        let epsilon: f64 = 10e-3;
+
+        // dist a <-> b
+        let dist_a_b = ((points[0].get_x() - points[1].get_x()).powf(2.0)
+            + (points[0].get_y() - points[1].get_y()).powf(2.0))
+        .sqrt();
+        assert!(dist_a_b < 1.0 + epsilon && dist_a_b > 1.0 - epsilon);
+
+        // dist a <-> c
+        let dist_a_c = ((points[0].get_x() - points[2].get_x()).powf(2.0)
+            + (points[0].get_y() - points[2].get_y()).powf(2.0))
+        .sqrt();
+        assert!(dist_a_c < 2.0 + epsilon && dist_a_c > 2.0 - epsilon);
+
+        // dist b <-> c
+        let dist_b_c = ((points[1].get_x() - points[2].get_x()).powf(2.0)
+            + (points[1].get_y() - points[2].get_y()).powf(2.0))
+        .sqrt();
+        assert!(dist_b_c < 3.0 + epsilon && dist_b_c > 3.0 - epsilon);
+    }
+
+    #[test]
+    fn classical_mds_preserves_distances_in_3d() {
+        // a regular tetrahedron can't be drawn in the plane without distortion
+        let distance_matrix: Vec<Vec<f64>> = (0..4)
+            .map(|i| (0..4).map(|j| if i == j { 0.0 } else { 1.0 }).collect())
+            .collect();
+
+        let points = ClassicalMDS::new()
+            .with_dimensions(3)
+            .project(&distance_matrix)
+            .unwrap();
+
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((points[i].distance(&points[j]) - distance_matrix[i][j]).abs() < 1e-9);
+            }
+        }
+        assert!(ClassicalMDS::new()
+            .with_dimensions(4)
+            .project(&distance_matrix)
+            .is_err());
+    }
+
+    #[test]
+    fn classical_mds_projects_the_right_number_of_points() {
+        let distance_matrix: Vec<Vec<f64>> = vec![
+            vec![0.0, 1.0, 2.0],
+            vec![1.0, 0.0, 3.0],
+            vec![2.0, 3.0, 0.0],
+        ];
+
+        let classical_mds = ClassicalMDS::new();
+
+        let points = classical_mds.project(&distance_matrix).unwrap();
+
+        assert_eq!(points.len(), 3);
+
+        let distance_matrix: Vec<Vec<f64>> = vec![
+            vec![0.0, 1.0, 2.0, 3.0],
+            vec![1.0, 0.0, 3.0, 4.0],
+            vec![2.0, 3.0, 0.0, 5.0],
+            vec![3.0, 4.0, 5.0, 0.0],
+        ];
+
+        let points = classical_mds.project(&distance_matrix).unwrap();
+
+        assert_eq!(points.len(), 4);
+
+        let input = InputData::from_filename(&format!(
+            "{}/tests/psfr100points.json",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+
+        let points = classical_mds.project(input.get_distance_matrix()).unwrap();
+
+        assert_eq!(points.len(), 100);
+    }
+
+    #[test]
+    fn classical_mds_matches_full_eigendecomposition() {
+        let input = InputData::from_filename(&format!(
+            "{}/tests/psfr100points.json",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let distance_matrix = input.get_distance_matrix();
+        let n = distance_matrix.len();
+
+        // reference : B = HAH with an explicit centering matrix and a full eigendecomposition
+        let h = DMatrix::from_diagonal_element(n, n, 1.0)
+            - (1.0 / n as f64) * DMatrix::from_element(n, n, 1.0);
+        let a = DMatrix::from_fn(n, n, |i, j| -0.5 * distance_matrix[i][j].powi(2));
+        let decomposition = (&h * a * &h).symmetric_eigen();
+        let mut order: Vec<usize> = (0..n).collect();
+        order.sort_by(|&a, &b| {
+            decomposition.eigenvalues[b]
+                .partial_cmp(&decomposition.eigenvalues[a])
+                .unwrap()
+        });
+        let reference: Vec<Position> = (0..n)
+            .map(|i| {
+                let x = decomposition.eigenvectors[(i, order[0])]
+                    * decomposition.eigenvalues[order[0]].sqrt();
+                let y = decomposition.eigenvectors[(i, order[1])]
+                    * decomposition.eigenvalues[order[1]].sqrt();
+                Position::new(x, y)
+            })
+            .collect();
+
+        let points = ClassicalMDS::new().project(distance_matrix).unwrap();
+
+        // the result may only differ by a sign flip of each axis
+        for i in 0..n {
+            assert!(
+                (points[i].get_x().abs() - reference[i].get_x().abs()).abs()
+                    < 1e-6 * reference[i].get_x().abs().max(1.0)
+            );
+            assert!(
+                (points[i].get_y().abs() - reference[i].get_y().abs()).abs()
+                    < 1e-6 * reference[i].get_y().abs().max(1.0)
+            );
+            for j in 0..n {
+                let expected = reference[i].distance(&reference[j]);
+                assert!(
+                    (points[i].distance(&points[j]) - expected).abs() < 1e-6 * expected.max(1.0)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn classical_mds_of_a_large_euclidean_matrix() {
+        // the centred matrix of euclidean distances has rank 3, so the iteration stops after a few steps
+        // instead of running over the whole null space
+        let mut rng = StdRng::seed_from_u64(42);
+        let coordinates: Vec<[f64; 3]> = (0..2000)
+            .map(|_| [rng.gen::<f64>(), rng.gen::<f64>(), rng.gen::<f64>() * 0.5])
+            .collect();
+        let distance_matrix: Vec<Vec<f64>> = coordinates
+            .iter()
+            .map(|a| {
+                coordinates
+                    .iter()
+                    .map(|b| (0..3).map(|k| (a[k] - b[k]).powi(2)).sum::<f64>().sqrt())
+                    .collect()
+            })
+            .collect();
+
+        let points = ClassicalMDS::new()
+            .with_dimensions(3)
+            .project(&distance_matrix)
+            .unwrap();
+
+        for i in (0..2000).step_by(37) {
+            for j in (0..2000).step_by(41) {
+                assert!((points[i].distance(&points[j]) - distance_matrix[i][j]).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn top_eigenpairs_finds_repeated_eigenvalues() {
+        // the centred matrix of a regular simplex has a single repeated eigenvalue besides 0
+        let n = 6;
+        let matrix = DMatrix::from_fn(n, n, |i, j| {
+            (if i == j { 1.0 } else { 0.0 }) - 1.0 / n as f64
+        });
+
+        for count in 1..=3 {
+            let couples = top_eigenpairs(&matrix, count);
+            assert_eq!(couples.len(), count);
+            for (lambda, vector) in couples {
+                assert!((lambda - 1.0).abs() < 1e-9);
+                assert!((&matrix * &vector - &vector).norm() < 1e-9);
+            }
+        }
+    }
+
+    #[test]
+    fn top_eigenpairs_finds_largest_eigenvalues() {
+        let matrix = DMatrix::from_diagonal(&DVector::from_vec(vec![1.0, -7.0, 5.0, 3.0, 2.0]));
+
+        let couples = top_eigenpairs(&matrix, 2);
+
+        assert_eq!(couples.len(), 2);
+        assert!((couples[0].0 - 5.0).abs() < 1e-9);
+        assert!((couples[1].0 - 3.0).abs() < 1e-9);
+        assert!((couples[0].1[2].abs() - 1.0).abs() < 1e-9);
+        assert!((couples[1].1[3].abs() - 1.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,197 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+use super::{
+    isomap::dijkstra,
+    projection_algorithm::{
+        check_square_matrix, missing_distances, ProjectionAlgorithm, ProjectionContext,
+    },
+};
+use crate::{
+    error::{TimecurveError, TimecurveErrorKind},
+    timecurve::Position,
+};
+use log::info;
+
+/// Structure completing the missing distances of the matrix before giving it to another projection algorithm.
+///
+/// Each missing distance is replaced by the length of the shortest path between the two points,
+/// going through the known distances only. This lets algorithms that need a full matrix, like the classical MDS,
+/// work on inputs where some distances couldn't be computed.
+///
+/// ### Example
+///
+/// ```rust
+/// use timecurves_rs::projection::{ClassicalMDS, ProjectionAlgorithm, ShortestPathCompletion};
+///
+/// let distance_matrix = vec![
+///     vec![0.0, 1.0, f64::NAN, 1.0],
+///     vec![1.0, 0.0, 1.0, 1.4],
+///     vec![f64::NAN, 1.0, 0.0, 1.0],
+///     vec![1.0, 1.4, 1.0, 0.0],
+/// ];
+///
+/// let projection = ShortestPathCompletion::new(ClassicalMDS::new());
+/// assert_eq!(projection.complete(&distance_matrix).unwrap()[0][2], 2.0);
+///
+/// let points = projection.project(&distance_matrix).unwrap();
+/// assert_eq!(points.len(), 4);
+/// ```
+pub struct ShortestPathCompletion<P: ProjectionAlgorithm> {
+    /// The algorithm projecting the completed matrix.
+    projection: P,
+}
+
+impl<P: ProjectionAlgorithm> ShortestPathCompletion<P> {
+    /// Creates a new instance of the completion.
+    ///
+    /// ### Arguments
+    ///
+    /// * `projection` - The algorithm projecting the completed matrix.
+    pub fn new(projection: P) -> Self {
+        Self { projection }
+    }
+
+    /// Completes the missing distances of a distance matrix.
+    ///
+    /// ### Arguments
+    ///
+    /// * `distance_matrix` - The distance matrix, with NaN for the missing distances.
+    ///
+    /// ### Returns
+    ///
+    /// The completed matrix, or a `DisconnectedGraph` error if some points can't be reached through the known distances.
+    pub fn complete(&self, distance_matrix: &[Vec<f64>]) -> Result<Vec<Vec<f64>>, TimecurveError> {
+        complete_shortest_paths(distance_matrix)
+    }
+}
+
+impl<P: ProjectionAlgorithm> ProjectionAlgorithm for ShortestPathCompletion<P> {
+    fn project(&self, distance_matrix: &Vec<Vec<f64>>) -> Result<Vec<Position>, TimecurveError> {
+        self.projection.project(&self.complete(distance_matrix)?)
+    }
+
+    fn project_with_context(
+        &self,
+        distance_matrix: &Vec<Vec<f64>>,
+        context: &ProjectionContext,
+    ) -> Result<Vec<Position>, TimecurveError> {
+        self.projection
+            .project_with_context(&self.complete(distance_matrix)?, context)
+    }
+}
+
+/// Replaces the missing distances of a matrix by the length of the shortest paths through the known distances.
+///
+/// ### Arguments
+///
+/// * `distance_matrix` - The distance matrix, with NaN for the missing distances.
+///
+/// ### Returns
+///
+/// The completed matrix, or a `DisconnectedGraph` error if some points can't be reached through the known distances.
+pub(crate) fn complete_shortest_paths(
+    distance_matrix: &[Vec<f64>],
+) -> Result<Vec<Vec<f64>>, TimecurveError> {
+    let n = check_square_matrix(distance_matrix)?;
+    let missing = missing_distances(distance_matrix);
+
+    let mut completed = distance_matrix.to_vec();
+    if missing.is_empty() {
+        return Ok(completed);
+    }
+
+    // an edge for each known distance, the mean is used when the two directions are known
+    let adjacency: Vec<Vec<(usize, f64)>> = (0..n)
+        .map(|i| {
+            (0..n)
+                .filter(|&j| j != i)
+                .filter_map(|j| {
+                    let known: Vec<f64> = [distance_matrix[i][j], distance_matrix[j][i]]
+                        .into_iter()
+                        .filter(|d| !d.is_nan())
+                        .collect();
+                    if known.is_empty() {
+                        None
+                    } else {
+                        Some((j, known.iter().sum::<f64>() / known.len() as f64))
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut sources: Vec<usize> = missing.iter().map(|&(i, _)| i).collect();
+    sources.dedup();
+
+    for source in sources {
+        let distances = dijkstra(&adjacency, source);
+        for (j, cell) in completed[source].iter_mut().enumerate() {
+            if !cell.is_nan() {
+                continue;
+            }
+            if distances[j].is_infinite() {
+                return Err(TimecurveError::new(
+                    TimecurveErrorKind::DisconnectedGraph,
+                    Some(&format!(
+                        "Point {} can't reach point {} through the known distances",
+                        source, j
+                    )),
+                ));
+            }
+            *cell = distances[j];
+        }
+    }
+
+    info!(
+        "{} missing distances completed with shortest paths.",
+        missing.len()
+    );
+
+    Ok(completed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::projection::ClassicalMDS;
+
+    #[test]
+    fn completion_uses_shortest_paths() {
+        // a path 0 - 1 - 2 - 3 where only consecutive distances are known, and a shortcut 0 - 3
+        let nan = f64::NAN;
+        let distance_matrix = vec![
+            vec![0.0, 1.0, nan, 2.5],
+            vec![1.0, 0.0, 1.0, nan],
+            vec![nan, 1.0, 0.0, 1.0],
+            vec![2.5, nan, 1.0, 0.0],
+        ];
+
+        let completed = complete_shortest_paths(&distance_matrix).unwrap();
+
+        assert_eq!(completed[0][2], 2.0);
+        assert_eq!(completed[2][0], 2.0);
+        assert_eq!(completed[1][3], 2.0);
+        assert_eq!(completed[0][3], 2.5);
+    }
+
+    #[test]
+    fn completion_fails_on_disconnected_points() {
+        let nan = f64::NAN;
+        let distance_matrix = vec![
+            vec![0.0, 1.0, nan],
+            vec![1.0, 0.0, nan],
+            vec![nan, nan, 0.0],
+        ];
+
+        let result = ShortestPathCompletion::new(ClassicalMDS::new()).project(&distance_matrix);
+
+        match result {
+            Err(e) => assert!(matches!(e.kind, TimecurveErrorKind::DisconnectedGraph)),
+            Ok(_) => panic!("a point without known distances should not be projected"),
+        }
+    }
+}
@@ -0,0 +1,274 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+use super::{
+    classical_mds::ClassicalMDS,
+    projection_algorithm::{check_complete_matrix, ProjectionAlgorithm},
+};
+use crate::{
+    error::{TimecurveError, TimecurveErrorKind},
+    timecurve::Position,
+};
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+/// The way Isomap connects the points of its neighbourhood graph.
+#[derive(Clone, Copy)]
+pub enum NeighbourhoodGraph {
+    /// Each point is connected to its k nearest neighbours (and the graph is made undirected).
+    KNearest(usize),
+    /// Each point is connected to all points closer than epsilon.
+    Epsilon(f64),
+}
+
+/// Structure representing the Isomap algorithm (Tenenbaum et al.).
+///
+/// The distances are replaced by geodesic distances, ie. shortest paths in a neighbourhood graph,
+/// before applying the classical MDS. Data drifting slowly along a curved path is unrolled
+/// instead of producing "horseshoe" artifacts.
+pub struct Isomap {
+    /// The way the neighbourhood graph is built.
+    graph: NeighbourhoodGraph,
+}
+
+impl Isomap {
+    /// Creates a new instance of the Isomap algorithm.
+    ///
+    /// ### Arguments
+    ///
+    /// * `graph` - The way the neighbourhood graph is built. `NeighbourhoodGraph::KNearest(10)` is a reasonable value.
+    ///   The graph must be connected, otherwise the projection fails.
+    pub fn new(graph: NeighbourhoodGraph) -> Self {
+        Self { graph }
+    }
+
+    /// Computes the geodesic distances between all points.
+    ///
+    /// ### Arguments
+    ///
+    /// * `distance_matrix` - The distance matrix.
+    ///
+    /// ### Returns
+    ///
+    /// The matrix of the shortest path lengths in the neighbourhood graph,
+    /// or a `DisconnectedGraph` error if some points can't be reached from the others.
+    pub fn geodesic_distances(
+        &self,
+        distance_matrix: &[Vec<f64>],
+    ) -> Result<Vec<Vec<f64>>, TimecurveError> {
+        let n = check_complete_matrix(distance_matrix)?;
+        let adjacency = self.neighbourhood_graph(distance_matrix)?;
+
+        let mut geodesics = Vec::with_capacity(n);
+        for source in 0..n {
+            let distances = dijkstra(&adjacency, source);
+
+            if let Some(unreachable) = distances.iter().position(|d| d.is_infinite()) {
+                let reachable = distances.iter().filter(|d| d.is_finite()).count();
+                return Err(TimecurveError::new(
+                    TimecurveErrorKind::DisconnectedGraph,
+                    Some(&format!(
+                        "Point {} can't reach point {}, only {} of {} points are connected to it. Try more neighbours or a larger epsilon",
+                        source, unreachable, reachable, n
+                    )),
+                ));
+            }
+
+            geodesics.push(distances);
+        }
+
+        Ok(geodesics)
+    }
+
+    /// Builds the undirected neighbourhood graph.
+    ///
+    /// ### Returns
+    ///
+    /// For each point, the list of its neighbours with the length of the edge.
+    fn neighbourhood_graph(
+        &self,
+        distance_matrix: &[Vec<f64>],
+    ) -> Result<Vec<Vec<(usize, f64)>>, TimecurveError> {
+        let n = distance_matrix.len();
+        let mut connected = vec![vec![false; n]; n];
+
+        match self.graph {
+            NeighbourhoodGraph::KNearest(k) => {
+                if k == 0 {
+                    return Err(TimecurveError::new(
+                        TimecurveErrorKind::InvalidParameter,
+                        Some("Isomap needs at least 1 neighbour"),
+                    ));
+                }
+                for (i, row) in distance_matrix.iter().enumerate() {
+                    let mut nearest: Vec<usize> = (0..n).filter(|&j| j != i).collect();
+                    nearest
+                        .sort_by(|&a, &b| row[a].partial_cmp(&row[b]).unwrap_or(Ordering::Equal));
+                    for &j in nearest.iter().take(k) {
+                        connected[i][j] = true;
+                        connected[j][i] = true;
+                    }
+                }
+            }
+            NeighbourhoodGraph::Epsilon(epsilon) => {
+                for (i, row) in distance_matrix.iter().enumerate() {
+                    for (j, &d) in row.iter().enumerate() {
+                        if i != j && d <= epsilon {
+                            connected[i][j] = true;
+                            connected[j][i] = true;
+                        }
+                    }
+                }
+            }
+        }
+
+        // an asymmetric matrix gives two lengths for the same edge, the mean is used
+        Ok((0..n)
+            .map(|i| {
+                (0..n)
+                    .filter(|&j| connected[i][j])
+                    .map(|j| (j, (distance_matrix[i][j] + distance_matrix[j][i]) / 2.0))
+                    .collect()
+            })
+            .collect())
+    }
+}
+
+impl ProjectionAlgorithm for Isomap {
+    fn project(&self, distance_matrix: &Vec<Vec<f64>>) -> Result<Vec<Position>, TimecurveError> {
+        let geodesics = self.geodesic_distances(distance_matrix)?;
+        ClassicalMDS::new().project_rows(&geodesics)
+    }
+}
+
+/// A node waiting to be visited by Dijkstra's algorithm, ordered by increasing distance.
+struct Visit {
+    distance: f64,
+    node: usize,
+}
+
+impl PartialEq for Visit {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Visit {}
+
+impl PartialOrd for Visit {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Visit {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed so that the binary heap pops the closest node first
+        other.distance.total_cmp(&self.distance)
+    }
+}
+
+/// Computes the length of the shortest paths from one point to all others with Dijkstra's algorithm.
+///
+/// ### Arguments
+///
+/// * `adjacency` - For each point, the list of its neighbours with the length of the edge.
+/// * `source` - The starting point.
+///
+/// ### Returns
+///
+/// The length of the shortest path to each point, infinite if it can't be reached.
+pub(crate) fn dijkstra(adjacency: &[Vec<(usize, f64)>], source: usize) -> Vec<f64> {
+    let mut distances = vec![f64::INFINITY; adjacency.len()];
+    let mut heap = BinaryHeap::new();
+
+    distances[source] = 0.0;
+    heap.push(Visit {
+        distance: 0.0,
+        node: source,
+    });
+
+    while let Some(Visit { distance, node }) = heap.pop() {
+        if distance > distances[node] {
+            continue;
+        }
+        for &(next, length) in &adjacency[node] {
+            let candidate = distance + length;
+            if candidate < distances[next] {
+                distances[next] = candidate;
+                heap.push(Visit {
+                    distance: candidate,
+                    node: next,
+                });
+            }
+        }
+    }
+
+    distances
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds the distance matrix of points on a half circle, which classical MDS bends into a horseshoe.
+    fn half_circle_distance_matrix() -> Vec<Vec<f64>> {
+        let points: Vec<Position> = (0..30)
+            .map(|i| {
+                let angle = std::f64::consts::PI * i as f64 / 29.0;
+                Position::new(angle.cos(), angle.sin())
+            })
+            .collect();
+        points
+            .iter()
+            .map(|a| points.iter().map(|b| a.distance(b)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn isomap_unrolls_curved_data() {
+        let distance_matrix = half_circle_distance_matrix();
+        let isomap = Isomap::new(NeighbourhoodGraph::KNearest(2));
+
+        let points = isomap.project(&distance_matrix).unwrap();
+
+        // the geodesic distance between the two ends is half the circumference
+        let ends = points[0].distance(&points[29]);
+        assert!((ends - std::f64::consts::PI).abs() < 0.01);
+
+        // and the points are laid out in order along a line
+        for i in 1..29 {
+            assert!(points[0].distance(&points[i]) < points[0].distance(&points[i + 1]));
+        }
+    }
+
+    #[test]
+    fn isomap_fails_on_disconnected_graph() {
+        let distance_matrix = half_circle_distance_matrix();
+
+        let result = Isomap::new(NeighbourhoodGraph::Epsilon(0.01)).project(&distance_matrix);
+
+        match result {
+            Err(e) => assert!(matches!(e.kind, TimecurveErrorKind::DisconnectedGraph)),
+            Ok(_) => panic!("a disconnected graph should not be projected"),
+        }
+    }
+
+    #[test]
+    fn dijkstra_finds_shortest_paths() {
+        // 0 - 1 - 2 with a long direct edge 0 - 2, and 3 isolated
+        let adjacency = vec![
+            vec![(1, 1.0), (2, 5.0)],
+            vec![(0, 1.0), (2, 1.0)],
+            vec![(0, 5.0), (1, 1.0)],
+            vec![],
+        ];
+
+        let distances = dijkstra(&adjacency, 0);
+
+        assert_eq!(distances[..3], [0.0, 1.0, 2.0]);
+        assert!(distances[3].is_infinite());
+    }
+}
@@ -0,0 +1,262 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+use super::projection_algorithm::{check_square_matrix, ProjectionAlgorithm};
+use crate::{
+    error::{TimecurveError, TimecurveErrorKind},
+    timecurve::Position,
+};
+use nalgebra::DMatrix;
+use rand::{rngs::StdRng, SeedableRng};
+
+/// The method used by the landmark MDS to choose its landmarks.
+#[derive(Clone, Copy)]
+pub enum LandmarkSelection {
+    /// The landmarks are chosen uniformly at random, with the given seed.
+    Random(u64),
+    /// The first landmark is the first point, each next landmark is the point
+    /// farthest away from all the landmarks already chosen. Gives a good coverage of the data.
+    MaxMin,
+}
+
+/// Structure representing the Landmark Multidimensional Scaling algorithm (de Silva & Tenenbaum).
+///
+/// A classical MDS is computed on a small set of landmarks only, and the other points are then placed
+/// by triangulation from their distances to the landmarks. The cost is linear in the number of points,
+/// which makes it usable on matrices with thousands of points. Only the distances between the landmarks
+/// and the other points are read from the matrix, so the other distances may be missing (NaN).
+pub struct LandmarkMDS {
+    /// The number of landmarks.
+    landmarks: usize,
+    /// The method used to choose the landmarks.
+    selection: LandmarkSelection,
+}
+
+impl LandmarkMDS {
+    /// Creates a new instance of the landmark MDS algorithm.
+    ///
+    /// ### Arguments
+    ///
+    /// * `landmarks` - The number of landmarks. Must be at least 3. 100 is a reasonable value.
+    ///   If there are less points than landmarks, all points are used as landmarks and the result is the classical MDS.
+    /// * `selection` - The method used to choose the landmarks.
+    pub fn new(landmarks: usize, selection: LandmarkSelection) -> Self {
+        Self {
+            landmarks,
+            selection,
+        }
+    }
+
+    /// Chooses the landmarks among the points of the distance matrix.
+    ///
+    /// ### Arguments
+    ///
+    /// * `distance_matrix` - The distance matrix.
+    ///
+    /// ### Returns
+    ///
+    /// The indices of the landmarks.
+    fn select_landmarks(&self, distance_matrix: &[Vec<f64>]) -> Vec<usize> {
+        let n = distance_matrix.len();
+        let k = self.landmarks.min(n);
+
+        match self.selection {
+            LandmarkSelection::Random(seed) => {
+                let mut rng = StdRng::seed_from_u64(seed);
+                let mut indices = rand::seq::index::sample(&mut rng, n, k).into_vec();
+                indices.sort();
+                indices
+            }
+            LandmarkSelection::MaxMin => {
+                let mut indices = vec![0];
+                // distance from each point to the closest landmark
+                let mut min_distances = distance_matrix[0].clone();
+
+                while indices.len() < k {
+                    let next = (0..n)
+                        .max_by(|&a, &b| {
+                            min_distances[a]
+                                .partial_cmp(&min_distances[b])
+                                .unwrap_or(std::cmp::Ordering::Equal)
+                        })
+                        .unwrap(); // okay to unwrap because n > 0
+
+                    indices.push(next);
+                    for i in 0..n {
+                        min_distances[i] = min_distances[i].min(distance_matrix[next][i]);
+                    }
+                }
+                indices
+            }
+        }
+    }
+}
+
+impl ProjectionAlgorithm for LandmarkMDS {
+    fn project(&self, distance_matrix: &Vec<Vec<f64>>) -> Result<Vec<Position>, TimecurveError> {
+        let n = check_square_matrix(distance_matrix)?;
+
+        if self.landmarks < 3 {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::InvalidParameter,
+                Some(&format!(
+                    "Landmark MDS needs at least 3 landmarks, got {}",
+                    self.landmarks
+                )),
+            ));
+        }
+
+        let landmarks = self.select_landmarks(distance_matrix);
+        let k = landmarks.len();
+
+        // the distances to the landmarks are the only ones needed
+        if let Some((a, l)) = (0..n)
+            .flat_map(|a| landmarks.iter().map(move |&l| (a, l)))
+            .find(|&(a, l)| distance_matrix[a][l].is_nan())
+        {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::MalformedDistanceMatrix,
+                Some(&format!(
+                    "The distance ({}, {}) to landmark {} is missing. Use other landmarks, or complete the matrix with ShortestPathCompletion",
+                    a, l, l
+                )),
+            ));
+        }
+
+        // squared distances between the landmarks
+        let delta = DMatrix::from_fn(k, k, |i, j| {
+            distance_matrix[landmarks[i]][landmarks[j]].powi(2)
+        });
+
+        // mean of each column of the squared distances, needed for the triangulation
+        let delta_mean: Vec<f64> = (0..k).map(|j| delta.column(j).mean()).collect();
+        let total_mean = delta.mean();
+
+        // classical MDS on the landmarks : B = -1/2 H Δ H, computed by double centering
+        let b = DMatrix::from_fn(k, k, |i, j| {
+            -0.5 * (delta[(i, j)] - delta_mean[i] - delta_mean[j] + total_mean)
+        });
+
+        let decomposition = b.symmetric_eigen();
+        let mut order: Vec<usize> = (0..k).collect();
+        order.sort_by(|&a, &b| {
+            decomposition.eigenvalues[b]
+                .partial_cmp(&decomposition.eigenvalues[a])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        // pseudo-inverse transpose of the landmark coordinates : its rows are ei / sqrt(λi)
+        // negative eigenvalues come from non-euclidean distances and are dropped
+        let pseudo_inverse: Vec<Vec<f64>> = order
+            .iter()
+            .take(2)
+            .map(|&c| {
+                let lambda = decomposition.eigenvalues[c];
+                if lambda <= 0.0 {
+                    return vec![0.0; k];
+                }
+                decomposition
+                    .eigenvectors
+                    .column(c)
+                    .iter()
+                    .map(|v| v / lambda.sqrt())
+                    .collect()
+            })
+            .collect();
+
+        // triangulation : xa = -1/2 L# (δa - δμ), where δa are the squared distances from a to the landmarks
+        let mut points = Vec::with_capacity(n);
+        for distances_from_a in distance_matrix.iter() {
+            let mut coordinates = [0.0; 2];
+            for (c, row) in pseudo_inverse.iter().enumerate() {
+                coordinates[c] = -0.5
+                    * landmarks
+                        .iter()
+                        .enumerate()
+                        .map(|(l, &landmark)| {
+                            row[l] * (distances_from_a[landmark].powi(2) - delta_mean[l])
+                        })
+                        .sum::<f64>();
+            }
+            points.push(Position::new(coordinates[0], coordinates[1]));
+        }
+
+        Ok(points)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds the distance matrix of points on a 2D grid.
+    fn grid_distance_matrix(size: usize) -> Vec<Vec<f64>> {
+        let points: Vec<Position> = (0..size * size)
+            .map(|i| Position::new((i % size) as f64, (i / size) as f64))
+            .collect();
+        points
+            .iter()
+            .map(|a| points.iter().map(|b| a.distance(b)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn landmark_mds_preserves_euclidean_distances() {
+        let distance_matrix = grid_distance_matrix(8);
+
+        for selection in [LandmarkSelection::Random(42), LandmarkSelection::MaxMin] {
+            let lmds = LandmarkMDS::new(10, selection);
+            let points = lmds.project(&distance_matrix).unwrap();
+
+            assert_eq!(points.len(), 64);
+            for i in 0..64 {
+                for j in 0..64 {
+                    assert!((points[i].distance(&points[j]) - distance_matrix[i][j]).abs() < 1e-6);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn landmark_mds_max_min_spreads_the_landmarks() {
+        let distance_matrix = grid_distance_matrix(5);
+        let lmds = LandmarkMDS::new(4, LandmarkSelection::MaxMin);
+
+        let mut landmarks = lmds.select_landmarks(&distance_matrix);
+        landmarks.sort();
+
+        // starting from a corner, the next landmarks are the other corners
+        assert_eq!(landmarks, vec![0, 4, 20, 24]);
+    }
+
+    #[test]
+    fn landmark_mds_ignores_missing_distances_between_other_points() {
+        let mut distance_matrix = grid_distance_matrix(8);
+        let lmds = LandmarkMDS::new(10, LandmarkSelection::MaxMin);
+        let landmarks = lmds.select_landmarks(&distance_matrix);
+        let others: Vec<usize> = (0..64).filter(|i| !landmarks.contains(i)).collect();
+
+        // a distance between two points that aren't landmarks doesn't change the result
+        let (a, b) = (others[0], others[1]);
+        distance_matrix[a][b] = f64::NAN;
+        distance_matrix[b][a] = f64::NAN;
+        let points = lmds.project(&distance_matrix).unwrap();
+        assert!((points[a].distance(&points[b]) - grid_distance_matrix(8)[a][b]).abs() < 1e-6);
+
+        // but the distances to the landmarks are needed
+        distance_matrix[a][landmarks[1]] = f64::NAN;
+        assert!(lmds.project(&distance_matrix).is_err());
+    }
+
+    #[test]
+    fn landmark_mds_rejects_too_few_landmarks() {
+        let distance_matrix = grid_distance_matrix(3);
+
+        assert!(LandmarkMDS::new(2, LandmarkSelection::MaxMin)
+            .project(&distance_matrix)
+            .is_err());
+    }
+}
@@ -0,0 +1,33 @@
+mod anchored_smacof;
+mod classical_mds;
+mod completion;
+mod isomap;
+mod landmark_mds;
+mod non_metric_mds;
+mod out_of_sample;
+mod pca;
+mod projection_algorithm;
+pub mod quality;
+mod sammon;
+mod smacof;
+mod temporal_smacof;
+mod tsne;
+mod umap;
+
+// use pour pouvoir écrire projection::Struct au lieu de projection::fichier::Struct
+pub use anchored_smacof::AnchoredSMACOF;
+pub use classical_mds::ClassicalMDS;
+pub use completion::ShortestPathCompletion;
+pub use isomap::{Isomap, NeighbourhoodGraph};
+pub use landmark_mds::{LandmarkMDS, LandmarkSelection};
+pub use non_metric_mds::NonMetricMDS;
+pub use out_of_sample::place_new_point;
+pub use pca::PCA;
+pub(crate) use projection_algorithm::{check_square_matrix, missing_distances};
+pub use projection_algorithm::{ProjectionAlgorithm, ProjectionContext};
+pub use quality::QualityReport;
+pub use sammon::Sammon;
+pub use smacof::SMACOF;
+pub use temporal_smacof::TemporalSMACOF;
+pub use tsne::{TSNEMode, TSNE};
+pub use umap::UMAP;
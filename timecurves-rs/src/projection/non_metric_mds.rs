@@ -0,0 +1,240 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+use super::{
+    classical_mds::ClassicalMDS,
+    projection_algorithm::{check_complete_matrix, ProjectionAlgorithm},
+    smacof::{circle_layout, guttman_transform},
+};
+use crate::{error::TimecurveError, timecurve::Position};
+use log::info;
+
+/// Structure representing Kruskal's non-metric Multidimensional Scaling algorithm.
+///
+/// Only the rank order of the distances is preserved : the projected distances are fitted
+/// to a monotonic transformation of the given ones (the disparities) through isotonic regression.
+/// This is suited to distance matrices that are only meaningful up to rank order, like edit counts.
+pub struct NonMetricMDS {
+    /// The maximum number of iterations.
+    max_iterations: usize,
+    /// The algorithm stops when the Stress-1 improves by less than this value between two iterations.
+    tolerance: f64,
+}
+
+impl NonMetricMDS {
+    /// Creates a new instance of the non-metric MDS algorithm.
+    ///
+    /// ### Arguments
+    ///
+    /// * `max_iterations` - The maximum number of iterations. 300 is a reasonable value.
+    /// * `tolerance` - The algorithm stops when the Stress-1 improves by less than this value. 1e-6 is a reasonable value.
+    pub fn new(max_iterations: usize, tolerance: f64) -> Self {
+        Self {
+            max_iterations,
+            tolerance,
+        }
+    }
+
+    /// Projects the points like `project`, but also returns the final Stress-1 of the layout.
+    ///
+    /// ### Arguments
+    ///
+    /// * `distance_matrix` - A reference to a vector of rows representing the distance matrix.
+    ///
+    /// ### Returns
+    ///
+    /// A tuple containing the projected points and Kruskal's Stress-1,
+    /// ie. sqrt(Σ(dij - d̂ij)² / Σdij²), where d are the projected distances and d̂ the disparities.
+    pub fn project_with_stress(
+        &self,
+        distance_matrix: &[Vec<f64>],
+    ) -> Result<(Vec<Position>, f64), TimecurveError> {
+        let n = check_complete_matrix(distance_matrix)?;
+
+        let mut layout = ClassicalMDS::new().project_rows(distance_matrix)?;
+        if layout
+            .iter()
+            .any(|p| !p.get_x().is_finite() || !p.get_y().is_finite())
+        {
+            layout = circle_layout(distance_matrix);
+        }
+
+        // pairs (i, j) with i < j, sorted by increasing dissimilarity
+        // the disparities are fitted in this order
+        let mut pairs: Vec<(usize, usize)> = Vec::with_capacity(n * (n - 1) / 2);
+        for i in 0..n {
+            for j in (i + 1)..n {
+                pairs.push((i, j));
+            }
+        }
+        pairs.sort_by(|a, b| {
+            distance_matrix[a.0][a.1]
+                .partial_cmp(&distance_matrix[b.0][b.1])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        // the disparities are scaled so that their sum of squares matches the one of the input,
+        // which keeps the layout at the same scale as the original distances
+        let target_norm: f64 = pairs
+            .iter()
+            .map(|&(i, j)| distance_matrix[i][j].powi(2))
+            .sum();
+
+        let mut disparities = vec![vec![0.0; n]; n];
+        let mut stress = f64::INFINITY;
+        let mut iterations = 0;
+
+        while iterations < self.max_iterations {
+            // fit the disparities to the current distances
+            let distances: Vec<f64> = pairs
+                .iter()
+                .map(|&(i, j)| layout[i].distance(&layout[j]))
+                .collect();
+            let mut fitted = isotonic_regression(&distances);
+
+            let fitted_norm: f64 = fitted.iter().map(|d| d * d).sum();
+            if fitted_norm > 0.0 {
+                let scale = (target_norm / fitted_norm).sqrt();
+                fitted.iter_mut().for_each(|d| *d *= scale);
+            }
+
+            for (k, &(i, j)) in pairs.iter().enumerate() {
+                disparities[i][j] = fitted[k];
+                disparities[j][i] = fitted[k];
+            }
+
+            // then move the points towards the disparities
+            layout = guttman_transform(&disparities, &layout);
+            iterations += 1;
+
+            let new_stress = stress_1(&disparities, &layout);
+            let improvement = stress - new_stress;
+            stress = new_stress;
+
+            if improvement < self.tolerance {
+                break;
+            }
+        }
+
+        info!(
+            "Non-metric MDS stopped after {} iterations, Stress-1 = {:.6}",
+            iterations, stress
+        );
+
+        Ok((layout, stress))
+    }
+}
+
+impl ProjectionAlgorithm for NonMetricMDS {
+    fn project(&self, distance_matrix: &Vec<Vec<f64>>) -> Result<Vec<Position>, TimecurveError> {
+        Ok(self.project_with_stress(distance_matrix)?.0)
+    }
+}
+
+/// Computes the monotonic (non-decreasing) least-squares fit of a sequence,
+/// using the Pool Adjacent Violators Algorithm.
+///
+/// ### Arguments
+///
+/// * `values` - The values to fit, in the order in which the result must be non-decreasing.
+///
+/// ### Returns
+///
+/// The fitted values, in the same order.
+fn isotonic_regression(values: &[f64]) -> Vec<f64> {
+    // each block holds (sum of its values, number of values)
+    let mut blocks: Vec<(f64, usize)> = Vec::with_capacity(values.len());
+
+    for &v in values {
+        blocks.push((v, 1));
+
+        // merge the last blocks while their means are decreasing
+        while blocks.len() > 1 {
+            let (sum_last, len_last) = blocks[blocks.len() - 1];
+            let (sum_prev, len_prev) = blocks[blocks.len() - 2];
+            if sum_prev / len_prev as f64 <= sum_last / len_last as f64 {
+                break;
+            }
+            blocks.pop();
+            let last = blocks.len() - 1;
+            blocks[last] = (sum_prev + sum_last, len_prev + len_last);
+        }
+    }
+
+    blocks
+        .iter()
+        .flat_map(|&(sum, len)| std::iter::repeat(sum / len as f64).take(len))
+        .collect()
+}
+
+/// Computes Kruskal's Stress-1 of a layout, ie. sqrt(Σ(dij - d̂ij)² / Σdij²).
+///
+/// ### Arguments
+///
+/// * `disparities` - The fitted disparities.
+/// * `layout` - The positions of the points.
+///
+/// ### Returns
+///
+/// The Stress-1 value. 0.0 means the rank order of the distances is perfectly preserved.
+fn stress_1(disparities: &[Vec<f64>], layout: &[Position]) -> f64 {
+    let mut raw_stress = 0.0;
+    let mut norm = 0.0;
+
+    for i in 0..layout.len() {
+        for j in (i + 1)..layout.len() {
+            let d = layout[i].distance(&layout[j]);
+            raw_stress += (d - disparities[i][j]).powi(2);
+            norm += d * d;
+        }
+    }
+
+    if norm == 0.0 {
+        return 0.0;
+    }
+
+    (raw_stress / norm).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn isotonic_regression_is_monotonic() {
+        let fitted = isotonic_regression(&[1.0, 3.0, 2.0, 4.0, 0.0, 6.0]);
+
+        assert_eq!(fitted.len(), 6);
+        for i in 1..fitted.len() {
+            assert!(fitted[i - 1] <= fitted[i]);
+        }
+        // the sum is preserved by the pooling
+        assert!((fitted.iter().sum::<f64>() - 16.0).abs() < 1e-9);
+
+        assert_eq!(isotonic_regression(&[1.0, 2.0, 3.0]), vec![1.0, 2.0, 3.0]);
+    }
+
+    #[test]
+    fn non_metric_mds_preserves_rank_order() {
+        // points on a line at positions 0, 1, 3, 7, with distances squared
+        // the ranks are preserved by a monotonic transformation, so the stress should be very low
+        let positions = [0.0_f64, 1.0, 3.0, 7.0];
+        let distance_matrix: Vec<Vec<f64>> = positions
+            .iter()
+            .map(|a| positions.iter().map(|b| (a - b).powi(2)).collect())
+            .collect();
+
+        let nmds = NonMetricMDS::new(500, 1e-9);
+        let (points, stress) = nmds.project_with_stress(&distance_matrix).unwrap();
+
+        assert_eq!(points.len(), 4);
+        assert!(stress < 0.05);
+
+        // the farthest pair in the input is still the farthest one in the projection
+        assert!(points[0].distance(&points[3]) > points[0].distance(&points[2]));
+        assert!(points[0].distance(&points[2]) > points[0].distance(&points[1]));
+    }
+}
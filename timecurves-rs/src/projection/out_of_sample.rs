@@ -0,0 +1,178 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+use crate::{
+    error::{TimecurveError, TimecurveErrorKind},
+    timecurve::Position,
+};
+
+/// Places a new point in an existing projection without moving the other points.
+///
+/// The starting position is given by Gower's add-a-point formula, which is exact when the layout is a classical MDS
+/// of euclidean distances, then it is refined by minimising the stress of the new point alone,
+/// so that it also works for the layouts of iterative projectors like SMACOF.
+/// In a 3D layout, the starting position is in the plane z = 0 and the refinement moves it along the z axis too.
+///
+/// ### Arguments
+///
+/// * `layout` - The positions of the projected points.
+/// * `distances` - The distances from the new point to each projected point, in the same order,
+///   and at the scale of the layout. Missing distances (NaN) are ignored.
+///
+/// ### Returns
+///
+/// The position of the new point, or an error if the layout is empty, the number of distances differs
+/// or all the distances are missing.
+pub fn place_new_point(layout: &[Position], distances: &[f64]) -> Result<Position, TimecurveError> {
+    const MAX_ITERATIONS: usize = 200;
+    const TOLERANCE: f64 = 1e-12;
+
+    if layout.is_empty() || layout.len() != distances.len() {
+        return Err(TimecurveError::new(
+            TimecurveErrorKind::InvalidParameter,
+            Some(&format!(
+                "Got {} distances for {} projected points",
+                distances.len(),
+                layout.len()
+            )),
+        ));
+    }
+    if let Some(j) = distances.iter().position(|d| d.is_infinite()) {
+        return Err(TimecurveError::new(
+            TimecurveErrorKind::InvalidParameter,
+            Some(&format!("Distance to point {} is infinite", j)),
+        ));
+    }
+
+    // only the points whose distance is known are used
+    let (layout, distances): (Vec<Position>, Vec<f64>) = layout
+        .iter()
+        .zip(distances)
+        .filter(|(_, d)| !d.is_nan())
+        .map(|(p, d)| (*p, *d))
+        .unzip();
+    if layout.is_empty() {
+        return Err(TimecurveError::new(
+            TimecurveErrorKind::InvalidParameter,
+            Some("All the distances to the projected points are missing"),
+        ));
+    }
+
+    let mut position = gower_position(&layout, &distances).unwrap_or_else(|| {
+        // degenerate layout, eg. all points on a line : start next to the closest point
+        let closest = (0..layout.len())
+            .min_by(|&a, &b| distances[a].total_cmp(&distances[b]))
+            .unwrap();
+        Position::new(
+            layout[closest].get_x() + distances[closest],
+            layout[closest].get_y(),
+        )
+    });
+
+    // local stress minimisation : Guttman transform applied to the new point only
+    let n = layout.len() as f64;
+    for _ in 0..MAX_ITERATIONS {
+        let mut x = 0.0;
+        let mut y = 0.0;
+        let mut z = 0.0;
+        for (p, delta) in layout.iter().zip(&distances) {
+            let d = position.distance(p);
+            let ratio = if d > 0.0 { delta / d } else { 0.0 };
+            x += p.get_x() + ratio * (position.get_x() - p.get_x());
+            y += p.get_y() + ratio * (position.get_y() - p.get_y());
+            z += p.get_z() + ratio * (position.get_z() - p.get_z());
+        }
+
+        let next = Position::new_3d(x / n, y / n, z / n);
+        let shift = next.distance(&position);
+        position = next;
+
+        if shift < TOLERANCE {
+            break;
+        }
+    }
+
+    Ok(position)
+}
+
+/// Computes Gower's add-a-point formula, ie. the least squares solution of
+/// |x - yj|² = δj² for all projected points yj.
+///
+/// ### Returns
+///
+/// The position, or `None` if the layout is degenerate.
+fn gower_position(layout: &[Position], distances: &[f64]) -> Option<Position> {
+    let n = layout.len() as f64;
+    let center = Position::new(
+        layout.iter().map(|p| p.get_x()).sum::<f64>() / n,
+        layout.iter().map(|p| p.get_y()).sum::<f64>() / n,
+    );
+    let centred: Vec<(f64, f64)> = layout
+        .iter()
+        .map(|p| (p.get_x() - center.get_x(), p.get_y() - center.get_y()))
+        .collect();
+
+    // substracting the mean of |x - yj|² = |x|² - 2 x.yj + |yj|² removes |x|²,
+    // which leaves the linear system 2 yj.x = (|yj|² - mean|y|²) - (δj² - mean δ²)
+    let mean_norm = centred.iter().map(|(x, y)| x * x + y * y).sum::<f64>() / n;
+    let mean_squared = distances.iter().map(|d| d * d).sum::<f64>() / n;
+
+    let mut normal = [[0.0; 2]; 2];
+    let mut rhs = [0.0; 2];
+    for ((x, y), delta) in centred.iter().zip(distances) {
+        let b = ((x * x + y * y - mean_norm) - (delta * delta - mean_squared)) / 2.0;
+        normal[0][0] += x * x;
+        normal[0][1] += x * y;
+        normal[1][1] += y * y;
+        rhs[0] += x * b;
+        rhs[1] += y * b;
+    }
+
+    let determinant = normal[0][0] * normal[1][1] - normal[0][1] * normal[0][1];
+    if determinant.abs() <= f64::EPSILON * (normal[0][0] * normal[1][1]).max(f64::MIN_POSITIVE) {
+        return None;
+    }
+
+    let x = (normal[1][1] * rhs[0] - normal[0][1] * rhs[1]) / determinant;
+    let y = (normal[0][0] * rhs[1] - normal[0][1] * rhs[0]) / determinant;
+
+    Some(Position::new(x + center.get_x(), y + center.get_y()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_point_is_placed_exactly_in_euclidean_layout() {
+        let layout = [
+            Position::new(0.0, 0.0),
+            Position::new(4.0, 0.0),
+            Position::new(4.0, 3.0),
+            Position::new(0.0, 3.0),
+        ];
+        let target = Position::new(1.0, 2.0);
+        let distances: Vec<f64> = layout.iter().map(|p| p.distance(&target)).collect();
+
+        let placed = place_new_point(&layout, &distances).unwrap();
+
+        assert!(placed.distance(&target) < 1e-9);
+    }
+
+    #[test]
+    fn new_point_is_placed_in_collinear_layout() {
+        let layout = [
+            Position::new(0.0, 0.0),
+            Position::new(1.0, 0.0),
+            Position::new(2.0, 0.0),
+        ];
+
+        let placed = place_new_point(&layout, &[3.0, 2.0, 1.0]).unwrap();
+
+        assert!(placed.distance(&Position::new(3.0, 0.0)) < 1e-6);
+        assert!(place_new_point(&layout, &[1.0, 2.0]).is_err());
+    }
+}
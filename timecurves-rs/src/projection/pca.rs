@@ -0,0 +1,196 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+use super::{
+    classical_mds::{top_eigenpairs, ClassicalMDS},
+    projection_algorithm::{ProjectionAlgorithm, ProjectionContext},
+};
+use crate::{
+    distance::check_features,
+    error::{TimecurveError, TimecurveErrorKind},
+    timecurve::Position,
+};
+use nalgebra::DMatrix;
+
+/// Structure representing the Principal Component Analysis (PCA),
+/// which projects the feature vectors of the points on their directions of largest variance.
+///
+/// The feature vectors are read from the `ProjectionContext`, ie. from the `features` array of the input file,
+/// so the distance matrix is not used. Without feature vectors, eg. when called with `project` alone,
+/// it falls back to the classical MDS of the distance matrix, which gives the same layout for euclidean distances.
+pub struct PCA {
+    /// The number of dimensions of the projection, 2 or 3.
+    dimensions: usize,
+}
+
+impl PCA {
+    /// Creates a new instance of the PCA, projecting in 2 dimensions.
+    /// Takes no arguments.
+    pub fn new() -> Self {
+        PCA { dimensions: 2 }
+    }
+
+    /// Sets the number of dimensions of the projection.
+    ///
+    /// ### Arguments
+    ///
+    /// * `dimensions` - The number of dimensions, 2 or 3. Other values make the projection fail.
+    pub fn with_dimensions(mut self, dimensions: usize) -> Self {
+        self.dimensions = dimensions;
+        self
+    }
+
+    /// Projects feature vectors on their principal components.
+    ///
+    /// ### Arguments
+    ///
+    /// * `features` - One numeric vector per point, all of the same length.
+    ///
+    /// ### Returns
+    ///
+    /// The coordinates of the points on the first principal components, centred on the origin.
+    /// An error is returned if the vectors don't all have the same length or contain non-finite values.
+    pub fn project_features(&self, features: &[Vec<f64>]) -> Result<Vec<Position>, TimecurveError> {
+        let p = check_features(features)?;
+        let n = features.len();
+
+        if !(2..=3).contains(&self.dimensions) {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::InvalidParameter,
+                Some(&format!(
+                    "The PCA projects in 2 or 3 dimensions, got {}",
+                    self.dimensions
+                )),
+            ));
+        }
+
+        let means: Vec<f64> = (0..p)
+            .map(|k| features.iter().map(|v| v[k]).sum::<f64>() / n.max(1) as f64)
+            .collect();
+        let centred = DMatrix::from_fn(n, p, |i, k| features[i][k] - means[k]);
+
+        // the principal axes are the top eigenvectors of the p x p covariance matrix,
+        // but when there are more features than points, the n x n Gram matrix is smaller
+        // and its eigenvectors scaled by √λ directly give the coordinates, like in the classical MDS
+        let coordinates = if p <= n {
+            let covariance = centred.transpose() * &centred;
+            let axes = top_eigenpairs(&covariance, self.dimensions);
+            DMatrix::from_fn(n, self.dimensions, |i, k| match axes.get(k) {
+                Some((_, axis)) => centred.row(i).transpose().dot(axis),
+                None => 0.0,
+            })
+        } else {
+            let gram = &centred * centred.transpose();
+            let couples = top_eigenpairs(&gram, self.dimensions);
+            DMatrix::from_fn(n, self.dimensions, |i, k| match couples.get(k) {
+                Some((lambda, e)) => e[i] * lambda.max(0.0).sqrt(),
+                None => 0.0,
+            })
+        };
+
+        Ok((0..n)
+            .map(|i| {
+                if self.dimensions == 3 {
+                    Position::new_3d(
+                        coordinates[(i, 0)],
+                        coordinates[(i, 1)],
+                        coordinates[(i, 2)],
+                    )
+                } else {
+                    Position::new(coordinates[(i, 0)], coordinates[(i, 1)])
+                }
+            })
+            .collect())
+    }
+}
+
+impl Default for PCA {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProjectionAlgorithm for PCA {
+    fn project(&self, distance_matrix: &Vec<Vec<f64>>) -> Result<Vec<Position>, TimecurveError> {
+        ClassicalMDS::new()
+            .with_dimensions(self.dimensions)
+            .project(distance_matrix)
+    }
+
+    fn project_with_context(
+        &self,
+        distance_matrix: &Vec<Vec<f64>>,
+        context: &ProjectionContext,
+    ) -> Result<Vec<Position>, TimecurveError> {
+        match context.get_features() {
+            Some(features) => {
+                if features.len() != distance_matrix.len() {
+                    return Err(TimecurveError::new(
+                        TimecurveErrorKind::InvalidParameter,
+                        Some(&format!(
+                            "There are {} feature vectors for {} points",
+                            features.len(),
+                            distance_matrix.len()
+                        )),
+                    ));
+                }
+                self.project_features(features)
+            }
+            None => self.project(distance_matrix),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distance::{distance_matrix, Metric};
+
+    #[test]
+    fn pca_preserves_flat_features() {
+        // points of a plane tilted in 4 dimensions
+        let flat = [(0.0, 0.0), (4.0, 0.0), (4.0, 1.0), (0.0, 1.0), (2.0, 3.0)];
+        let features: Vec<Vec<f64>> = flat
+            .iter()
+            .map(|&(a, b)| vec![a + b, a - b, 2.0 * b, 5.0])
+            .collect();
+        let distances = distance_matrix(&features, Metric::Euclidean).unwrap();
+
+        let points = PCA::new().project_features(&features).unwrap();
+
+        for i in 0..points.len() {
+            for j in 0..points.len() {
+                assert!((points[i].distance(&points[j]) - distances[i][j]).abs() < 1e-6);
+            }
+        }
+        // the first axis carries the most variance
+        let spread = |f: fn(&Position) -> f64| points.iter().map(|p| f(p) * f(p)).sum::<f64>();
+        assert!(spread(Position::get_x) >= spread(Position::get_y));
+    }
+
+    #[test]
+    fn pca_with_more_features_than_points() {
+        let features = vec![
+            vec![1.0, 0.0, 0.0, 0.0, 0.0],
+            vec![0.0, 1.0, 0.0, 0.0, 0.0],
+            vec![0.0, 0.0, 1.0, 0.0, 0.0],
+        ];
+        let context =
+            ProjectionContext::new(vec![0, 1, 2], vec![0, 0, 0]).with_features(Some(features));
+        let distances = vec![vec![0.0; 3]; 3];
+
+        let points = PCA::new()
+            .project_with_context(&distances, &context)
+            .unwrap();
+
+        // an equilateral triangle of side √2
+        assert!((points[0].distance(&points[1]) - 2.0_f64.sqrt()).abs() < 1e-6);
+        assert!((points[1].distance(&points[2]) - 2.0_f64.sqrt()).abs() < 1e-6);
+
+        let wrong = ProjectionContext::new(vec![0], vec![0]).with_features(Some(vec![vec![0.0]]));
+        assert!(PCA::new().project_with_context(&distances, &wrong).is_err());
+    }
+}
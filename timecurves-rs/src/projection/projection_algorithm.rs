@@ -0,0 +1,342 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+use crate::{
+    error::{TimecurveError, TimecurveErrorKind},
+    input::InputData,
+    timecurve::{label_to_time, Position},
+};
+
+/// Trait representing a projection algorithm.
+pub trait ProjectionAlgorithm {
+    /// Projects points described by a distance matrix onto a 2D space.
+    ///
+    /// ### Arguments
+    ///
+    /// * `distance_matrix` - A reference to a vector of rows representing the distance matrix.
+    ///
+    /// ### Returns
+    ///
+    /// Returns a vector of tuples (x,y) representing the projected points.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `TimecurveError` if the projection fails.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use timecurves_rs::projection::{ProjectionAlgorithm, ClassicalMDS};
+    ///
+    /// let distance_matrix : Vec<Vec<f64>> = vec![
+    ///     vec![0.0, 1.0, 2.0],
+    ///     vec![1.0, 0.0, 3.0],
+    ///     vec![2.0, 3.0, 0.0]
+    /// ];
+    ///
+    /// let mds = ClassicalMDS::new();
+    ///
+    /// let result = mds.project(&distance_matrix);
+    ///
+    /// match result {
+    ///     Ok(points) => {
+    ///         for point in points {
+    ///             println!("({},{})", point.get_x(), point.get_y());
+    ///         }
+    ///     }
+    ///     Err(e) => {
+    ///         println!("Error while computing the projection :");
+    ///         println!("{}", e);
+    ///     }
+    /// }
+    /// ```
+    fn project(&self, distance_matrix: &Vec<Vec<f64>>) -> Result<Vec<Position>, TimecurveError>;
+
+    /// Projects points described by a distance matrix onto a 2D space, knowing when each point happened
+    /// and which dataset it belongs to. This is what `TimecurveSet::new` calls.
+    ///
+    /// Algorithms that only need the distances don't have to implement it, by default it calls `project`.
+    ///
+    /// ### Arguments
+    ///
+    /// * `distance_matrix` - A reference to a vector of rows representing the distance matrix.
+    /// * `context` - The timestamps and dataset membership of the rows of the matrix.
+    ///
+    /// ### Returns
+    ///
+    /// Returns a vector of tuples (x,y) representing the projected points.
+    fn project_with_context(
+        &self,
+        distance_matrix: &Vec<Vec<f64>>,
+        _context: &ProjectionContext,
+    ) -> Result<Vec<Position>, TimecurveError> {
+        self.project(distance_matrix)
+    }
+}
+
+/// Allows choosing the projection algorithm at runtime, eg. from a command line option.
+impl<T: ProjectionAlgorithm + ?Sized> ProjectionAlgorithm for Box<T> {
+    fn project(&self, distance_matrix: &Vec<Vec<f64>>) -> Result<Vec<Position>, TimecurveError> {
+        (**self).project(distance_matrix)
+    }
+
+    fn project_with_context(
+        &self,
+        distance_matrix: &Vec<Vec<f64>>,
+        context: &ProjectionContext,
+    ) -> Result<Vec<Position>, TimecurveError> {
+        (**self).project_with_context(distance_matrix, context)
+    }
+}
+
+/// Allows projecting several inputs with the same algorithm, eg. one per dataset.
+impl<T: ProjectionAlgorithm + ?Sized> ProjectionAlgorithm for &T {
+    fn project(&self, distance_matrix: &Vec<Vec<f64>>) -> Result<Vec<Position>, TimecurveError> {
+        (**self).project(distance_matrix)
+    }
+
+    fn project_with_context(
+        &self,
+        distance_matrix: &Vec<Vec<f64>>,
+        context: &ProjectionContext,
+    ) -> Result<Vec<Position>, TimecurveError> {
+        (**self).project_with_context(distance_matrix, context)
+    }
+}
+
+/// Information about the points to project besides their distances,
+/// for projection algorithms that take time into account.
+/// There is one entry per row of the distance matrix.
+pub struct ProjectionContext {
+    /// The unix timestamp of each point.
+    timestamps: Vec<i64>,
+    /// The index of the dataset each point belongs to.
+    datasets: Vec<usize>,
+    /// The points pinned to fixed coordinates, as (row of the distance matrix, position) couples.
+    anchors: Vec<(usize, Position)>,
+    /// The feature vectors of the points, for projection algorithms working on them instead of the distances.
+    features: Option<Vec<Vec<f64>>>,
+}
+
+impl ProjectionContext {
+    /// Creates a new `ProjectionContext` from the timestamps and dataset indices of the points.
+    ///
+    /// ### Arguments
+    ///
+    /// * `timestamps` - The unix timestamp of each point.
+    /// * `datasets` - The index of the dataset each point belongs to. Must have the same length as `timestamps`.
+    pub fn new(timestamps: Vec<i64>, datasets: Vec<usize>) -> Self {
+        Self {
+            timestamps,
+            datasets,
+            anchors: Vec::new(),
+            features: None,
+        }
+    }
+
+    /// Sets the points pinned to fixed coordinates, for anchored projection algorithms.
+    ///
+    /// ### Arguments
+    ///
+    /// * `anchors` - The (row of the distance matrix, position) couples of the pinned points.
+    pub fn with_anchors(mut self, anchors: Vec<(usize, Position)>) -> Self {
+        self.anchors = anchors;
+        self
+    }
+
+    /// Sets the feature vectors of the points, for projection algorithms such as `PCA`.
+    ///
+    /// ### Arguments
+    ///
+    /// * `features` - One numeric vector per row of the distance matrix, or `None`.
+    pub fn with_features(mut self, features: Option<Vec<Vec<f64>>>) -> Self {
+        self.features = features;
+        self
+    }
+
+    /// Creates a new `ProjectionContext` from the datasets of an `InputData`.
+    /// The rows of the distance matrix are the timelabels of all datasets, one dataset after the other.
+    ///
+    /// ### Arguments
+    ///
+    /// * `input_data` - The input data.
+    ///
+    /// ### Returns
+    ///
+    /// The context, or an `InvalidTimeLabel` error if a timelabel can't be parsed,
+    /// or an `InvalidParameter` error if an anchor doesn't match exactly one point.
+    pub fn from_input(input_data: &InputData) -> Result<Self, TimecurveError> {
+        let mut timestamps = Vec::new();
+        let mut datasets = Vec::new();
+        // (dataset name, timelabel) of each row
+        let mut rows = Vec::new();
+
+        for (i, dataset) in input_data.get_datasets().iter().enumerate() {
+            for label in dataset.get_timelabels() {
+                timestamps.push(label_to_time(label)?);
+                datasets.push(i);
+                rows.push((dataset.get_name(), label.as_str()));
+            }
+        }
+
+        let mut anchors = Vec::new();
+        for anchor in input_data.get_anchors() {
+            let matching: Vec<usize> = match (anchor.get_index(), anchor.get_label()) {
+                (Some(index), _) => (index < rows.len()).then_some(index).into_iter().collect(),
+                (None, Some(label)) => (0..rows.len())
+                    .filter(|&i| {
+                        rows[i].1 == label && anchor.get_dataset().map_or(true, |d| rows[i].0 == d)
+                    })
+                    .collect(),
+                (None, None) => Vec::new(),
+            };
+
+            if matching.len() != 1 {
+                return Err(TimecurveError::new(
+                    TimecurveErrorKind::InvalidParameter,
+                    Some(&format!(
+                        "Anchor (index {:?}, dataset {:?}, label {:?}) matches {} points instead of 1",
+                        anchor.get_index(),
+                        anchor.get_dataset(),
+                        anchor.get_label(),
+                        matching.len()
+                    )),
+                ));
+            }
+            anchors.push((matching[0], Position::new(anchor.get_x(), anchor.get_y())));
+        }
+
+        Ok(Self::new(timestamps, datasets)
+            .with_anchors(anchors)
+            .with_features(input_data.get_features().cloned()))
+    }
+
+    /// Returns the unix timestamp of each point.
+    pub fn get_timestamps(&self) -> &[i64] {
+        &self.timestamps
+    }
+
+    /// Returns the index of the dataset each point belongs to.
+    pub fn get_datasets(&self) -> &[usize] {
+        &self.datasets
+    }
+
+    /// Returns the points pinned to fixed coordinates, as (row of the distance matrix, position) couples.
+    pub fn get_anchors(&self) -> &[(usize, Position)] {
+        &self.anchors
+    }
+
+    /// Returns the feature vectors of the points, if the input has some.
+    pub fn get_features(&self) -> Option<&Vec<Vec<f64>>> {
+        self.features.as_ref()
+    }
+
+    /// Returns the pairs of points that follow each other in time within the same dataset,
+    /// ie. the pairs of points that are joined by a segment on the timecurves.
+    pub fn temporal_neighbours(&self) -> Vec<(usize, usize)> {
+        let mut order: Vec<usize> = (0..self.timestamps.len()).collect();
+        order.sort_by_key(|&i| (self.datasets[i], self.timestamps[i]));
+
+        order
+            .windows(2)
+            .filter(|pair| self.datasets[pair[0]] == self.datasets[pair[1]])
+            .map(|pair| (pair[0], pair[1]))
+            .collect()
+    }
+}
+
+/// Checks that the distance matrix is non-empty and square.
+///
+/// ### Arguments
+///
+/// * `distance_matrix` - The distance matrix to check.
+///
+/// ### Returns
+///
+/// The number of points described by the matrix, or a `MalformedDistanceMatrix` error.
+pub(crate) fn check_square_matrix(distance_matrix: &[Vec<f64>]) -> Result<usize, TimecurveError> {
+    let n = distance_matrix.len();
+    if n == 0 {
+        return Err(TimecurveError::new(
+            TimecurveErrorKind::MalformedDistanceMatrix,
+            Some("Matrix is empty"),
+        ));
+    }
+
+    for (i, row) in distance_matrix.iter().enumerate() {
+        if row.len() != n {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::MalformedDistanceMatrix,
+                Some(&format!(
+                    "Has {} rows != {} columns (row {})",
+                    n,
+                    row.len(),
+                    i
+                )),
+            ));
+        }
+    }
+
+    Ok(n)
+}
+
+/// Checks that the distance matrix is non-empty, square and has no missing distance.
+/// Used by the projection algorithms that can't handle missing distances.
+///
+/// ### Arguments
+///
+/// * `distance_matrix` - The distance matrix to check.
+///
+/// ### Returns
+///
+/// The number of points described by the matrix, or a `MalformedDistanceMatrix` error naming the missing cells.
+pub(crate) fn check_complete_matrix(distance_matrix: &[Vec<f64>]) -> Result<usize, TimecurveError> {
+    const SHOWN_CELLS: usize = 10;
+
+    let n = check_square_matrix(distance_matrix)?;
+
+    let missing = missing_distances(distance_matrix);
+    if !missing.is_empty() {
+        let cells: Vec<String> = missing
+            .iter()
+            .take(SHOWN_CELLS)
+            .map(|(i, j)| format!("({}, {})", i, j))
+            .collect();
+        return Err(TimecurveError::new(
+            TimecurveErrorKind::MalformedDistanceMatrix,
+            Some(&format!(
+                "{} distances are missing : {}{}. Use a weighted algorithm like SMACOF, or complete the matrix with ShortestPathCompletion",
+                missing.len(),
+                cells.join(", "),
+                if missing.len() > SHOWN_CELLS { ", ..." } else { "" }
+            )),
+        ));
+    }
+
+    Ok(n)
+}
+
+/// Finds the missing distances of a distance matrix, ie. the NaN entries.
+///
+/// ### Arguments
+///
+/// * `distance_matrix` - The distance matrix.
+///
+/// ### Returns
+///
+/// The list of (row, column) of the missing distances, in reading order.
+pub(crate) fn missing_distances(distance_matrix: &[Vec<f64>]) -> Vec<(usize, usize)> {
+    distance_matrix
+        .iter()
+        .enumerate()
+        .flat_map(|(i, row)| {
+            row.iter()
+                .enumerate()
+                .filter(|(_, d)| d.is_nan())
+                .map(move |(j, _)| (i, j))
+        })
+        .collect()
+}
@@ -0,0 +1,348 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+use super::projection_algorithm::check_square_matrix;
+use crate::{
+    error::{TimecurveError, TimecurveErrorKind},
+    timecurve::Position,
+};
+use std::cmp::Ordering;
+
+/// Measures of how faithfully a projection represents the distance matrix.
+///
+/// The measures don't depend on the scale of the projected points : the projected distances
+/// are multiplied by the factor that best fits them to the original ones before comparing them.
+/// So the report gives the same result before and after the normalisation of a `TimecurveSet`.
+/// The missing distances of the matrix (NaN) are ignored, and count as infinite for the ranks of the neighbours.
+#[derive(Clone)]
+pub struct QualityReport {
+    /// The number of neighbours used for trustworthiness and continuity.
+    k: usize,
+    /// The factor applied to the projected distances.
+    scale: f64,
+    /// The normalised stress of the scaled layout.
+    stress: f64,
+    /// The trustworthiness at k neighbours.
+    trustworthiness: f64,
+    /// The continuity at k neighbours.
+    continuity: f64,
+    /// The error of each point, in the order of the rows of the distance matrix.
+    local_errors: Vec<f64>,
+    /// The (original, projected) distance of each pair of points.
+    shepard: Vec<(f64, f64)>,
+}
+
+impl QualityReport {
+    /// Computes the quality report of a projection.
+    ///
+    /// ### Arguments
+    ///
+    /// * `distance_matrix` - The distance matrix given to the projection algorithm.
+    /// * `points` - The projected points, in the order of the rows of the distance matrix.
+    /// * `k` - The number of neighbours used for trustworthiness and continuity.
+    ///   It must be at least 1 and smaller than (2n - 1) / 3, where n is the number of points.
+    ///
+    /// ### Returns
+    ///
+    /// The quality report, or an error if the matrix is malformed, the number of points differs or `k` is invalid.
+    pub fn new(
+        distance_matrix: &[Vec<f64>],
+        points: &[Position],
+        k: usize,
+    ) -> Result<Self, TimecurveError> {
+        let n = check_square_matrix(distance_matrix)?;
+
+        if points.len() != n {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::InvalidParameter,
+                Some(&format!(
+                    "Got {} projected points for a matrix of {} rows",
+                    points.len(),
+                    n
+                )),
+            ));
+        }
+        if k == 0 || k > max_neighbours(n) {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::InvalidParameter,
+                Some(&format!(
+                    "The number of neighbours must be between 1 and {} for {} points, got {}",
+                    max_neighbours(n),
+                    n,
+                    k
+                )),
+            ));
+        }
+
+        let projected: Vec<Vec<f64>> = points
+            .iter()
+            .map(|a| points.iter().map(|b| a.distance(b)).collect())
+            .collect();
+
+        let scale = fit_scale(distance_matrix, points);
+
+        let mut raw_stress = 0.0;
+        let mut norm = 0.0;
+        let mut shepard = Vec::with_capacity(n * (n - 1) / 2);
+        for i in 0..n {
+            for j in (i + 1)..n {
+                let delta = distance_matrix[i][j];
+                if delta.is_nan() {
+                    continue;
+                }
+                let d = scale * projected[i][j];
+                raw_stress += (delta - d).powi(2);
+                norm += delta.powi(2);
+                shepard.push((delta, d));
+            }
+        }
+        let stress = if norm > 0.0 { raw_stress / norm } else { 0.0 };
+
+        let local_errors = (0..n)
+            .map(|i| {
+                let mut error = 0.0;
+                let mut norm = 0.0;
+                for j in (0..n).filter(|&j| j != i && !distance_matrix[i][j].is_nan()) {
+                    error += (distance_matrix[i][j] - scale * projected[i][j]).powi(2);
+                    norm += distance_matrix[i][j].powi(2);
+                }
+                if norm > 0.0 {
+                    error / norm
+                } else {
+                    0.0
+                }
+            })
+            .collect();
+
+        let original_ranks = neighbour_ranks(distance_matrix);
+        let projected_ranks = neighbour_ranks(&projected);
+
+        Ok(Self {
+            k,
+            scale,
+            stress,
+            trustworthiness: rank_preservation(&projected_ranks, &original_ranks, k),
+            continuity: rank_preservation(&original_ranks, &projected_ranks, k),
+            local_errors,
+            shepard,
+        })
+    }
+
+    /// Returns the number of neighbours used for trustworthiness and continuity.
+    pub fn get_k(&self) -> usize {
+        self.k
+    }
+
+    /// Returns the factor applied to the projected distances before comparing them to the original ones.
+    pub fn get_scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// Returns the normalised stress, ie. Σ(δij - s * dij)² / Σδij², where s is the scale.
+    /// 0.0 means the distances are perfectly preserved.
+    pub fn get_stress(&self) -> f64 {
+        self.stress
+    }
+
+    /// Returns the trustworthiness, between 0.0 and 1.0.
+    /// It is lowered by points that are among the k nearest neighbours in the projection but not in the original data,
+    /// ie. false neighbours created by the projection.
+    pub fn get_trustworthiness(&self) -> f64 {
+        self.trustworthiness
+    }
+
+    /// Returns the continuity, between 0.0 and 1.0.
+    /// It is lowered by points that are among the k nearest neighbours in the original data but not in the projection,
+    /// ie. neighbours torn apart by the projection.
+    pub fn get_continuity(&self) -> f64 {
+        self.continuity
+    }
+
+    /// Returns the error of each point, in the order of the rows of the distance matrix.
+    /// The error of a point is the normalised stress of its row : Σj(δij - s * dij)² / Σjδij².
+    pub fn get_local_errors(&self) -> &[f64] {
+        &self.local_errors
+    }
+
+    /// Returns the data of the Shepard diagram : the (original, projected) distance of each pair of points i < j
+    /// whose distance is known.
+    /// The projected distances are multiplied by the scale, so a perfect projection gives points on the diagonal.
+    pub fn get_shepard(&self) -> &[(f64, f64)] {
+        &self.shepard
+    }
+}
+
+/// Computes the factor that best fits the projected distances to the original ones, see `QualityReport::get_scale`.
+/// Unlike the whole report, it doesn't need to store the projected distances.
+///
+/// ### Arguments
+///
+/// * `distance_matrix` - A square distance matrix. The missing distances (NaN) are ignored.
+/// * `points` - The projected points, in the order of the rows of the distance matrix.
+///
+/// ### Returns
+///
+/// The scale s minimising Σ(δij - s * dij)², or 1.0 if all the points are at the same position.
+pub(crate) fn fit_scale(distance_matrix: &[Vec<f64>], points: &[Position]) -> f64 {
+    // the scale minimising Σ(δij - s * dij)² is Σδij dij / Σdij²
+    let mut cross = 0.0;
+    let mut squared = 0.0;
+    for i in 0..points.len() {
+        for j in (i + 1)..points.len() {
+            if distance_matrix[i][j].is_nan() {
+                continue;
+            }
+            let d = points[i].distance(&points[j]);
+            cross += distance_matrix[i][j] * d;
+            squared += d.powi(2);
+        }
+    }
+
+    if squared > 0.0 {
+        cross / squared
+    } else {
+        1.0
+    }
+}
+
+/// Returns the largest number of neighbours for which trustworthiness and continuity are defined.
+///
+/// ### Arguments
+///
+/// * `n` - The number of points.
+pub(crate) fn max_neighbours(n: usize) -> usize {
+    // the normalisation factor 2 / (nk(2n - 3k - 1)) needs 2n - 3k - 1 > 0
+    (2 * n).saturating_sub(2) / 3
+}
+
+/// Computes the rank of each point in the neighbourhood of each other point.
+///
+/// ### Arguments
+///
+/// * `distances` - A square distance matrix.
+///
+/// ### Returns
+///
+/// A matrix where the element (i, j) is the rank of j among the neighbours of i, starting at 1 for the nearest one.
+/// Ties are broken by index, and missing distances come last.
+fn neighbour_ranks(distances: &[Vec<f64>]) -> Vec<Vec<usize>> {
+    let n = distances.len();
+    let mut ranks = vec![vec![0; n]; n];
+
+    for (i, row) in distances.iter().enumerate() {
+        let mut order: Vec<usize> = (0..n).filter(|&j| j != i).collect();
+        let key = |j: usize| {
+            if row[j].is_nan() {
+                f64::INFINITY
+            } else {
+                row[j]
+            }
+        };
+        order.sort_by(|&a, &b| key(a).partial_cmp(&key(b)).unwrap_or(Ordering::Equal));
+        for (rank, &j) in order.iter().enumerate() {
+            ranks[i][j] = rank + 1;
+        }
+    }
+
+    ranks
+}
+
+/// Computes the trustworthiness or the continuity, which only differ by the roles of the two spaces.
+///
+/// ### Arguments
+///
+/// * `neighbourhood_ranks` - The ranks defining the neighbourhoods that are checked.
+/// * `penalty_ranks` - The ranks in the other space, used to penalise the intruders.
+/// * `k` - The size of the neighbourhoods.
+///
+/// ### Returns
+///
+/// 1 - 2 / (nk(2n - 3k - 1)) * Σi Σj (r(i, j) - k), for the points j among the k nearest neighbours of i
+/// in the first space but not in the second one, where r are the ranks in the second space.
+fn rank_preservation(
+    neighbourhood_ranks: &[Vec<usize>],
+    penalty_ranks: &[Vec<usize>],
+    k: usize,
+) -> f64 {
+    let n = neighbourhood_ranks.len();
+    let mut penalty = 0;
+
+    for i in 0..n {
+        for j in (0..n).filter(|&j| j != i) {
+            if neighbourhood_ranks[i][j] <= k && penalty_ranks[i][j] > k {
+                penalty += penalty_ranks[i][j] - k;
+            }
+        }
+    }
+
+    let (n, k) = (n as f64, k as f64);
+    1.0 - 2.0 / (n * k * (2.0 * n - 3.0 * k - 1.0)) * penalty as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grid() -> (Vec<Position>, Vec<Vec<f64>>) {
+        let points: Vec<Position> = (0..25)
+            .map(|i| Position::new((i % 5) as f64, (i / 5) as f64))
+            .collect();
+        let distance_matrix = points
+            .iter()
+            .map(|a| points.iter().map(|b| a.distance(b)).collect())
+            .collect();
+        (points, distance_matrix)
+    }
+
+    #[test]
+    fn perfect_projection_has_perfect_quality() {
+        let (points, distance_matrix) = grid();
+        // the scale and the position of the layout don't matter (exact values, so that ties between distances are kept)
+        let scaled: Vec<Position> = points
+            .iter()
+            .map(|p| Position::new(p.get_x() * 0.5 + 4.0, p.get_y() * 0.5 - 2.0))
+            .collect();
+
+        let report = QualityReport::new(&distance_matrix, &scaled, 5).unwrap();
+
+        assert!((report.get_scale() - 2.0).abs() < 1e-9);
+        assert!(report.get_stress() < 1e-12);
+        assert!((report.get_trustworthiness() - 1.0).abs() < 1e-12);
+        assert!((report.get_continuity() - 1.0).abs() < 1e-12);
+        assert!(report.get_local_errors().iter().all(|e| *e < 1e-12));
+        assert_eq!(report.get_shepard().len(), 25 * 24 / 2);
+        assert!(report
+            .get_shepard()
+            .iter()
+            .all(|(delta, d)| (delta - d).abs() < 1e-9));
+    }
+
+    #[test]
+    fn misplaced_point_is_detected() {
+        let (mut points, distance_matrix) = grid();
+        // swap two opposite corners
+        points.swap(0, 24);
+
+        let report = QualityReport::new(&distance_matrix, &points, 5).unwrap();
+
+        assert!(report.get_trustworthiness() < 1.0);
+        assert!(report.get_continuity() < 1.0);
+        let errors = report.get_local_errors();
+        let worst = (0..25)
+            .max_by(|&a, &b| errors[a].total_cmp(&errors[b]))
+            .unwrap();
+        assert!(worst == 0 || worst == 24);
+    }
+
+    #[test]
+    fn invalid_neighbour_count_is_rejected() {
+        let (points, distance_matrix) = grid();
+
+        assert!(QualityReport::new(&distance_matrix, &points, 0).is_err());
+        assert!(QualityReport::new(&distance_matrix, &points, max_neighbours(25) + 1).is_err());
+        assert!(QualityReport::new(&distance_matrix, &points[1..], 5).is_err());
+    }
+}
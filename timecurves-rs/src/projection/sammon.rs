@@ -0,0 +1,279 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+use super::{
+    classical_mds::ClassicalMDS,
+    projection_algorithm::{check_complete_matrix, ProjectionAlgorithm},
+    smacof::circle_layout,
+};
+use crate::{
+    error::{TimecurveError, TimecurveErrorKind},
+    timecurve::Position,
+};
+use log::info;
+
+/// Structure representing Sammon's nonlinear mapping.
+///
+/// The error on each distance is divided by the distance itself, so small distances are preserved
+/// more accurately than large ones. This suits series where consecutive points differ only slightly,
+/// like Wikipedia revisions changing a few characters. Pairs of points at distance 0 are ignored.
+pub struct Sammon {
+    /// The maximum number of iterations.
+    max_iterations: usize,
+    /// The algorithm stops when the stress improves by less than this fraction between two iterations.
+    tolerance: f64,
+    /// The initial step size of the pseudo-Newton update.
+    step_size: f64,
+}
+
+impl Sammon {
+    /// Creates a new instance of Sammon's mapping.
+    ///
+    /// ### Arguments
+    ///
+    /// * `max_iterations` - The maximum number of iterations. 500 is a reasonable value.
+    /// * `tolerance` - The algorithm stops when the stress improves by less than this fraction of itself. 1e-9 is a reasonable value.
+    /// * `step_size` - The step size of each pseudo-Newton update, called "magic factor" by Sammon.
+    ///   When a step would increase the stress, it is halved until the stress decreases. 1.0 is a reasonable value.
+    pub fn new(max_iterations: usize, tolerance: f64, step_size: f64) -> Self {
+        Self {
+            max_iterations,
+            tolerance,
+            step_size,
+        }
+    }
+
+    /// Projects the points like `project`, but also returns the final Sammon stress of the layout.
+    ///
+    /// ### Arguments
+    ///
+    /// * `distance_matrix` - A reference to a vector of rows representing the distance matrix.
+    ///
+    /// ### Returns
+    ///
+    /// A tuple containing the projected points and the Sammon stress,
+    /// ie. (1 / Σδij) * Σ(δij - dij)² / δij, where δ are the given distances and d the projected ones.
+    pub fn project_with_stress(
+        &self,
+        distance_matrix: &[Vec<f64>],
+    ) -> Result<(Vec<Position>, f64), TimecurveError> {
+        const MAX_HALVINGS: usize = 20;
+
+        check_complete_matrix(distance_matrix)?;
+
+        if self.step_size <= 0.0 {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::InvalidParameter,
+                Some(&format!(
+                    "Sammon step size must be positive, got {}",
+                    self.step_size
+                )),
+            ));
+        }
+
+        let mut layout = ClassicalMDS::new().project_rows(distance_matrix)?;
+        if layout
+            .iter()
+            .any(|p| !p.get_x().is_finite() || !p.get_y().is_finite())
+        {
+            layout = circle_layout(distance_matrix);
+        }
+
+        let mut stress = sammon_stress(distance_matrix, &layout);
+        let mut iterations = 0;
+
+        while iterations < self.max_iterations {
+            iterations += 1;
+            let steps = newton_steps(distance_matrix, &layout);
+
+            // step-size control : halve the step until the stress decreases
+            let mut step_size = self.step_size;
+            let mut improved = None;
+            for _ in 0..MAX_HALVINGS {
+                let candidate: Vec<Position> = layout
+                    .iter()
+                    .zip(&steps)
+                    .map(|(p, s)| {
+                        Position::new(p.get_x() - step_size * s[0], p.get_y() - step_size * s[1])
+                    })
+                    .collect();
+                let candidate_stress = sammon_stress(distance_matrix, &candidate);
+                if candidate_stress < stress {
+                    improved = Some((candidate, candidate_stress));
+                    break;
+                }
+                step_size /= 2.0;
+            }
+
+            let Some((candidate, candidate_stress)) = improved else {
+                break;
+            };
+
+            let improvement = (stress - candidate_stress) / stress;
+            layout = candidate;
+            stress = candidate_stress;
+
+            if improvement < self.tolerance {
+                break;
+            }
+        }
+
+        info!(
+            "Sammon mapping stopped after {} iterations, stress = {:.6}",
+            iterations, stress
+        );
+
+        Ok((layout, stress))
+    }
+}
+
+impl ProjectionAlgorithm for Sammon {
+    fn project(&self, distance_matrix: &Vec<Vec<f64>>) -> Result<Vec<Position>, TimecurveError> {
+        Ok(self.project_with_stress(distance_matrix)?.0)
+    }
+}
+
+/// Computes Sammon's pseudo-Newton step for each point : the gradient of the stress
+/// divided by the absolute value of its second derivative, for each coordinate.
+///
+/// ### Arguments
+///
+/// * `distance_matrix` - The distances to preserve.
+/// * `layout` - The current positions of the points.
+///
+/// ### Returns
+///
+/// The step of each point, to be substracted from its position.
+fn newton_steps(distance_matrix: &[Vec<f64>], layout: &[Position]) -> Vec<[f64; 2]> {
+    let n = layout.len();
+    let mut steps = Vec::with_capacity(n);
+
+    for p in 0..n {
+        let mut gradient = [0.0; 2];
+        let mut hessian = [0.0; 2];
+        for j in 0..n {
+            let delta = distance_matrix[p][j];
+            if p == j || delta <= 0.0 {
+                continue;
+            }
+            let d = layout[p].distance(&layout[j]).max(f64::EPSILON);
+            let diff = [
+                layout[p].get_x() - layout[j].get_x(),
+                layout[p].get_y() - layout[j].get_y(),
+            ];
+            let error = delta - d;
+
+            for q in 0..2 {
+                gradient[q] += error / (delta * d) * diff[q];
+                hessian[q] += (error - diff[q] * diff[q] / d * (1.0 + error / d)) / (delta * d);
+            }
+        }
+
+        // the common factor -2 / Σδ of the derivatives cancels out in the ratio, except for the sign
+        steps.push([
+            if hessian[0] != 0.0 {
+                -gradient[0] / hessian[0].abs()
+            } else {
+                0.0
+            },
+            if hessian[1] != 0.0 {
+                -gradient[1] / hessian[1].abs()
+            } else {
+                0.0
+            },
+        ]);
+    }
+
+    steps
+}
+
+/// Computes the Sammon stress of a layout, ie. (1 / Σδij) * Σ(δij - dij)² / δij.
+///
+/// ### Arguments
+///
+/// * `distance_matrix` - The distances to preserve.
+/// * `layout` - The positions of the points.
+///
+/// ### Returns
+///
+/// The Sammon stress. 0.0 means the distances are perfectly preserved.
+fn sammon_stress(distance_matrix: &[Vec<f64>], layout: &[Position]) -> f64 {
+    let mut stress = 0.0;
+    let mut norm = 0.0;
+
+    for i in 0..layout.len() {
+        for j in (i + 1)..layout.len() {
+            let delta = distance_matrix[i][j];
+            if delta <= 0.0 {
+                continue;
+            }
+            let d = layout[i].distance(&layout[j]);
+            stress += (delta - d).powi(2) / delta;
+            norm += delta;
+        }
+    }
+
+    if norm == 0.0 {
+        return 0.0;
+    }
+
+    stress / norm
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::InputData;
+
+    #[test]
+    fn sammon_preserves_euclidean_distances() {
+        // the four corners of a 3x4 rectangle and its center
+        let points = [
+            Position::new(0.0, 0.0),
+            Position::new(3.0, 0.0),
+            Position::new(3.0, 4.0),
+            Position::new(0.0, 4.0),
+            Position::new(1.5, 2.0),
+        ];
+        let distance_matrix: Vec<Vec<f64>> = points
+            .iter()
+            .map(|a| points.iter().map(|b| a.distance(b)).collect())
+            .collect();
+
+        let (projected, stress) = Sammon::new(500, 1e-12, 1.0)
+            .project_with_stress(&distance_matrix)
+            .unwrap();
+
+        assert!(stress < 1e-6);
+        for i in 0..5 {
+            for j in 0..5 {
+                assert!(
+                    (projected[i].distance(&projected[j]) - distance_matrix[i][j]).abs() < 1e-2
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sammon_reduces_the_stress_of_classical_mds() {
+        let input = InputData::from_filename(&format!(
+            "{}/tests/psfr100points.json",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let distance_matrix = input.get_distance_matrix();
+
+        let initial = ClassicalMDS::new().project(distance_matrix).unwrap();
+        let initial_stress = sammon_stress(distance_matrix, &initial);
+
+        let (points, stress) = Sammon::new(50, 1e-9, 1.0)
+            .project_with_stress(distance_matrix)
+            .unwrap();
+
+        assert_eq!(points.len(), 100);
+        assert!(stress < initial_stress);
+    }
+}
@@ -0,0 +1,432 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+use super::{
+    classical_mds::ClassicalMDS,
+    completion::complete_shortest_paths,
+    projection_algorithm::{check_square_matrix, missing_distances, ProjectionAlgorithm},
+};
+use crate::{
+    error::{TimecurveError, TimecurveErrorKind},
+    timecurve::Position,
+};
+use log::{info, warn};
+use nalgebra::DMatrix;
+
+/// Structure representing the SMACOF (Scaling by MAjorizing a COmplicated Function) algorithm.
+///
+/// It is a metric MDS that iteratively minimises the stress of the layout,
+/// ie. the squared difference between the given distances and the projected ones.
+/// Unlike the classical MDS, it gives good results on non-euclidean distance matrices.
+///
+/// The layout keeps the number of dimensions of the initial layout, so starting from a 3D classical MDS gives a 3D projection.
+///
+/// Missing distances (NaN, or `null` in the input file) are given a weight of 0, so they are simply ignored.
+/// The known distances must still connect all the points, otherwise their relative positions are undetermined.
+pub struct SMACOF {
+    /// The maximum number of iterations.
+    max_iterations: usize,
+    /// The algorithm stops when the stress improves by less than this value between two iterations.
+    tolerance: f64,
+    /// The layout to start from. If `None`, the result of the classical MDS is used.
+    initial_layout: Option<Vec<Position>>,
+}
+
+impl SMACOF {
+    /// Creates a new instance of the SMACOF algorithm.
+    ///
+    /// ### Arguments
+    ///
+    /// * `max_iterations` - The maximum number of iterations. 300 is a reasonable value.
+    /// * `tolerance` - The algorithm stops when the normalised stress improves by less than this value. 1e-6 is a reasonable value.
+    /// * `initial_layout` - The layout to start from, with one position per row of the distance matrix.
+    ///   If `None`, the result of the classical MDS is used.
+    pub fn new(
+        max_iterations: usize,
+        tolerance: f64,
+        initial_layout: Option<Vec<Position>>,
+    ) -> Self {
+        Self {
+            max_iterations,
+            tolerance,
+            initial_layout,
+        }
+    }
+
+    /// Projects the points like `project`, but also returns the final stress of the layout.
+    ///
+    /// ### Arguments
+    ///
+    /// * `distance_matrix` - A reference to a vector of rows representing the distance matrix.
+    ///
+    /// ### Returns
+    ///
+    /// A tuple containing the projected points and the normalised stress,
+    /// ie. Σ(δij - dij)² / Σδij², where δ are the given distances and d the projected ones.
+    pub fn project_with_stress(
+        &self,
+        distance_matrix: &[Vec<f64>],
+    ) -> Result<(Vec<Position>, f64), TimecurveError> {
+        let n = check_square_matrix(distance_matrix)?;
+
+        // with missing distances, the weighted version of the Guttman transform is used
+        let pseudo_inverse = if missing_distances(distance_matrix).is_empty() {
+            None
+        } else {
+            Some(weighted_laplacian_pseudo_inverse(distance_matrix)?)
+        };
+
+        let mut layout = match &self.initial_layout {
+            Some(layout) => {
+                if layout.len() != n {
+                    return Err(TimecurveError::new(
+                        TimecurveErrorKind::InvalidParameter,
+                        Some(&format!(
+                            "Initial layout has {} points but the matrix has {} rows",
+                            layout.len(),
+                            n
+                        )),
+                    ));
+                }
+                layout.clone()
+            }
+            None if pseudo_inverse.is_some() => {
+                ClassicalMDS::new().project_rows(&complete_shortest_paths(distance_matrix)?)?
+            }
+            None => ClassicalMDS::new().project_rows(distance_matrix)?,
+        };
+
+        // the classical MDS gives NaN coordinates when the matrix has less than two positive eigenvalues,
+        // and the Guttman transform can't recover from a degenerate layout, so we start from a circle instead
+        if layout
+            .iter()
+            .any(|p| !p.get_x().is_finite() || !p.get_y().is_finite())
+        {
+            warn!("Initial layout contains invalid coordinates, starting SMACOF from a circle instead.");
+            layout = circle_layout(distance_matrix);
+        }
+
+        let mut stress = normalised_stress(distance_matrix, &layout);
+        let mut iterations = 0;
+
+        while iterations < self.max_iterations {
+            layout = match &pseudo_inverse {
+                Some(pseudo_inverse) => {
+                    weighted_guttman_transform(distance_matrix, &layout, pseudo_inverse)
+                }
+                None => guttman_transform(distance_matrix, &layout),
+            };
+            iterations += 1;
+
+            let new_stress = normalised_stress(distance_matrix, &layout);
+            let improvement = stress - new_stress;
+            stress = new_stress;
+
+            if improvement < self.tolerance {
+                break;
+            }
+        }
+
+        info!(
+            "SMACOF stopped after {} iterations, normalised stress = {:.6}",
+            iterations, stress
+        );
+
+        Ok((layout, stress))
+    }
+}
+
+impl ProjectionAlgorithm for SMACOF {
+    fn project(&self, distance_matrix: &Vec<Vec<f64>>) -> Result<Vec<Position>, TimecurveError> {
+        Ok(self.project_with_stress(distance_matrix)?.0)
+    }
+}
+
+/// Computes one step of the SMACOF algorithm, the Guttman transform X' = (1/n) B(X) X.
+///
+/// ### Arguments
+///
+/// * `distance_matrix` - The distances to preserve.
+/// * `layout` - The current positions of the points.
+///
+/// ### Returns
+///
+/// The new positions of the points. The stress of the new layout is never greater than the stress of the old one.
+pub(crate) fn guttman_transform(
+    distance_matrix: &[Vec<f64>],
+    layout: &[Position],
+) -> Vec<Position> {
+    let n = layout.len();
+    let mut new_layout = Vec::with_capacity(n);
+
+    for i in 0..n {
+        let mut x = 0.0;
+        let mut y = 0.0;
+        let mut z = 0.0;
+        for j in 0..n {
+            let d = layout[i].distance(&layout[j]);
+            if i == j || d == 0.0 {
+                continue;
+            }
+
+            // (B(X) X)i = Σj (δij / dij) * (xi - xj)
+            let ratio = distance_matrix[i][j] / d;
+            x += ratio * (layout[i].get_x() - layout[j].get_x());
+            y += ratio * (layout[i].get_y() - layout[j].get_y());
+            z += ratio * (layout[i].get_z() - layout[j].get_z());
+        }
+        new_layout.push(Position::new_3d(x / n as f64, y / n as f64, z / n as f64));
+    }
+
+    new_layout
+}
+
+/// Computes one step of the weighted SMACOF algorithm, the Guttman transform X' = V⁺ B(X) X,
+/// where the missing distances have a weight of 0 and the others a weight of 1.
+///
+/// ### Arguments
+///
+/// * `distance_matrix` - The distances to preserve, with NaN for the missing ones.
+/// * `layout` - The current positions of the points.
+/// * `pseudo_inverse` - The pseudo-inverse of the weighted laplacian V, see `weighted_laplacian_pseudo_inverse`.
+///
+/// ### Returns
+///
+/// The new positions of the points. The stress of the new layout is never greater than the stress of the old one.
+fn weighted_guttman_transform(
+    distance_matrix: &[Vec<f64>],
+    layout: &[Position],
+    pseudo_inverse: &DMatrix<f64>,
+) -> Vec<Position> {
+    let n = layout.len();
+    let mut bx = DMatrix::zeros(n, 3);
+
+    for i in 0..n {
+        for j in 0..n {
+            let d = layout[i].distance(&layout[j]);
+            let delta = distance_matrix[i][j];
+            if i == j || d == 0.0 || delta.is_nan() {
+                continue;
+            }
+
+            let ratio = delta / d;
+            bx[(i, 0)] += ratio * (layout[i].get_x() - layout[j].get_x());
+            bx[(i, 1)] += ratio * (layout[i].get_y() - layout[j].get_y());
+            bx[(i, 2)] += ratio * (layout[i].get_z() - layout[j].get_z());
+        }
+    }
+
+    let new_layout = pseudo_inverse * bx;
+    (0..n)
+        .map(|i| Position::new_3d(new_layout[(i, 0)], new_layout[(i, 1)], new_layout[(i, 2)]))
+        .collect()
+}
+
+/// Computes the Moore-Penrose pseudo-inverse of the weighted laplacian V of the known distances,
+/// where Vij = -1 if the distance between i and j is known, and Vii = -Σj Vij.
+///
+/// ### Arguments
+///
+/// * `distance_matrix` - The distances, with NaN for the missing ones.
+///
+/// ### Returns
+///
+/// The pseudo-inverse, or a `DisconnectedGraph` error if the known distances don't connect all the points.
+fn weighted_laplacian_pseudo_inverse(
+    distance_matrix: &[Vec<f64>],
+) -> Result<DMatrix<f64>, TimecurveError> {
+    let n = distance_matrix.len();
+    let known = |i: usize, j: usize| i != j && !distance_matrix[i][j].is_nan();
+
+    // the points must be connected, otherwise V has more than one null eigenvalue
+    let mut reached = vec![false; n];
+    let mut stack = vec![0];
+    reached[0] = true;
+    while let Some(i) = stack.pop() {
+        for (j, reached) in reached.iter_mut().enumerate() {
+            if !*reached && (known(i, j) || known(j, i)) {
+                *reached = true;
+                stack.push(j);
+            }
+        }
+    }
+    if let Some(unreached) = reached.iter().position(|r| !r) {
+        return Err(TimecurveError::new(
+            TimecurveErrorKind::DisconnectedGraph,
+            Some(&format!(
+                "Point {} can't be placed relatively to point 0 with the known distances",
+                unreached
+            )),
+        ));
+    }
+
+    // the weights are made symmetric, so that V is
+    let mut laplacian = DMatrix::zeros(n, n);
+    for i in 0..n {
+        for j in 0..n {
+            let weight = (known(i, j) as u8 + known(j, i) as u8) as f64 / 2.0;
+            laplacian[(i, j)] -= weight;
+            laplacian[(i, i)] += weight;
+        }
+    }
+
+    // V⁺ = (V + 11t / n)⁻¹ - 11t / n, as the null space of V is spanned by 1
+    let ones = DMatrix::from_element(n, n, 1.0 / n as f64);
+    let inverse = (laplacian + &ones).try_inverse().ok_or_else(|| {
+        TimecurveError::new(
+            TimecurveErrorKind::DisconnectedGraph,
+            Some("The laplacian of the known distances can't be inverted"),
+        )
+    })?;
+
+    Ok(inverse - ones)
+}
+
+/// Computes the normalised stress of a layout, ie. Σ(δij - dij)² / Σδij².
+///
+/// ### Arguments
+///
+/// * `distance_matrix` - The distances to preserve.
+/// * `layout` - The positions of the points.
+///
+/// ### Returns
+///
+/// The normalised stress. 0.0 means the distances are perfectly preserved. The missing distances are ignored.
+pub(crate) fn normalised_stress(distance_matrix: &[Vec<f64>], layout: &[Position]) -> f64 {
+    let mut raw_stress = 0.0;
+    let mut norm = 0.0;
+
+    for i in 0..layout.len() {
+        for j in (i + 1)..layout.len() {
+            let delta = distance_matrix[i][j];
+            if delta.is_nan() {
+                continue;
+            }
+            let d = layout[i].distance(&layout[j]);
+            raw_stress += (delta - d).powi(2);
+            norm += delta.powi(2);
+        }
+    }
+
+    if norm == 0.0 {
+        return 0.0;
+    }
+
+    raw_stress / norm
+}
+
+/// Places the points evenly on a circle whose diameter is the mean distance of the matrix.
+/// Used as a fallback starting layout.
+pub(crate) fn circle_layout(distance_matrix: &[Vec<f64>]) -> Vec<Position> {
+    let n = distance_matrix.len();
+    let known: Vec<f64> = distance_matrix
+        .iter()
+        .flatten()
+        .copied()
+        .filter(|d| !d.is_nan())
+        .collect();
+    let mean = known.iter().sum::<f64>() / known.len().max(1) as f64;
+    let radius = mean.max(f64::EPSILON) / 2.0;
+
+    (0..n)
+        .map(|i| {
+            let angle = 2.0 * std::f64::consts::PI * i as f64 / n as f64;
+            Position::new(radius * angle.cos(), radius * angle.sin())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::InputData;
+
+    #[test]
+    fn smacof_preserves_euclidean_distances() {
+        // the four corners of a 3x4 rectangle
+        let distance_matrix: Vec<Vec<f64>> = vec![
+            vec![0.0, 3.0, 5.0, 4.0],
+            vec![3.0, 0.0, 4.0, 5.0],
+            vec![5.0, 4.0, 0.0, 3.0],
+            vec![4.0, 5.0, 3.0, 0.0],
+        ];
+
+        let smacof = SMACOF::new(300, 1e-9, None);
+        let (points, stress) = smacof.project_with_stress(&distance_matrix).unwrap();
+
+        assert!(stress < 1e-6);
+        for i in 0..4 {
+            for j in 0..4 {
+                assert!((points[i].distance(&points[j]) - distance_matrix[i][j]).abs() < 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn smacof_does_not_increase_the_stress_of_the_initial_layout() {
+        let input = InputData::from_filename(&format!(
+            "{}/tests/psfr100points.json",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let distance_matrix = input.get_distance_matrix();
+
+        let initial = ClassicalMDS::new().project(distance_matrix).unwrap();
+        let initial_stress = normalised_stress(distance_matrix, &initial);
+
+        let smacof = SMACOF::new(100, 1e-6, Some(initial));
+        let (points, stress) = smacof.project_with_stress(distance_matrix).unwrap();
+
+        assert_eq!(points.len(), 100);
+        assert!(stress <= initial_stress);
+    }
+
+    #[test]
+    fn smacof_rejects_initial_layout_of_wrong_size() {
+        let distance_matrix: Vec<Vec<f64>> = vec![vec![0.0, 1.0], vec![1.0, 0.0]];
+
+        let smacof = SMACOF::new(10, 1e-6, Some(vec![Position::new(0.0, 0.0)]));
+
+        assert!(smacof.project(&distance_matrix).is_err());
+    }
+
+    #[test]
+    fn smacof_ignores_missing_distances() {
+        // the four corners of a 3x4 rectangle and its center, without the distances between opposite corners
+        let points = [
+            Position::new(0.0, 0.0),
+            Position::new(3.0, 0.0),
+            Position::new(3.0, 4.0),
+            Position::new(0.0, 4.0),
+            Position::new(1.5, 2.0),
+        ];
+        let mut distance_matrix: Vec<Vec<f64>> = points
+            .iter()
+            .map(|a| points.iter().map(|b| a.distance(b)).collect())
+            .collect();
+        for (i, j) in [(0, 2), (2, 0), (1, 3), (3, 1)] {
+            distance_matrix[i][j] = f64::NAN;
+        }
+
+        let (projected, stress) = SMACOF::new(500, 1e-12, None)
+            .project_with_stress(&distance_matrix)
+            .unwrap();
+
+        assert!(stress < 1e-6);
+        // the missing distances are recovered
+        assert!((projected[0].distance(&projected[2]) - 5.0).abs() < 1e-2);
+        assert!((projected[1].distance(&projected[3]) - 5.0).abs() < 1e-2);
+
+        // but a point without any known distance can't be placed
+        for row in distance_matrix.iter_mut() {
+            row[4] = f64::NAN;
+        }
+        distance_matrix[4] = vec![f64::NAN; 5];
+        assert!(SMACOF::new(10, 1e-6, None)
+            .project(&distance_matrix)
+            .is_err());
+    }
+}
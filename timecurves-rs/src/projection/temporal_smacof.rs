@@ -0,0 +1,332 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+use super::{
+    classical_mds::ClassicalMDS,
+    projection_algorithm::{check_complete_matrix, ProjectionAlgorithm, ProjectionContext},
+    smacof::{circle_layout, normalised_stress},
+};
+use crate::{
+    error::{TimecurveError, TimecurveErrorKind},
+    timecurve::Position,
+};
+use log::{info, warn};
+
+/// Structure representing a time-aware variant of the SMACOF algorithm.
+///
+/// It minimises the stress of the layout plus a penalty on the distance between points
+/// that follow each other in time, so that consecutive timepoints are not scattered arbitrarily
+/// and the curves are easier to follow. The time order is read from the `ProjectionContext`,
+/// so this algorithm only makes sense through `project_with_context`, eg. from `TimecurveSet::new`.
+/// Called with `project` alone, it behaves like the plain SMACOF algorithm.
+pub struct TemporalSMACOF {
+    /// The maximum number of iterations.
+    max_iterations: usize,
+    /// The algorithm stops when the penalised stress improves by less than this value between two iterations.
+    tolerance: f64,
+    /// The weight of the temporal penalty.
+    temporal_weight: f64,
+}
+
+impl TemporalSMACOF {
+    /// Creates a new instance of the time-aware SMACOF algorithm.
+    ///
+    /// ### Arguments
+    ///
+    /// * `max_iterations` - The maximum number of iterations. 300 is a reasonable value.
+    /// * `tolerance` - The algorithm stops when the normalised penalised stress improves by less than this value.
+    ///   1e-6 is a reasonable value.
+    /// * `temporal_weight` - The weight of the penalty. With 1.0, the distance between two consecutive points
+    ///   weighs as much as all the stress terms of one point. 0.0 gives the plain SMACOF algorithm.
+    pub fn new(max_iterations: usize, tolerance: f64, temporal_weight: f64) -> Self {
+        Self {
+            max_iterations,
+            tolerance,
+            temporal_weight,
+        }
+    }
+
+    /// Projects the points like `project_with_context`, but also returns the final stress of the layout.
+    ///
+    /// ### Arguments
+    ///
+    /// * `distance_matrix` - A reference to a vector of rows representing the distance matrix.
+    /// * `context` - The timestamps and dataset membership of the rows of the matrix.
+    ///
+    /// ### Returns
+    ///
+    /// A tuple containing the projected points and the normalised stress,
+    /// ie. Σ(δij - dij)² / Σδij², without the temporal penalty.
+    pub fn project_with_stress(
+        &self,
+        distance_matrix: &[Vec<f64>],
+        context: &ProjectionContext,
+    ) -> Result<(Vec<Position>, f64), TimecurveError> {
+        let n = check_complete_matrix(distance_matrix)?;
+
+        if context.get_timestamps().len() != n || context.get_datasets().len() != n {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::InvalidParameter,
+                Some(&format!(
+                    "Context describes {} points but the matrix has {} rows",
+                    context.get_timestamps().len(),
+                    n
+                )),
+            ));
+        }
+        if self.temporal_weight < 0.0 {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::InvalidParameter,
+                Some(&format!(
+                    "Temporal weight must not be negative, got {}",
+                    self.temporal_weight
+                )),
+            ));
+        }
+
+        let neighbours = context.temporal_neighbours();
+        let lambda = self.temporal_weight * (n as f64 - 1.0).max(1.0);
+
+        let mut layout = ClassicalMDS::new().project_rows(distance_matrix)?;
+        if layout
+            .iter()
+            .any(|p| !p.get_x().is_finite() || !p.get_y().is_finite())
+        {
+            layout = circle_layout(distance_matrix);
+        }
+
+        let norm: f64 = distance_matrix
+            .iter()
+            .enumerate()
+            .flat_map(|(i, row)| row[i + 1..].iter())
+            .map(|d| d * d)
+            .sum::<f64>()
+            .max(f64::EPSILON);
+        let penalised_stress = |layout: &[Position]| {
+            normalised_stress(distance_matrix, layout)
+                + lambda
+                    * neighbours
+                        .iter()
+                        .map(|&(i, j)| layout[i].distance(&layout[j]).powi(2))
+                        .sum::<f64>()
+                    / norm
+        };
+
+        let mut stress = penalised_stress(&layout);
+        let mut iterations = 0;
+
+        while iterations < self.max_iterations {
+            layout = majorization_step(distance_matrix, &layout, &neighbours, lambda);
+            iterations += 1;
+
+            let new_stress = penalised_stress(&layout);
+            let improvement = stress - new_stress;
+            stress = new_stress;
+
+            if improvement < self.tolerance {
+                break;
+            }
+        }
+
+        let stress = normalised_stress(distance_matrix, &layout);
+        info!(
+            "Time-aware SMACOF stopped after {} iterations, normalised stress = {:.6}",
+            iterations, stress
+        );
+
+        Ok((layout, stress))
+    }
+}
+
+impl ProjectionAlgorithm for TemporalSMACOF {
+    fn project(&self, distance_matrix: &Vec<Vec<f64>>) -> Result<Vec<Position>, TimecurveError> {
+        warn!("Time-aware SMACOF called without the time order of the points, no temporal penalty is applied.");
+        let context = ProjectionContext::new(
+            vec![0; distance_matrix.len()],
+            (0..distance_matrix.len()).collect(),
+        );
+        Ok(self.project_with_stress(distance_matrix, &context)?.0)
+    }
+
+    fn project_with_context(
+        &self,
+        distance_matrix: &Vec<Vec<f64>>,
+        context: &ProjectionContext,
+    ) -> Result<Vec<Position>, TimecurveError> {
+        Ok(self.project_with_stress(distance_matrix, context)?.0)
+    }
+}
+
+/// Computes one step of the majorization of the penalised stress.
+///
+/// The stress is majorized like in the SMACOF algorithm, and the penalty λ Σ dij² over the temporal neighbours
+/// is already quadratic, so the next layout X is the solution of (V + λL) X = B(Z) Z,
+/// where Z is the current layout, V = nI - 11t and L is the laplacian of the temporal neighbours graph.
+///
+/// ### Arguments
+///
+/// * `distance_matrix` - The distances to preserve.
+/// * `layout` - The current positions of the points.
+/// * `neighbours` - The pairs of temporal neighbours.
+/// * `lambda` - The weight of the penalty.
+///
+/// ### Returns
+///
+/// The new positions of the points. The penalised stress never increases.
+fn majorization_step(
+    distance_matrix: &[Vec<f64>],
+    layout: &[Position],
+    neighbours: &[(usize, usize)],
+    lambda: f64,
+) -> Vec<Position> {
+    let n = layout.len();
+
+    // right hand side B(Z) Z, which is centred
+    let mut rhs = [vec![0.0; n], vec![0.0; n]];
+    for i in 0..n {
+        for j in 0..n {
+            let d = layout[i].distance(&layout[j]);
+            if i == j || d == 0.0 {
+                continue;
+            }
+            let ratio = distance_matrix[i][j] / d;
+            rhs[0][i] += ratio * (layout[i].get_x() - layout[j].get_x());
+            rhs[1][i] += ratio * (layout[i].get_y() - layout[j].get_y());
+        }
+    }
+
+    // V + λL, applied without building the matrix
+    let apply = |x: &[f64]| -> Vec<f64> {
+        let sum: f64 = x.iter().sum();
+        let mut y: Vec<f64> = x.iter().map(|v| n as f64 * v - sum).collect();
+        for &(i, j) in neighbours {
+            let diff = lambda * (x[i] - x[j]);
+            y[i] += diff;
+            y[j] -= diff;
+        }
+        y
+    };
+
+    let x = conjugate_gradient(apply, &rhs[0], layout.iter().map(|p| p.get_x()).collect());
+    let y = conjugate_gradient(apply, &rhs[1], layout.iter().map(|p| p.get_y()).collect());
+
+    x.into_iter()
+        .zip(y)
+        .map(|(x, y)| Position::new(x, y))
+        .collect()
+}
+
+/// Solves the linear system A x = b with the conjugate gradient method, for a symmetric positive
+/// (semi-)definite matrix A. The system solved by `majorization_step` is well conditioned,
+/// so a few iterations are enough.
+///
+/// ### Arguments
+///
+/// * `apply` - The function computing A x.
+/// * `b` - The right hand side.
+/// * `x` - The starting point.
+///
+/// ### Returns
+///
+/// The approximate solution.
+fn conjugate_gradient(apply: impl Fn(&[f64]) -> Vec<f64>, b: &[f64], mut x: Vec<f64>) -> Vec<f64> {
+    const MAX_ITERATIONS: usize = 100;
+    const TOLERANCE: f64 = 1e-10;
+
+    let dot = |a: &[f64], b: &[f64]| a.iter().zip(b).map(|(a, b)| a * b).sum::<f64>();
+
+    // the solution is only defined up to a translation, the starting point is centred like b
+    let mean = x.iter().sum::<f64>() / x.len() as f64;
+    x.iter_mut().for_each(|v| *v -= mean);
+
+    let ax = apply(&x);
+    let mut r: Vec<f64> = b.iter().zip(&ax).map(|(b, ax)| b - ax).collect();
+    let mut p = r.clone();
+    let mut rr = dot(&r, &r);
+    let threshold = TOLERANCE * dot(b, b).max(f64::EPSILON);
+
+    for _ in 0..MAX_ITERATIONS {
+        if rr <= threshold {
+            break;
+        }
+        let ap = apply(&p);
+        let alpha = rr / dot(&p, &ap);
+        for i in 0..x.len() {
+            x[i] += alpha * p[i];
+            r[i] -= alpha * ap[i];
+        }
+        let new_rr = dot(&r, &r);
+        let beta = new_rr / rr;
+        rr = new_rr;
+        for i in 0..p.len() {
+            p[i] = r[i] + beta * p[i];
+        }
+    }
+
+    x
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{input::InputData, projection::SMACOF};
+
+    fn psfr() -> (Vec<Vec<f64>>, ProjectionContext) {
+        let input = InputData::from_filename(&format!(
+            "{}/tests/psfr100points.json",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let context = ProjectionContext::from_input(&input).unwrap();
+        (input.get_distance_matrix().clone(), context)
+    }
+
+    /// Sum of the distances between consecutive points.
+    fn curve_length(points: &[Position], context: &ProjectionContext) -> f64 {
+        context
+            .temporal_neighbours()
+            .iter()
+            .map(|&(i, j)| points[i].distance(&points[j]))
+            .sum()
+    }
+
+    #[test]
+    fn temporal_smacof_without_penalty_is_smacof() {
+        let (distance_matrix, context) = psfr();
+
+        let (_, stress) = TemporalSMACOF::new(50, 0.0, 0.0)
+            .project_with_stress(&distance_matrix, &context)
+            .unwrap();
+        let (_, reference) = SMACOF::new(50, 0.0, None)
+            .project_with_stress(&distance_matrix)
+            .unwrap();
+
+        assert!((stress - reference).abs() < 1e-6);
+    }
+
+    #[test]
+    fn temporal_smacof_brings_consecutive_points_closer() {
+        let (distance_matrix, context) = psfr();
+
+        let (free, free_stress) = TemporalSMACOF::new(100, 1e-6, 0.0)
+            .project_with_stress(&distance_matrix, &context)
+            .unwrap();
+        let (penalised, penalised_stress) = TemporalSMACOF::new(100, 1e-6, 1.0)
+            .project_with_stress(&distance_matrix, &context)
+            .unwrap();
+
+        assert!(curve_length(&penalised, &context) < curve_length(&free, &context));
+        // the distances are less well preserved in exchange
+        assert!(penalised_stress >= free_stress);
+    }
+
+    #[test]
+    fn temporal_neighbours_follow_time_order_within_datasets() {
+        let context = ProjectionContext::new(vec![30, 10, 20, 5, 1], vec![0, 0, 0, 1, 1]);
+
+        assert_eq!(context.temporal_neighbours(), vec![(1, 2), (2, 0), (4, 3)]);
+    }
+}
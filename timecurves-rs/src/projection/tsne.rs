@@ -0,0 +1,552 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+use super::projection_algorithm::{check_complete_matrix, ProjectionAlgorithm};
+use crate::{
+    error::{TimecurveError, TimecurveErrorKind},
+    timecurve::Position,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// The way the repulsive forces between all pairs of points are computed by t-SNE.
+#[derive(Clone, Copy)]
+pub enum TSNEMode {
+    /// All pairs are computed exactly. The cost of an iteration is quadratic in the number of points,
+    /// so it should only be used for a few hundred points.
+    Exact,
+    /// The forces are approximated with a quadtree, and the input similarities are restricted
+    /// to the 3 * perplexity nearest neighbours of each point. The cost of an iteration is O(n log n).
+    /// The parameter is the accuracy threshold θ, between 0.0 (exact) and 1.0 (fast). 0.5 is a reasonable value.
+    BarnesHut(f64),
+}
+
+/// Structure representing the t-distributed Stochastic Neighbor Embedding algorithm (van der Maaten & Hinton).
+///
+/// t-SNE preserves the local neighbourhoods of the points rather than the global distances,
+/// which makes clusters of similar points stand out. The distances between the clusters are not meaningful.
+pub struct TSNE {
+    /// The perplexity, ie. the effective number of neighbours of each point.
+    perplexity: f64,
+    /// The step size of the gradient descent.
+    learning_rate: f64,
+    /// The number of iterations of the gradient descent.
+    iterations: usize,
+    /// The seed of the random initial layout.
+    seed: u64,
+    /// The way the repulsive forces are computed.
+    mode: TSNEMode,
+}
+
+impl TSNE {
+    /// Creates a new instance of the t-SNE algorithm.
+    ///
+    /// ### Arguments
+    ///
+    /// * `perplexity` - The effective number of neighbours of each point. 30.0 is a reasonable value,
+    ///   it should be lower for small datasets.
+    /// * `learning_rate` - The step size of the gradient descent. 200.0 is a reasonable value.
+    /// * `iterations` - The number of iterations of the gradient descent. 1000 is a reasonable value.
+    /// * `seed` - The seed of the random initial layout. The same seed always gives the same result.
+    /// * `mode` - The way the repulsive forces are computed.
+    pub fn new(
+        perplexity: f64,
+        learning_rate: f64,
+        iterations: usize,
+        seed: u64,
+        mode: TSNEMode,
+    ) -> Self {
+        Self {
+            perplexity,
+            learning_rate,
+            iterations,
+            seed,
+            mode,
+        }
+    }
+}
+
+impl ProjectionAlgorithm for TSNE {
+    fn project(&self, distance_matrix: &Vec<Vec<f64>>) -> Result<Vec<Position>, TimecurveError> {
+        const EXAGGERATION: f64 = 12.0;
+        const MIN_GAIN: f64 = 0.01;
+
+        let n = check_complete_matrix(distance_matrix)?;
+
+        if self.perplexity <= 0.0 || self.learning_rate <= 0.0 {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::InvalidParameter,
+                Some("t-SNE perplexity and learning rate must be positive"),
+            ));
+        }
+        if let TSNEMode::BarnesHut(theta) = self.mode {
+            if !(0.0..=1.0).contains(&theta) {
+                return Err(TimecurveError::new(
+                    TimecurveErrorKind::InvalidParameter,
+                    Some(&format!("Barnes-Hut θ must be in [0, 1], got {}", theta)),
+                ));
+            }
+        }
+
+        if n == 1 {
+            return Ok(vec![Position::new(0.0, 0.0)]);
+        }
+
+        let neighbours = match self.mode {
+            TSNEMode::Exact => n - 1,
+            TSNEMode::BarnesHut(_) => ((3.0 * self.perplexity) as usize).clamp(1, n - 1),
+        };
+        let p = joint_probabilities(distance_matrix, self.perplexity, neighbours);
+
+        // small random initial layout
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let mut y: Vec<[f64; 2]> = (0..n)
+            .map(|_| [rng.gen_range(-1e-4..1e-4), rng.gen_range(-1e-4..1e-4)])
+            .collect();
+        let mut velocity = vec![[0.0_f64; 2]; n];
+        let mut gains = vec![[1.0_f64; 2]; n];
+
+        // the similarities are exaggerated at the beginning so that clusters form early
+        let exaggeration_end = (self.iterations / 4).min(250);
+
+        for iteration in 0..self.iterations {
+            let exaggeration = if iteration < exaggeration_end {
+                EXAGGERATION
+            } else {
+                1.0
+            };
+            let momentum = if iteration < exaggeration_end {
+                0.5
+            } else {
+                0.8
+            };
+
+            let repulsion = match self.mode {
+                TSNEMode::Exact => exact_repulsion(&y),
+                TSNEMode::BarnesHut(theta) => QuadTree::new(&y).repulsion(&y, theta),
+            };
+
+            for i in 0..n {
+                // attractive forces, only between neighbours
+                let mut attraction = [0.0; 2];
+                for &(j, p_ij) in &p[i] {
+                    let diff = [y[i][0] - y[j][0], y[i][1] - y[j][1]];
+                    let w = 1.0 / (1.0 + diff[0] * diff[0] + diff[1] * diff[1]);
+                    attraction[0] += exaggeration * p_ij * w * diff[0];
+                    attraction[1] += exaggeration * p_ij * w * diff[1];
+                }
+
+                for d in 0..2 {
+                    let gradient = 4.0 * (attraction[d] - repulsion[i][d]);
+
+                    // adaptive gains : the step grows while the gradient keeps the same direction
+                    gains[i][d] = if gradient.signum() != velocity[i][d].signum() {
+                        gains[i][d] + 0.2
+                    } else {
+                        (gains[i][d] * 0.8).max(MIN_GAIN)
+                    };
+
+                    velocity[i][d] =
+                        momentum * velocity[i][d] - self.learning_rate * gains[i][d] * gradient;
+                }
+            }
+
+            for i in 0..n {
+                y[i][0] += velocity[i][0];
+                y[i][1] += velocity[i][1];
+            }
+        }
+
+        Ok(y.iter().map(|p| Position::new(p[0], p[1])).collect())
+    }
+}
+
+/// Computes the symmetric joint probabilities pij of the input space.
+///
+/// ### Arguments
+///
+/// * `distance_matrix` - The distance matrix.
+/// * `perplexity` - The effective number of neighbours of each point.
+/// * `neighbours` - The number of nearest neighbours of each point that get a non-zero probability.
+///
+/// ### Returns
+///
+/// A sparse matrix : for each point, a list of (neighbour index, pij).
+fn joint_probabilities(
+    distance_matrix: &[Vec<f64>],
+    perplexity: f64,
+    neighbours: usize,
+) -> Vec<Vec<(usize, f64)>> {
+    const TOLERANCE: f64 = 1e-5;
+    const MAX_STEPS: usize = 200;
+
+    let n = distance_matrix.len();
+    let target_entropy = perplexity.ln();
+
+    // conditional probabilities pj|i
+    let mut conditional: Vec<Vec<(usize, f64)>> = Vec::with_capacity(n);
+    for (i, row) in distance_matrix.iter().enumerate() {
+        let mut nearest: Vec<usize> = (0..n).filter(|&j| j != i).collect();
+        if neighbours < nearest.len() {
+            nearest.select_nth_unstable_by(neighbours, |&a, &b| {
+                row[a]
+                    .partial_cmp(&row[b])
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            });
+            nearest.truncate(neighbours);
+        }
+        let squared: Vec<f64> = nearest.iter().map(|&j| row[j] * row[j]).collect();
+
+        // binary search of the precision β so that the entropy of the distribution matches the perplexity
+        let mut beta = 1.0;
+        let mut beta_min = f64::NEG_INFINITY;
+        let mut beta_max = f64::INFINITY;
+        let mut probabilities = vec![0.0; nearest.len()];
+
+        for _ in 0..MAX_STEPS {
+            // distances are shifted by the smallest one to avoid underflows, which doesn't change the distribution
+            let min = squared.iter().cloned().fold(f64::INFINITY, f64::min);
+            let mut sum = 0.0;
+            for (k, d) in squared.iter().enumerate() {
+                probabilities[k] = (-(d - min) * beta).exp();
+                sum += probabilities[k];
+            }
+
+            let mut entropy = 0.0;
+            for (k, d) in squared.iter().enumerate() {
+                probabilities[k] /= sum;
+                entropy += beta * (d - min) * probabilities[k];
+            }
+            entropy += sum.ln();
+
+            if (entropy - target_entropy).abs() < TOLERANCE {
+                break;
+            }
+
+            // too much entropy means the distribution is too wide, so the precision must increase
+            if entropy > target_entropy {
+                beta_min = beta;
+                beta = if beta_max.is_infinite() {
+                    beta * 2.0
+                } else {
+                    (beta + beta_max) / 2.0
+                };
+            } else {
+                beta_max = beta;
+                beta = if beta_min.is_infinite() {
+                    beta / 2.0
+                } else {
+                    (beta + beta_min) / 2.0
+                };
+            }
+        }
+
+        conditional.push(nearest.into_iter().zip(probabilities).collect());
+    }
+
+    // pij = (pj|i + pi|j) / 2n
+    let mut joint: Vec<Vec<(usize, f64)>> = vec![Vec::new(); n];
+    for (i, row) in conditional.iter().enumerate() {
+        for &(j, p) in row {
+            let p_ji = conditional[j]
+                .iter()
+                .find(|&&(k, _)| k == i)
+                .map(|&(_, p)| p);
+
+            // each pair is only added once, from the point with the lowest index,
+            // or from the only point that has the other as a neighbour
+            if i < j || p_ji.is_none() {
+                let p_ij = (p + p_ji.unwrap_or(0.0)) / (2.0 * n as f64);
+                joint[i].push((j, p_ij));
+                joint[j].push((i, p_ij));
+            }
+        }
+    }
+
+    joint
+}
+
+/// Computes the normalised repulsive forces between all pairs of points exactly.
+///
+/// ### Arguments
+///
+/// * `y` - The current layout.
+///
+/// ### Returns
+///
+/// For each point, Σj wij² (yi - yj) / Z where wij = 1 / (1 + |yi - yj|²) and Z = Σk≠l wkl.
+fn exact_repulsion(y: &[[f64; 2]]) -> Vec<[f64; 2]> {
+    let n = y.len();
+    let mut forces = vec![[0.0; 2]; n];
+    let mut z = 0.0;
+
+    for i in 0..n {
+        for j in 0..n {
+            if i == j {
+                continue;
+            }
+            let diff = [y[i][0] - y[j][0], y[i][1] - y[j][1]];
+            let w = 1.0 / (1.0 + diff[0] * diff[0] + diff[1] * diff[1]);
+            z += w;
+            forces[i][0] += w * w * diff[0];
+            forces[i][1] += w * w * diff[1];
+        }
+    }
+
+    for force in &mut forces {
+        force[0] /= z;
+        force[1] /= z;
+    }
+    forces
+}
+
+/// A node of the quadtree used by the Barnes-Hut approximation.
+struct QuadNode {
+    /// The center of the square covered by the node.
+    center: [f64; 2],
+    /// Half the width of the square covered by the node.
+    half_width: f64,
+    /// The sum of the positions of the points in the node.
+    mass: [f64; 2],
+    /// The number of points in the node.
+    count: usize,
+    /// The index of the first of the four children in the tree, if the node is subdivided.
+    children: Option<usize>,
+    /// The point held by the node, if it is a leaf with a single point.
+    point: Option<usize>,
+}
+
+/// A quadtree summarizing groups of points by their center of mass.
+struct QuadTree {
+    nodes: Vec<QuadNode>,
+}
+
+impl QuadTree {
+    /// Beyond this depth, points are grouped in the same leaf. Avoids infinite subdivisions for duplicated points.
+    const MAX_DEPTH: usize = 50;
+
+    /// Builds the quadtree of a layout.
+    fn new(y: &[[f64; 2]]) -> Self {
+        let mut min = [f64::INFINITY; 2];
+        let mut max = [f64::NEG_INFINITY; 2];
+        for p in y {
+            for d in 0..2 {
+                min[d] = min[d].min(p[d]);
+                max[d] = max[d].max(p[d]);
+            }
+        }
+
+        let half_width = ((max[0] - min[0]).max(max[1] - min[1]) / 2.0).max(f64::EPSILON) * 1.001;
+        let mut tree = QuadTree {
+            nodes: vec![QuadNode::new(
+                [(min[0] + max[0]) / 2.0, (min[1] + max[1]) / 2.0],
+                half_width,
+            )],
+        };
+
+        for (i, p) in y.iter().enumerate() {
+            tree.insert(0, i, *p, y, 0);
+        }
+        tree
+    }
+
+    /// Inserts a point in the subtree starting at `node`.
+    fn insert(&mut self, node: usize, index: usize, p: [f64; 2], y: &[[f64; 2]], depth: usize) {
+        self.nodes[node].count += 1;
+        self.nodes[node].mass[0] += p[0];
+        self.nodes[node].mass[1] += p[1];
+
+        if self.nodes[node].children.is_none() {
+            if self.nodes[node].count == 1 {
+                self.nodes[node].point = Some(index);
+                return;
+            }
+            if depth >= Self::MAX_DEPTH {
+                return;
+            }
+
+            // subdivide, and move the point that was held by this leaf down
+            let first_child = self.nodes.len();
+            let center = self.nodes[node].center;
+            let quarter = self.nodes[node].half_width / 2.0;
+            for (dx, dy) in [(-1.0, -1.0), (1.0, -1.0), (-1.0, 1.0), (1.0, 1.0)] {
+                self.nodes.push(QuadNode::new(
+                    [center[0] + dx * quarter, center[1] + dy * quarter],
+                    quarter,
+                ));
+            }
+            self.nodes[node].children = Some(first_child);
+
+            if let Some(old) = self.nodes[node].point.take() {
+                let child = self.child_for(node, y[old]);
+                self.insert(child, old, y[old], y, depth + 1);
+            }
+        }
+
+        let child = self.child_for(node, p);
+        self.insert(child, index, p, y, depth + 1);
+    }
+
+    /// Returns the child of a subdivided node in which a position falls.
+    fn child_for(&self, node: usize, p: [f64; 2]) -> usize {
+        let center = self.nodes[node].center;
+        let first_child = self.nodes[node].children.unwrap(); // only called on subdivided nodes
+        let right = (p[0] > center[0]) as usize;
+        let top = (p[1] > center[1]) as usize;
+        first_child + right + 2 * top
+    }
+
+    /// Computes the normalised repulsive forces of the layout, approximating groups of points
+    /// that are far enough by their center of mass.
+    fn repulsion(&self, y: &[[f64; 2]], theta: f64) -> Vec<[f64; 2]> {
+        let mut forces = vec![[0.0; 2]; y.len()];
+        let mut z = 0.0;
+        let mut stack = Vec::new();
+
+        for (i, p) in y.iter().enumerate() {
+            stack.push(0);
+            while let Some(k) = stack.pop() {
+                let node = &self.nodes[k];
+                if node.count == 0 || node.point == Some(i) {
+                    continue;
+                }
+
+                let count = node.count as f64;
+                let diff = [p[0] - node.mass[0] / count, p[1] - node.mass[1] / count];
+                let squared = diff[0] * diff[0] + diff[1] * diff[1];
+
+                match node.children {
+                    Some(first_child) if 2.0 * node.half_width >= theta * squared.sqrt() => {
+                        stack.extend(first_child..first_child + 4);
+                    }
+                    _ => {
+                        let w = 1.0 / (1.0 + squared);
+                        // a grouped leaf at maximum depth may contain the point itself
+                        let count = if node.children.is_none() && squared == 0.0 {
+                            count - 1.0
+                        } else {
+                            count
+                        };
+                        z += count * w;
+                        forces[i][0] += count * w * w * diff[0];
+                        forces[i][1] += count * w * w * diff[1];
+                    }
+                }
+            }
+        }
+
+        for force in &mut forces {
+            force[0] /= z;
+            force[1] /= z;
+        }
+        forces
+    }
+}
+
+impl QuadNode {
+    fn new(center: [f64; 2], half_width: f64) -> Self {
+        QuadNode {
+            center,
+            half_width,
+            mass: [0.0; 2],
+            count: 0,
+            children: None,
+            point: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds the distance matrix of two groups of points on a line, far from each other.
+    fn two_clusters_distance_matrix() -> Vec<Vec<f64>> {
+        let positions: Vec<f64> = (0..40)
+            .map(|i| if i < 20 { i as f64 } else { 1000.0 + i as f64 })
+            .collect();
+        positions
+            .iter()
+            .map(|a| positions.iter().map(|b| (a - b).abs()).collect())
+            .collect()
+    }
+
+    /// Checks that the points of each group are closer to each other than to the other group.
+    fn assert_clusters_separated(points: &[Position]) {
+        let mean_distance = |a: std::ops::Range<usize>, b: std::ops::Range<usize>| {
+            let mut sum = 0.0;
+            let mut count = 0.0;
+            for i in a {
+                for j in b.clone() {
+                    sum += points[i].distance(&points[j]);
+                    count += 1.0;
+                }
+            }
+            sum / count
+        };
+
+        let inter = mean_distance(0..20, 20..40);
+        assert!(mean_distance(0..20, 0..20) < inter);
+        assert!(mean_distance(20..40, 20..40) < inter);
+    }
+
+    #[test]
+    fn tsne_separates_clusters() {
+        let distance_matrix = two_clusters_distance_matrix();
+
+        for mode in [TSNEMode::Exact, TSNEMode::BarnesHut(0.5)] {
+            let tsne = TSNE::new(5.0, 100.0, 300, 1, mode);
+            let points = tsne.project(&distance_matrix).unwrap();
+
+            assert_eq!(points.len(), 40);
+            assert!(points
+                .iter()
+                .all(|p| p.get_x().is_finite() && p.get_y().is_finite()));
+            assert_clusters_separated(&points);
+        }
+    }
+
+    #[test]
+    fn tsne_accepts_the_bounds_of_theta() {
+        let distance_matrix = two_clusters_distance_matrix();
+
+        for theta in [0.0, 1.0] {
+            let tsne = TSNE::new(5.0, 100.0, 50, 1, TSNEMode::BarnesHut(theta));
+            assert!(tsne.project(&distance_matrix).is_ok());
+        }
+        for theta in [-0.1, 1.1, f64::NAN] {
+            let tsne = TSNE::new(5.0, 100.0, 50, 1, TSNEMode::BarnesHut(theta));
+            assert!(matches!(
+                tsne.project(&distance_matrix).map_err(|e| e.kind),
+                Err(TimecurveErrorKind::InvalidParameter)
+            ));
+        }
+    }
+
+    #[test]
+    fn tsne_is_reproducible() {
+        let distance_matrix = two_clusters_distance_matrix();
+        let tsne = TSNE::new(5.0, 100.0, 100, 7, TSNEMode::BarnesHut(0.5));
+
+        let a = tsne.project(&distance_matrix).unwrap();
+        let b = tsne.project(&distance_matrix).unwrap();
+
+        for i in 0..a.len() {
+            assert_eq!(a[i].get_x(), b[i].get_x());
+            assert_eq!(a[i].get_y(), b[i].get_y());
+        }
+    }
+
+    #[test]
+    fn joint_probabilities_sum_to_one() {
+        let distance_matrix = two_clusters_distance_matrix();
+
+        for neighbours in [39, 15] {
+            let p = joint_probabilities(&distance_matrix, 5.0, neighbours);
+            let sum: f64 = p.iter().flatten().map(|(_, p)| p).sum();
+            assert!((sum - 1.0).abs() < 1e-9);
+        }
+    }
+}
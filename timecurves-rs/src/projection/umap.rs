@@ -0,0 +1,360 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+use super::{
+    classical_mds::ClassicalMDS,
+    projection_algorithm::{check_complete_matrix, ProjectionAlgorithm},
+};
+use crate::{
+    error::{TimecurveError, TimecurveErrorKind},
+    timecurve::Position,
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Structure representing the Uniform Manifold Approximation and Projection algorithm (McInnes et al.).
+///
+/// The k nearest neighbour graph is built directly from the distance matrix, then the layout is optimised
+/// so that neighbours stay close and other points are pushed away. Like t-SNE, it makes clusters stand out,
+/// for example groups of near-duplicate video frames, but it keeps more of the global structure.
+pub struct UMAP {
+    /// The number of neighbours of each point in the graph.
+    n_neighbors: usize,
+    /// The minimum distance between points in the layout.
+    min_dist: f64,
+    /// The seed of the random sampling.
+    seed: u64,
+}
+
+impl UMAP {
+    /// Creates a new instance of the UMAP algorithm.
+    ///
+    /// ### Arguments
+    ///
+    /// * `n_neighbors` - The number of neighbours of each point in the graph. Small values favour the local structure,
+    ///   large values the global one. 15 is a reasonable value.
+    /// * `min_dist` - The minimum distance between points in the layout, between 0.0 and 1.0.
+    ///   Small values give tighter clusters. 0.1 is a reasonable value.
+    /// * `seed` - The seed of the random sampling. The same seed always gives the same result.
+    pub fn new(n_neighbors: usize, min_dist: f64, seed: u64) -> Self {
+        Self {
+            n_neighbors,
+            min_dist,
+            seed,
+        }
+    }
+}
+
+impl ProjectionAlgorithm for UMAP {
+    fn project(&self, distance_matrix: &Vec<Vec<f64>>) -> Result<Vec<Position>, TimecurveError> {
+        const NEGATIVE_SAMPLES: usize = 5;
+        const INITIAL_SIZE: f64 = 10.0;
+        const NOISE: f64 = 0.01;
+
+        let n = check_complete_matrix(distance_matrix)?;
+
+        if self.n_neighbors < 2 || !(0.0..=1.0).contains(&self.min_dist) {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::InvalidParameter,
+                Some(&format!(
+                    "UMAP needs at least 2 neighbours and a minimum distance in [0, 1], got {} and {}",
+                    self.n_neighbors, self.min_dist
+                )),
+            ));
+        }
+
+        if n == 1 {
+            return Ok(vec![Position::new(0.0, 0.0)]);
+        }
+
+        let edges = fuzzy_graph(distance_matrix, self.n_neighbors.min(n - 1));
+        let (a, b) = fit_curve(self.min_dist);
+        let mut rng = StdRng::seed_from_u64(self.seed);
+
+        // the layout starts from the classical MDS, scaled to a fixed size,
+        // which gives a more stable global structure than a random layout
+        let initial = ClassicalMDS::new().project_rows(distance_matrix)?;
+        let extent = initial
+            .iter()
+            .flat_map(|p| [p.get_x().abs(), p.get_y().abs()])
+            .filter(|v| v.is_finite())
+            .fold(0.0, f64::max);
+        let mut y: Vec<[f64; 2]> = initial
+            .iter()
+            .map(|p| {
+                if extent > 0.0 && p.get_x().is_finite() && p.get_y().is_finite() {
+                    // a little noise gets points out of degenerate layouts, eg. all on a line
+                    [
+                        p.get_x() / extent * INITIAL_SIZE + rng.gen_range(-NOISE..NOISE),
+                        p.get_y() / extent * INITIAL_SIZE + rng.gen_range(-NOISE..NOISE),
+                    ]
+                } else {
+                    [
+                        rng.gen_range(-INITIAL_SIZE..INITIAL_SIZE),
+                        rng.gen_range(-INITIAL_SIZE..INITIAL_SIZE),
+                    ]
+                }
+            })
+            .collect();
+
+        let epochs = if n <= 10000 { 500 } else { 200 };
+
+        // each edge is sampled proportionally to its weight : the strongest ones at every epoch
+        let max_weight = edges.iter().fold(0.0, |acc: f64, e| acc.max(e.2));
+        let epochs_per_sample: Vec<f64> = edges.iter().map(|e| max_weight / e.2).collect();
+        let mut next_sample = epochs_per_sample.clone();
+
+        let clip = |v: f64| v.clamp(-4.0, 4.0);
+
+        for epoch in 0..epochs {
+            let alpha = 1.0 - epoch as f64 / epochs as f64;
+
+            for (e, &(i, j, _)) in edges.iter().enumerate() {
+                if next_sample[e] > (epoch + 1) as f64 {
+                    continue;
+                }
+                next_sample[e] += epochs_per_sample[e];
+
+                // attraction between the two ends of the edge
+                let diff = [y[i][0] - y[j][0], y[i][1] - y[j][1]];
+                let squared = diff[0] * diff[0] + diff[1] * diff[1];
+                if squared > 0.0 {
+                    let coefficient =
+                        -2.0 * a * b * squared.powf(b - 1.0) / (1.0 + a * squared.powf(b));
+                    for d in 0..2 {
+                        let step = clip(coefficient * diff[d]) * alpha;
+                        y[i][d] += step;
+                        y[j][d] -= step;
+                    }
+                }
+
+                // repulsion from random points
+                for _ in 0..NEGATIVE_SAMPLES {
+                    let k = rng.gen_range(0..n);
+                    if k == i {
+                        continue;
+                    }
+                    let diff = [y[i][0] - y[k][0], y[i][1] - y[k][1]];
+                    let squared = diff[0] * diff[0] + diff[1] * diff[1];
+                    let coefficient = 2.0 * b / ((0.001 + squared) * (1.0 + a * squared.powf(b)));
+                    for d in 0..2 {
+                        let step = if coefficient > 0.0 {
+                            clip(coefficient * diff[d])
+                        } else {
+                            4.0
+                        };
+                        y[i][d] += step * alpha;
+                    }
+                }
+            }
+        }
+
+        Ok(y.iter().map(|p| Position::new(p[0], p[1])).collect())
+    }
+}
+
+/// Builds the fuzzy k nearest neighbour graph of the points.
+///
+/// ### Arguments
+///
+/// * `distance_matrix` - The distance matrix.
+/// * `k` - The number of neighbours of each point.
+///
+/// ### Returns
+///
+/// The list of undirected edges (i, j, weight), with i < j and weights in \]0, 1\].
+fn fuzzy_graph(distance_matrix: &[Vec<f64>], k: usize) -> Vec<(usize, usize, f64)> {
+    const TOLERANCE: f64 = 1e-5;
+    const MAX_STEPS: usize = 64;
+
+    let n = distance_matrix.len();
+    let target = (k as f64).log2();
+
+    // directed weights of the k nearest neighbours of each point
+    let mut directed: Vec<Vec<(usize, f64)>> = Vec::with_capacity(n);
+    for (i, row) in distance_matrix.iter().enumerate() {
+        let mut nearest: Vec<usize> = (0..n).filter(|&j| j != i).collect();
+        nearest.sort_by(|&a, &b| {
+            row[a]
+                .partial_cmp(&row[b])
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        nearest.truncate(k);
+
+        // ρ is the distance to the nearest neighbour, so that each point is connected to at least one other
+        let rho = row[nearest[0]];
+
+        // binary search of σ so that Σ exp(-(d - ρ) / σ) = log2(k)
+        let mut sigma = 1.0;
+        let mut low = 0.0;
+        let mut high = f64::INFINITY;
+        for _ in 0..MAX_STEPS {
+            let sum: f64 = nearest
+                .iter()
+                .map(|&j| (-(row[j] - rho).max(0.0) / sigma).exp())
+                .sum();
+
+            if (sum - target).abs() < TOLERANCE {
+                break;
+            }
+            if sum > target {
+                high = sigma;
+                sigma = (low + high) / 2.0;
+            } else {
+                low = sigma;
+                sigma = if high.is_infinite() {
+                    sigma * 2.0
+                } else {
+                    (low + high) / 2.0
+                };
+            }
+        }
+
+        directed.push(
+            nearest
+                .iter()
+                .map(|&j| (j, (-(row[j] - rho).max(0.0) / sigma).exp()))
+                .collect(),
+        );
+    }
+
+    // fuzzy union of the two directions : w = wij + wji - wij * wji
+    let mut edges = Vec::new();
+    for (i, neighbours) in directed.iter().enumerate() {
+        for &(j, w_ij) in neighbours {
+            let w_ji = directed[j].iter().find(|&&(k, _)| k == i).map(|&(_, w)| w);
+            // each edge is only added once
+            if i < j || w_ji.is_none() {
+                let w_ji = w_ji.unwrap_or(0.0);
+                let w = w_ij + w_ji - w_ij * w_ji;
+                if w > 0.0 {
+                    edges.push((i.min(j), i.max(j), w));
+                }
+            }
+        }
+    }
+    edges
+}
+
+/// Finds the parameters a and b so that 1 / (1 + a * d^2b) approximates the target membership curve,
+/// which is 1 below `min_dist` and decays exponentially after it.
+///
+/// ### Arguments
+///
+/// * `min_dist` - The minimum distance between points in the layout.
+///
+/// ### Returns
+///
+/// The couple (a, b).
+fn fit_curve(min_dist: f64) -> (f64, f64) {
+    const SPREAD: f64 = 1.0;
+    const SAMPLES: usize = 300;
+
+    let samples: Vec<(f64, f64)> = (1..=SAMPLES)
+        .map(|s| {
+            let d = 3.0 * SPREAD * s as f64 / SAMPLES as f64;
+            let target = if d < min_dist {
+                1.0
+            } else {
+                (-(d - min_dist) / SPREAD).exp()
+            };
+            (d, target)
+        })
+        .collect();
+
+    let error = |a: f64, b: f64| -> f64 {
+        samples
+            .iter()
+            .map(|&(d, target)| (1.0 / (1.0 + a * d.powf(2.0 * b)) - target).powi(2))
+            .sum()
+    };
+
+    // coarse grid search, then refined around the best couple
+    let mut best = (1.0, 1.0);
+    let mut best_error = error(best.0, best.1);
+    let mut a_step = 0.5;
+    let mut b_step = 0.25;
+    for _ in 0..30 {
+        let center = best;
+        for i in -4..=4 {
+            for j in -4..=4 {
+                let a = center.0 + i as f64 * a_step;
+                let b = center.1 + j as f64 * b_step;
+                if a <= 0.0 || b <= 0.0 {
+                    continue;
+                }
+                let e = error(a, b);
+                if e < best_error {
+                    best = (a, b);
+                    best_error = e;
+                }
+            }
+        }
+        if best == center {
+            a_step /= 2.0;
+            b_step /= 2.0;
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds the distance matrix of three groups of near-duplicate points.
+    fn clusters_distance_matrix() -> Vec<Vec<f64>> {
+        let positions: Vec<f64> = (0..45)
+            .map(|i| (i / 15) as f64 * 100.0 + (i % 15) as f64 * 0.1)
+            .collect();
+        positions
+            .iter()
+            .map(|a| positions.iter().map(|b| (a - b).abs()).collect())
+            .collect()
+    }
+
+    #[test]
+    fn umap_separates_clusters() {
+        let distance_matrix = clusters_distance_matrix();
+        let points = UMAP::new(10, 0.1, 3).project(&distance_matrix).unwrap();
+
+        assert_eq!(points.len(), 45);
+
+        // each point is closer to the points of its own cluster than to any other
+        for i in 0..45 {
+            let own = (0..45)
+                .filter(|&j| j / 15 == i / 15)
+                .map(|j| points[i].distance(&points[j]))
+                .fold(0.0, f64::max);
+            let other = (0..45)
+                .filter(|&j| j / 15 != i / 15)
+                .map(|j| points[i].distance(&points[j]))
+                .fold(f64::INFINITY, f64::min);
+            assert!(own < other);
+        }
+    }
+
+    #[test]
+    fn umap_is_reproducible() {
+        let distance_matrix = clusters_distance_matrix();
+        let umap = UMAP::new(10, 0.1, 3);
+
+        let a = umap.project(&distance_matrix).unwrap();
+        let b = umap.project(&distance_matrix).unwrap();
+
+        for i in 0..a.len() {
+            assert_eq!(a[i].get_x(), b[i].get_x());
+            assert_eq!(a[i].get_y(), b[i].get_y());
+        }
+    }
+
+    #[test]
+    fn fit_curve_matches_reference_parameters() {
+        // values given by the reference implementation for min_dist = 0.1 and spread = 1.0
+        let (a, b) = fit_curve(0.1);
+        assert!((a - 1.577).abs() < 0.1);
+        assert!((b - 0.895).abs() < 0.05);
+    }
+}
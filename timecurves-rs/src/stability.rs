@@ -0,0 +1,421 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+//! Stability analysis of a projection, to tell the real features of the curves, eg. loops,
+//! from the artifacts of the projection.
+
+use crate::{
+    error::{TimecurveError, TimecurveErrorKind},
+    input::{Anchor, Dataset, InputData},
+    projection::ProjectionAlgorithm,
+    timecurve::{Position, TimecurveSet},
+};
+use nalgebra::{Matrix3, Vector3};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::collections::HashMap;
+
+/// The ways the input can be perturbed between two runs of a stability analysis.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Perturbation {
+    /// Each distance is multiplied by 1 + ε, with ε drawn from a normal distribution of the given standard deviation,
+    /// eg. 0.05 for a 5% noise. Missing distances stay missing. The feature vectors, if any, are not perturbed.
+    Noise(f64),
+    /// Only the given fraction of the points of each dataset is kept, eg. 0.8, and at least 3 of them.
+    /// The analysis then tells how much the curves depend on a few points.
+    Subsample(f64),
+    /// The input is not perturbed, only the seed given to the projection changes.
+    /// Only makes sense for stochastic projection algorithms like `TSNE` or `UMAP`.
+    Seed,
+}
+
+/// Structure representing a bootstrap analysis of the stability of a projection :
+/// the input is perturbed and projected several times, each run is aligned to a reference set
+/// with a Procrustes analysis, and the spread of the positions of each point is measured.
+pub struct StabilityAnalysis {
+    /// The number of perturbed projections.
+    runs: usize,
+    /// How the input is perturbed.
+    perturbation: Perturbation,
+    /// The seed of the perturbations. Run `i` gives the seed `seed + 1 + i` to the projection.
+    seed: u64,
+}
+
+impl StabilityAnalysis {
+    /// Creates a new stability analysis, with a seed of 0.
+    ///
+    /// ### Arguments
+    ///
+    /// * `runs` - The number of perturbed projections. 20 is a reasonable value.
+    /// * `perturbation` - How the input is perturbed, see `Perturbation`.
+    pub fn new(runs: usize, perturbation: Perturbation) -> Self {
+        Self {
+            runs,
+            perturbation,
+            seed: 0,
+        }
+    }
+
+    /// Sets the seed of the perturbations. The same seed always gives the same result.
+    ///
+    /// ### Arguments
+    ///
+    /// * `seed` - The seed.
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Runs the analysis.
+    ///
+    /// ### Arguments
+    ///
+    /// * `input_data` - The input that is perturbed.
+    /// * `reference` - The set the runs are aligned to, usually created from `input_data` without perturbation.
+    ///   The variances are in its coordinates.
+    /// * `projection` - Creates the projection algorithm of a run from its perturbed input and its seed,
+    ///   eg. `|_, seed| TSNE::new(30.0, 200.0, 1000, seed, TSNEMode::Exact)`. Deterministic algorithms can ignore the seed,
+    ///   and the input is only needed by the algorithms that depend on it, eg. `SMACOF` with an initial layout.
+    ///
+    /// ### Returns
+    ///
+    /// The spread of the positions of each point of the reference, or an error if a parameter is invalid,
+    /// or if a perturbed projection fails.
+    pub fn analyse<P: ProjectionAlgorithm>(
+        &self,
+        input_data: &InputData,
+        reference: &TimecurveSet,
+        projection: impl Fn(&InputData, u64) -> P,
+    ) -> Result<StabilityReport, TimecurveError> {
+        let valid = match self.perturbation {
+            Perturbation::Noise(deviation) => deviation.is_finite() && deviation >= 0.0,
+            Perturbation::Subsample(fraction) => fraction > 0.0 && fraction <= 1.0,
+            Perturbation::Seed => true,
+        };
+        if !valid || self.runs == 0 {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::InvalidParameter,
+                Some(&format!(
+                    "Invalid stability analysis : {} runs with {:?}",
+                    self.runs, self.perturbation
+                )),
+            ));
+        }
+
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        // positions of each (curve, label) of the reference in all the runs
+        let mut samples: HashMap<(String, String), Vec<Position>> = reference
+            .get_curves()
+            .iter()
+            .flat_map(|c| {
+                c.get_points().iter().map(move |p| {
+                    (
+                        (c.get_name().to_string(), p.get_label().to_string()),
+                        vec![],
+                    )
+                })
+            })
+            .collect();
+
+        for run in 0..self.runs {
+            let perturbed = match self.perturbation {
+                Perturbation::Noise(deviation) => add_noise(input_data, deviation, &mut rng),
+                Perturbation::Subsample(fraction) => subsample(input_data, fraction, &mut rng),
+                Perturbation::Seed => copy(input_data),
+            };
+
+            let run_seed = self.seed.wrapping_add(1 + run as u64);
+            let timecurves = TimecurveSet::new(&perturbed, projection(&perturbed, run_seed))?
+                .aligned_to(reference)?;
+
+            for curve in timecurves.get_curves() {
+                for point in curve.get_points() {
+                    let key = (curve.get_name().to_string(), point.get_label().to_string());
+                    if let Some(positions) = samples.get_mut(&key) {
+                        positions.push(*point.get_pos());
+                    }
+                }
+            }
+        }
+
+        Ok(StabilityReport {
+            runs: self.runs,
+            points: samples
+                .into_iter()
+                .map(|(key, positions)| (key, PointStability::new(&positions)))
+                .collect(),
+        })
+    }
+}
+
+/// The spread of the positions of a point over the runs of a stability analysis.
+#[derive(Clone, Copy)]
+pub struct PointStability {
+    /// The mean position of the point.
+    mean: Position,
+    /// The covariance matrix of the positions of the point.
+    covariance: Matrix3<f64>,
+    /// The number of runs in which the point was projected.
+    samples: usize,
+}
+
+impl PointStability {
+    /// Computes the mean and covariance of a list of positions.
+    fn new(positions: &[Position]) -> Self {
+        let vectors: Vec<Vector3<f64>> = positions
+            .iter()
+            .map(|p| Vector3::new(p.get_x(), p.get_y(), p.get_z()))
+            .collect();
+        let count = vectors.len().max(1) as f64;
+        let mean = vectors.iter().sum::<Vector3<f64>>() / count;
+        let covariance = vectors
+            .iter()
+            .map(|v| (v - mean) * (v - mean).transpose())
+            .sum::<Matrix3<f64>>()
+            / count;
+
+        Self {
+            mean: Position::new_3d(mean.x, mean.y, mean.z),
+            covariance,
+            samples: positions.len(),
+        }
+    }
+
+    /// Returns the mean position of the point over the runs.
+    pub fn get_mean(&self) -> Position {
+        self.mean
+    }
+
+    /// Returns the covariance matrix of the x, y and z coordinates of the point over the runs.
+    /// Its eigenvectors and the square roots of its eigenvalues give the axes of the uncertainty ellipse (or ellipsoid).
+    pub fn get_covariance(&self) -> &Matrix3<f64> {
+        &self.covariance
+    }
+
+    /// Returns the positional variance of the point, ie. the mean square distance to its mean position.
+    pub fn get_variance(&self) -> f64 {
+        self.covariance.trace()
+    }
+
+    /// Returns the number of runs in which the point was projected.
+    /// It is less than the number of runs when points are subsampled, and 0 if the point was never kept.
+    pub fn get_samples(&self) -> usize {
+        self.samples
+    }
+}
+
+/// The result of a `StabilityAnalysis`, giving the spread of the positions of each point of the reference set.
+#[derive(Clone)]
+pub struct StabilityReport {
+    /// The number of runs of the analysis.
+    runs: usize,
+    /// The spread of each point, by (curve name, timelabel).
+    points: HashMap<(String, String), PointStability>,
+}
+
+impl StabilityReport {
+    /// Returns the number of runs of the analysis.
+    pub fn get_runs(&self) -> usize {
+        self.runs
+    }
+
+    /// Returns the spread of the positions of a point.
+    ///
+    /// ### Arguments
+    ///
+    /// * `curve` - The name of the curve of the point.
+    /// * `label` - The timelabel of the point.
+    ///
+    /// ### Returns
+    ///
+    /// The spread of the point, or `None` if the reference set has no such point.
+    pub fn get(&self, curve: &str, label: &str) -> Option<&PointStability> {
+        self.points.get(&(curve.to_string(), label.to_string()))
+    }
+}
+
+/// Copies an input, without perturbing it.
+fn copy(input_data: &InputData) -> InputData {
+    InputData::from(
+        input_data.get_distance_matrix().clone(),
+        input_data.get_datasets().clone(),
+    )
+    .with_anchors(input_data.get_anchors().to_vec())
+    .with_features(input_data.get_features().cloned())
+}
+
+/// Multiplies each distance of an input by 1 + ε, ε following a normal distribution, keeping the matrix symmetric.
+fn add_noise(input_data: &InputData, deviation: f64, rng: &mut StdRng) -> InputData {
+    let mut matrix = input_data.get_distance_matrix().clone();
+    let n = matrix.len();
+    for i in 0..n {
+        for j in (i + 1)..n.min(matrix[i].len()) {
+            // Box-Muller transform
+            let (u, v): (f64, f64) = (1.0 - rng.gen::<f64>(), rng.gen());
+            let epsilon =
+                deviation * (-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos();
+            let factor = (1.0 + epsilon).max(0.0);
+            matrix[i][j] *= factor;
+            if let Some(d) = matrix.get_mut(j).and_then(|row| row.get_mut(i)) {
+                *d *= factor;
+            }
+        }
+    }
+
+    InputData::from(matrix, input_data.get_datasets().clone())
+        .with_anchors(input_data.get_anchors().to_vec())
+        .with_features(input_data.get_features().cloned())
+}
+
+/// Keeps a random fraction of the points of each dataset of an input, and at least 3 of them.
+/// The anchors of the removed points are dropped.
+fn subsample(input_data: &InputData, fraction: f64, rng: &mut StdRng) -> InputData {
+    let mut rows = Vec::new();
+    let mut datasets = Vec::new();
+    let mut first_row = 0;
+
+    for dataset in input_data.get_datasets() {
+        let labels = dataset.get_timelabels();
+        let count = ((fraction * labels.len() as f64).ceil() as usize)
+            .clamp(labels.len().min(3), labels.len());
+        let mut kept = rand::seq::index::sample(rng, labels.len(), count).into_vec();
+        kept.sort_unstable();
+
+        rows.extend(kept.iter().map(|i| first_row + i));
+        datasets.push(Dataset::new(
+            dataset.get_name(),
+            kept.iter().map(|&i| labels[i].clone()).collect(),
+        ));
+        first_row += labels.len();
+    }
+
+    let matrix = input_data.get_distance_matrix();
+    let features = input_data
+        .get_features()
+        .map(|features| rows.iter().map(|&i| features[i].clone()).collect());
+
+    let anchors = input_data
+        .get_anchors()
+        .iter()
+        .filter_map(|anchor| match (anchor.get_index(), anchor.get_label()) {
+            (Some(index), _) => rows
+                .iter()
+                .position(|&row| row == index)
+                .map(|row| Anchor::at_index(row, anchor.get_x(), anchor.get_y())),
+            (None, Some(label)) => datasets
+                .iter()
+                .any(|d| {
+                    anchor
+                        .get_dataset()
+                        .map_or(true, |name| d.get_name() == name)
+                        && d.get_timelabels().iter().any(|l| l == label)
+                })
+                .then(|| anchor.clone()),
+            (None, None) => None,
+        })
+        .collect();
+
+    InputData::from(
+        rows.iter()
+            .map(|&i| rows.iter().map(|&j| matrix[i][j]).collect())
+            .collect(),
+        datasets,
+    )
+    .with_anchors(anchors)
+    .with_features(features)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::projection::{ClassicalMDS, ProjectionAlgorithm, SMACOF};
+
+    fn spiral() -> InputData {
+        let points: Vec<Position> = (0..12)
+            .map(|i| {
+                let angle = i as f64 * 0.6;
+                Position::new(
+                    angle.cos() * (1.0 + 0.3 * i as f64),
+                    angle.sin() * (1.0 + 0.3 * i as f64),
+                )
+            })
+            .collect();
+        let matrix = points
+            .iter()
+            .map(|a| points.iter().map(|b| a.distance(b)).collect())
+            .collect();
+        let labels = (0..12).map(|i| (i + 1).to_string()).collect();
+        InputData::from(matrix, vec![Dataset::new("a", labels)])
+    }
+
+    #[test]
+    fn noise_makes_points_move() {
+        let input_data = spiral();
+        let reference = TimecurveSet::new(&input_data, ClassicalMDS::new()).unwrap();
+
+        // without noise, every run gives the reference again
+        let report = StabilityAnalysis::new(3, Perturbation::Noise(0.0))
+            .analyse(&input_data, &reference, |_, _| ClassicalMDS::new())
+            .unwrap();
+        let stability = report.get("a", "5").unwrap();
+        assert_eq!(stability.get_samples(), 3);
+        assert!(stability.get_variance() < 1e-12);
+
+        let report = StabilityAnalysis::new(10, Perturbation::Noise(0.1))
+            .with_seed(4)
+            .analyse(&input_data, &reference, |_, _| ClassicalMDS::new())
+            .unwrap();
+        let stability = report.get("a", "5").unwrap();
+        assert!(stability.get_variance() > 0.0);
+        assert!(
+            stability
+                .get_mean()
+                .distance(reference.get_curves()[0].get_points()[4].get_pos())
+                < 0.1
+        );
+        assert!(report.get("a", "13").is_none());
+    }
+
+    #[test]
+    fn subsampling_drops_points() {
+        let input_data = spiral();
+        let reference = TimecurveSet::new(&input_data, ClassicalMDS::new()).unwrap();
+
+        let report = StabilityAnalysis::new(8, Perturbation::Subsample(0.5))
+            .analyse(&input_data, &reference, |_, _| ClassicalMDS::new())
+            .unwrap();
+
+        let samples: usize = (1..=12)
+            .map(|i| report.get("a", &i.to_string()).unwrap().get_samples())
+            .sum();
+        assert_eq!(samples, 8 * 6);
+
+        assert!(StabilityAnalysis::new(8, Perturbation::Subsample(1.5))
+            .analyse(&input_data, &reference, |_, _| ClassicalMDS::new())
+            .is_err());
+    }
+
+    #[test]
+    fn subsampling_with_a_projection_depending_on_the_input() {
+        let input_data = spiral();
+        let reference = TimecurveSet::new(&input_data, ClassicalMDS::new()).unwrap();
+
+        // the initial layout of SMACOF must have a point per row of the subsampled matrix
+        let smacof = |input: &InputData, _| {
+            let layout = ClassicalMDS::new()
+                .project(input.get_distance_matrix())
+                .unwrap();
+            SMACOF::new(100, 1e-6, Some(layout))
+        };
+        let report = StabilityAnalysis::new(4, Perturbation::Subsample(0.5))
+            .analyse(&input_data, &reference, smacof)
+            .unwrap();
+
+        let samples: usize = (1..=12)
+            .map(|i| report.get("a", &i.to_string()).unwrap().get_samples())
+            .sum();
+        assert_eq!(samples, 4 * 6);
+    }
+}
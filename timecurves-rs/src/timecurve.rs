@@ -7,19 +7,32 @@
 */
 
 use crate::{
+    alignment::{procrustes, AlignmentStrategy, FirstToLastAlignment, NoAlignment, Similarity},
+    color::{ColorScheme, CurveColorScheme},
+    crossing::{curve_crossings, Crossing},
     error::{TimecurveError, TimecurveErrorKind},
     input::{Dataset, InputData},
-    projection::ProjectionAlgorithm,
+    interpolation::{BachInterpolation, Interpolation},
+    normalisation::{DistanceNormalisation, NormalisationStrategy, UnitSquareNormalisation},
+    projection::{
+        check_square_matrix, place_new_point,
+        quality::{fit_scale, max_neighbours},
+        ProjectionAlgorithm, ProjectionContext, QualityReport,
+    },
 };
 use palette::{Darken, Hsv, IntoColor, Mix, Srgb};
+use std::sync::Arc;
 
 #[derive(Clone, Copy)]
-/// Represents a position in 2D space.
+/// Represents a position in 2D or 3D space.
+/// Positions created with `Position::new` lie in the plane z = 0.
 pub struct Position {
     // The x coordinate of the position.
     x: f64,
     // The y coordinate of the position.
     y: f64,
+    // The z coordinate of the position, 0.0 for 2D positions.
+    z: f64,
 }
 
 impl Position {
@@ -32,9 +45,24 @@ impl Position {
     ///
     /// ### Returns
     ///
-    /// A new `Position` with the given coordinates.
+    /// A new `Position` with the given coordinates, and a z coordinate of 0.0.
     pub fn new(x: f64, y: f64) -> Self {
-        Position { x, y }
+        Position { x, y, z: 0.0 }
+    }
+
+    /// Creates a new `Position` with the given x, y and z coordinates.
+    ///
+    /// ### Arguments
+    ///
+    /// * `x` - The x coordinate.
+    /// * `y` - The y coordinate.
+    /// * `z` - The z coordinate.
+    ///
+    /// ### Returns
+    ///
+    /// A new `Position` with the given coordinates.
+    pub fn new_3d(x: f64, y: f64, z: f64) -> Self {
+        Position { x, y, z }
     }
 
     /// Performs linear interpolation between two positions.
@@ -51,6 +79,7 @@ impl Position {
         Position {
             x: (1.0 - t) * self.x + t * other.x,
             y: (1.0 - t) * self.y + t * other.y,
+            z: (1.0 - t) * self.z + t * other.z,
         }
     }
 
@@ -63,6 +92,17 @@ impl Position {
     pub fn get_y(&self) -> f64 {
         self.y
     }
+
+    /// Returns the z coordinate of the position, 0.0 for 2D positions.
+    pub fn get_z(&self) -> f64 {
+        self.z
+    }
+
+    /// Returns the euclidean distance between this position and another one.
+    pub fn distance(&self, other: &Position) -> f64 {
+        ((self.x - other.x).powi(2) + (self.y - other.y).powi(2) + (self.z - other.z).powi(2))
+            .sqrt()
+    }
 }
 
 /// Represents a point on a timecurve.
@@ -72,7 +112,7 @@ pub struct TimecurvePoint {
     label: String,
     /// The unix time value of the point. Is equivalent to the label, but in numerical form.
     t: i64,
-    /// The (x, y) position of the point in 2D space.
+    /// The (x, y, z) position of the point, z being 0.0 for 2D curves.
     pos: Position,
     /// The control point in the direction of the the previous point on the curve.
     c_prev: Option<Position>,
@@ -80,6 +120,12 @@ pub struct TimecurvePoint {
     c_next: Option<Position>,
     /// The color of the point, for visualization purposes
     color: (u8, u8, u8),
+    /// The index of the row of the distance matrix corresponding to the point.
+    index: usize,
+    /// The error of the projection around the point, see `QualityReport::get_local_errors`.
+    local_error: f64,
+    /// Whether the point is pinned to fixed coordinates by the anchors of the input.
+    anchored: bool,
 }
 
 impl TimecurvePoint {
@@ -118,13 +164,40 @@ impl TimecurvePoint {
         self.pos.get_y()
     }
 
+    /// Returns the z-coordinate of the position of the timecurve point, 0.0 for 2D curves.
+    pub fn get_pos_z(&self) -> f64 {
+        self.pos.get_z()
+    }
+
     /// Returns the color of the point, as a RGB tuple.
     pub fn get_color(&self) -> (u8, u8, u8) {
         self.color
     }
+
+    /// Returns the index of the row of the distance matrix corresponding to the point.
+    pub fn get_index(&self) -> usize {
+        self.index
+    }
+
+    /// Returns the error of the projection around the point. 0.0 means the distances to all other points are preserved.
+    /// See `QualityReport::get_local_errors` for more details.
+    /// It is only computed if the set was built with `TimecurveSetBuilder::with_quality`, or for points added later :
+    /// it is 0.0 otherwise.
+    pub fn get_local_error(&self) -> f64 {
+        self.local_error
+    }
+
+    /// Returns `true` if the point is listed in the anchors of the input, see `Anchor`.
+    /// Anchors are only honoured by anchored projection algorithms like `AnchoredSMACOF`.
+    pub fn is_anchored(&self) -> bool {
+        self.anchored
+    }
 }
 
 /// Represents a single timecurve.
+///
+/// Curves of less than 3 points are valid : a curve of 2 points is a straight segment,
+/// a curve of a single point is a dot without control points, and a curve without points is not drawn.
 #[derive(Clone)]
 pub struct Timecurve {
     /// The name of the timecurve.
@@ -172,6 +245,9 @@ impl Timecurve {
                 c_prev: None,
                 c_next: None,
                 color: (0, 0, 0),
+                index: i,
+                local_error: 0.0,
+                anchored: false,
             });
         }
 
@@ -192,74 +268,45 @@ impl Timecurve {
     ///
     /// ##### Arguments
     ///
-    /// * `sigma` - The smoothing parameter for the control points. For more information, see the paper.
-    fn compute_control_points(&mut self, sigma: f64) {
-        for i in 1..self.points.len() - 1 {
-            let current = &self.points[i];
-            let previous = &self.points[i - 1];
-            let next = &self.points[i + 1];
-
-            // These control points are positioned so that the line joining them is parallel to (pi−1, pi+1).
-
-            let mut line = (previous.pos.x - next.pos.x, previous.pos.y - next.pos.y);
-            let norm = (line.0.powi(2) + line.1.powi(2)).sqrt();
-            line = (line.0 / norm, line.1 / norm);
-
-            // The distance of ci,1 (resp. ci+1,0) to pi is set to the distance
-            // between pi and pi−1 (resp. pi+1) multiplied by a a smoothing parameter σ .
-
-            // distance between the previous and the current point
-            let dist_p_c = ((previous.pos.x - current.pos.x).powi(2)
-                + (previous.pos.y - current.pos.y).powi(2))
-            .sqrt();
-
-            let control_1 = Position::new(
-                current.pos.x + line.0 * dist_p_c * sigma,
-                current.pos.y + line.1 * dist_p_c * sigma,
-            );
-
-            // distance between the current and the next point
-            let dist_c_n = ((current.pos.x - next.pos.x).powi(2)
-                + (current.pos.y - next.pos.y).powi(2))
-            .sqrt();
-
-            // second control point
-            let control_2 = Position::new(
-                current.pos.x - line.0 * dist_c_n * sigma,
-                current.pos.y - line.1 * dist_c_n * sigma,
-            );
-
-            self.points[i].c_prev = Some(control_1);
-
-            self.points[i].c_next = Some(control_2);
+    /// * `interpolation` - The interpolation scheme giving the control points of each segment of the curve.
+    fn compute_control_points(&mut self, interpolation: &dyn Interpolation) {
+        let positions: Vec<Position> = self.points.iter().map(|p| p.pos).collect();
+        for point in self.points.iter_mut() {
+            point.c_prev = None;
+            point.c_next = None;
         }
 
-        // special case for the first and last points
-        // the cpoint for the first/last point is calculated based on the line between
-        // the first/last point and the next/previous control point
-        let len = self.points.len();
-
-        let p0 = &self.points[0].pos;
-        let c0 = &self.points[1].get_c_prev().unwrap();
-        let mut line0 = (p0.x - c0.x, p0.y - c0.y);
-        let norm = (line0.0.powi(2) + line0.1.powi(2)).sqrt();
-        line0 = (line0.0 / norm, line0.1 / norm);
-
-        self.points[0].c_next = Some(Position::new(
-            p0.x - line0.0 * sigma,
-            p0.y - line0.1 * sigma,
-        ));
+        // the first point has no control point before it, and the last one none after it
+        for (i, (c_next, c_prev)) in interpolation
+            .control_points(&positions)
+            .into_iter()
+            .enumerate()
+        {
+            self.points[i].c_next = Some(c_next);
+            self.points[i + 1].c_prev = Some(c_prev);
+        }
+    }
 
-        let p1: &Position = &self.points[len - 1].pos;
-        let c1 = &self.points[len - 2].get_c_next().unwrap();
-        let mut line1 = (p1.x - c1.x, p1.y - c1.y);
-        let norm = (line1.0.powi(2) + line1.1.powi(2)).sqrt();
-        line1 = (line1.0 / norm, line1.1 / norm);
+    /// Recomputes the control points that depend on the position of one point, eg. after it was added.
+    ///
+    /// ##### Arguments
+    ///
+    /// * `index` - The index of the point in the curve.
+    /// * `interpolation` - The interpolation scheme giving the control points of each segment of the curve.
+    fn update_control_points_around(&mut self, index: usize, interpolation: &dyn Interpolation) {
+        let positions: Vec<Position> = self.points.iter().map(|p| p.pos).collect();
+        let segments = interpolation.control_points(&positions);
+        let range = match interpolation.locality() {
+            Some(k) => index.saturating_sub(k)..(index + k).min(segments.len()),
+            None => 0..segments.len(),
+        };
 
-        self.points[len - 1].c_prev = Some(Position::new(
-            p1.x - line1.0 * sigma,
-            p1.y - line1.1 * sigma,
-        ));
+        for (i, (c_next, c_prev)) in segments.into_iter().enumerate() {
+            if range.contains(&i) {
+                self.points[i].c_next = Some(c_next);
+                self.points[i + 1].c_prev = Some(c_prev);
+            }
+        }
     }
 
     /// Evaluates the timecurve at a given point along the curve.
@@ -268,22 +315,30 @@ impl Timecurve {
     ///
     /// ### Arguments
     ///
-    /// * `u` - The parametric value at which to evaluate the timecurve. Should be in the range \[0, n\].
+    /// * `u` - The parametric value at which to evaluate the timecurve. Should be in the range \[0, n - 1\],
+    ///   n being the number of points.
     ///
     /// ### Returns
     ///
-    /// The position of the timecurve at the given parametric value.
+    /// The position of the timecurve at the given parametric value,
+    /// or an `EvaluatedOutsideRange` error if `u` is outside of the range, eg. for a curve without points.
     pub fn evaluate(&self, u: f64) -> Result<Position, TimecurveError> {
+        if !(0.0..=self.points.len() as f64 - 1.0).contains(&u) {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::EvaluatedOutsideRange,
+                Some(&format!(
+                    "{} is outside of [0, {}]",
+                    u,
+                    self.points.len() as f64 - 1.0
+                )),
+            ));
+        }
+
         let t = u.fract();
 
         let p0_index = u.floor() as usize;
         let p3_index = (u.floor() as usize + 1).clamp(0, self.points.len() - 1);
 
-        println!(
-            "\nEvaluating at u = {:.3}(t = {:.3}), between P0 = {} & P3 = {}",
-            u, t, p0_index, p3_index
-        );
-
         // if evaluating exactly on a point, return the point
         if t == 0.0 {
             return Ok(self.points[p0_index].get_pos().clone());
@@ -325,62 +380,75 @@ impl Timecurve {
         return Ok(d.lerp(&e, t));
     }
 
-    /// Rotates all points of the timecurve around the origin by a given angle.
-    /// This is useful for aligning the timecurves.
+    /// Computes the points where the curve crosses itself, ie. where the data came back close to an earlier state.
+    /// Consecutive segments always meet at their common point, so their crossings are not reported.
     ///
-    /// ### Arguments
+    /// ### Returns
     ///
-    /// * `angle` - The angle of rotation, in radians.
-    fn rotate_points_around_origin(&mut self, angle: f64) {
-        for p in &mut self.points {
-            p.pos = rotate_point_around_origin(angle, p.pos);
-            if let Some(c) = p.c_prev {
-                p.c_prev = Some(rotate_point_around_origin(angle, c));
-            }
-            if let Some(c) = p.c_next {
-                p.c_next = Some(rotate_point_around_origin(angle, c));
-            }
-        }
+    /// The crossings between the bezier segments of the curve, ordered by segments. See `Crossing`.
+    pub fn compute_self_crossings(&self) -> Vec<Crossing> {
+        curve_crossings((self, 0), (self, 0))
     }
 
-    /// Normalises the points of the timecurve to the range \[0, 1\].
-    /// Arguments are needed so that we can normalise all timecurves contained in a set the same way.
+    /// Applies a transformation to all points and control points of the timecurve.
     ///
     /// ### Arguments
     ///
-    /// * `min` - The minimum value of the x and y coordinates of all points in the timecurve set.
-    /// * `range` - The range of the x and y coordinates of all points in the timecurve set.
-    fn normalise_points(&mut self, min: Position, range: f64) {
-        // substract xmin or ymind to bring points into positive range ([0; +inf], [0; +inf])
-        // then divide by range to bring them into ([0; 1], [0; 1])
+    /// * `transform` - The function giving the new position of a point.
+    fn transform_points(&mut self, transform: impl Fn(Position) -> Position) {
         for p in &mut self.points {
-            p.pos.x = (p.pos.x - min.x) / range;
-            p.pos.y = (p.pos.y - min.y) / range;
-
+            p.pos = transform(p.pos);
             if let Some(c) = p.c_prev {
-                let new_x = (c.x - min.x) / range;
-                let new_y = (c.y - min.y) / range;
-                p.c_prev = Some(Position::new(new_x, new_y));
+                p.c_prev = Some(transform(c));
             }
-
             if let Some(c) = p.c_next {
-                let new_x = (c.x - min.x) / range;
-                let new_y = (c.y - min.y) / range;
-                p.c_next = Some(Position::new(new_x, new_y));
+                p.c_next = Some(transform(c));
             }
         }
     }
 }
 
-/// Represents a set of one or more timecurves sharing the same 2D space.
+/// The number of neighbours used for the quality report computed with `TimecurveSetBuilder::with_quality`.
+const DEFAULT_QUALITY_NEIGHBOURS: usize = 10;
+
+/// The smoothing parameter of the default interpolation of the curves.
+const SMOOTHING: f64 = 0.3;
+
+/// The ways to lay out curves that are projected independently, see `TimecurveSet::new_independent`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arrangement {
+    /// The curves are placed from left to right, in the order of the datasets.
+    SideBySide,
+    /// The curves are placed in a grid with the given number of columns, from left to right and top to bottom.
+    /// Each curve is at the bottom left of a cell of the size of the largest curve.
+    SmallMultiples(usize),
+}
+
+/// Represents a set of one or more timecurves sharing the same 2D or 3D space.
+#[derive(Clone)]
 pub struct TimecurveSet {
     /// A vector containing all the timecurves in the set.
     curves: Vec<Timecurve>,
+    /// The quality of the projection the set was created from, if it was requested and there are enough points.
+    quality: Option<QualityReport>,
+    /// The factor converting the distances of the input into distances between the points of the set.
+    distance_scale: f64,
+    /// The transformation from the coordinates of the projection to the coordinates of the set.
+    frame: Similarity,
+    /// The interpolation scheme of the curves, kept to add new points.
+    interpolation: Arc<dyn Interpolation>,
+    /// Whether the points of the curves are sorted chronologically.
+    sorted: bool,
+    /// The colour scheme of the points, kept to colour new points.
+    color_scheme: Arc<dyn ColorScheme>,
 }
 
 impl TimecurveSet {
     /// Creates a new `TimecurveSet` from an `InputData` instance and a projection algorithm.
     /// The timecurves are aligned and normalised in the process, and the points are sorted chronologically.
+    /// The first and last points of the first curve are aligned horizontally, see `FirstToLastAlignment`,
+    /// unless the input has anchors : the set is then only normalised, see `get_frame`.
+    /// Use a `TimecurveSetBuilder` to change the stages of the construction.
     ///
     /// ### Arguments
     /// * `input_data` - The input data containing the datasets and distance matrix.
@@ -392,27 +460,70 @@ impl TimecurveSet {
         input_data: &InputData,
         proj_algo: impl ProjectionAlgorithm,
     ) -> Result<Self, TimecurveError> {
-        let mut timecurves = TimecurveSet { curves: Vec::new() };
-        let projected_points = proj_algo.project(&input_data.get_distance_matrix())?;
+        TimecurveSetBuilder::new(proj_algo).build(input_data)
+    }
 
-        let mut index = 0; // index to keep track of where we are in the projected points
-        for dataset in input_data.get_datasets() {
-            let mut timecurve = Timecurve::new(
-                &dataset,
-                &projected_points[index..index + dataset.get_timelabels().len()],
-            )?;
+    /// Creates a new `TimecurveSet` like `new`, but with a custom alignment strategy.
+    ///
+    /// ### Arguments
+    /// * `input_data` - The input data containing the datasets and distance matrix.
+    /// * `proj_algo` - The projection algorithm to use to project the points.
+    /// * `alignment` - The strategy choosing the orientation of the curves.
+    ///
+    /// ### Returns
+    /// A new `TimecurveSet` instance, or an error if the projection or the alignment failed.
+    pub fn new_with_alignment(
+        input_data: &InputData,
+        proj_algo: impl ProjectionAlgorithm,
+        alignment: impl AlignmentStrategy,
+    ) -> Result<Self, TimecurveError> {
+        TimecurveSetBuilder::new(proj_algo)
+            .with_alignment(alignment)
+            .build(input_data)
+    }
 
-            timecurve.points.sort_by_key(|p| p.t);
-            timecurve.compute_control_points(0.3);
-            timecurves.curves.push(timecurve);
+    /// Creates a new `TimecurveSet` in which each curve is projected from the distances of its own dataset only,
+    /// ie. from its diagonal block of the distance matrix, eg. when the distances between datasets are meaningless.
+    /// The other entries of the matrix are ignored and can be missing (`null`).
+    ///
+    /// Each curve is aligned on its own, then the curves are laid out next to each other,
+    /// all in the unit of the distances so that their sizes can be compared, and the whole set is normalised.
+    /// As the curves don't share a projection, the set has no quality report, its frame only describes the
+    /// final normalisation, and the anchors of the input are ignored.
+    ///
+    /// ### Arguments
+    /// * `input_data` - The input data containing the datasets and distance matrix.
+    /// * `proj_algo` - The projection algorithm used for each dataset.
+    /// * `alignment` - The strategy choosing the orientation of each curve. It is applied to sets of a single curve,
+    ///   so `FirstToLastAlignment` must align on curve 0, which is then each curve in turn.
+    /// * `arrangement` - How the curves are laid out, see `Arrangement`.
+    ///
+    /// ### Returns
+    /// A new `TimecurveSet` instance, or a `MalformedDistanceMatrix` error if the matrix doesn't have one row
+    /// per timelabel, or an error if the projection or the alignment of a dataset failed.
+    pub fn new_independent(
+        input_data: &InputData,
+        proj_algo: impl ProjectionAlgorithm,
+        alignment: impl AlignmentStrategy,
+        arrangement: Arrangement,
+    ) -> Result<Self, TimecurveError> {
+        TimecurveSetBuilder::new(proj_algo)
+            .with_alignment(alignment)
+            .with_arrangement(Some(arrangement))
+            .build(input_data)
+    }
 
-            index += dataset.get_timelabels().len();
+    /// Creates a set from curves, with the default settings and no quality report.
+    fn from_curves(curves: Vec<Timecurve>) -> Self {
+        TimecurveSet {
+            curves,
+            quality: None,
+            distance_scale: 1.0,
+            frame: Similarity::identity(),
+            interpolation: Arc::new(BachInterpolation::new(SMOOTHING)),
+            sorted: true,
+            color_scheme: Arc::new(CurveColorScheme::new()),
         }
-        //Must be in this order if we want the curve to be around the origin
-        timecurves.align();
-        timecurves.normalise();
-        timecurves.update_colors();
-        return Ok(timecurves);
     }
 
     /// Returns a slice over the timecurves in the set.
@@ -420,77 +531,624 @@ impl TimecurveSet {
         &self.curves
     }
 
-    /// Aligns the timecurves in the set so that the first and last points of the first curve are aligned horizontally.
-    fn align(&mut self) {
-        // for multiple datasets, we align based on the first curve
-        // like in the examples in the webpage
-        let first_curve = match self.curves.get(0) {
-            Some(v) => v,
-            None => return,
-        };
+    /// Returns the quality of the projection the set was created from, computed with 10 neighbours
+    /// (or less if there are too few points). `None` unless it was requested with `TimecurveSetBuilder::with_quality`,
+    /// if there are less than 3 points, or if points were added with `with_new_point`.
+    /// Use `compute_quality` to get it afterwards.
+    pub fn get_quality(&self) -> Option<&QualityReport> {
+        self.quality.as_ref()
+    }
 
-        // if there are no points to align
-        if first_curve.points.len() < 2 {
-            return;
-        }
+    /// Returns the transformation from the coordinates of the projection, ie. the unit of the distances
+    /// and of the anchors, to the coordinates of the set. It combines the alignment and the normalisation of the set,
+    /// eg. `get_frame().apply(p)` gives where an anchor at `p` is drawn.
+    pub fn get_frame(&self) -> &Similarity {
+        &self.frame
+    }
 
-        let p0 = first_curve.points.first().unwrap(); // okay to unwrap here because we checked for length
-        let p1 = first_curve.points.last().unwrap();
+    /// Returns the factor converting the distances of the input into distances between the points of the set.
+    pub fn get_distance_scale(&self) -> f64 {
+        self.distance_scale
+    }
 
-        // find the angle needed for first and last point to be aligned horizontally
-        let angle = -(p1.pos.y - p0.pos.y).atan2(p1.pos.x - p0.pos.x);
+    /// Returns `true` if the points of the set don't all lie in the plane z = 0,
+    /// ie. if the set was created from a projection in 3 dimensions.
+    pub fn is_3d(&self) -> bool {
+        self.curves
+            .iter()
+            .flat_map(|c| &c.points)
+            .any(|p| p.pos.z != 0.0)
+    }
 
-        // rotate all points around the origin by that angle
-        for curve in &mut self.curves {
-            curve.rotate_points_around_origin(angle);
+    /// Computes the quality of the projection the set was created from, with a custom number of neighbours.
+    /// The alignment and normalisation of the set don't change the result.
+    ///
+    /// ### Arguments
+    ///
+    /// * `distance_matrix` - The distance matrix the set was created from.
+    /// * `k` - The number of neighbours used for trustworthiness and continuity.
+    ///
+    /// ### Returns
+    ///
+    /// The quality report, or an error if `k` is invalid or the matrix doesn't match the set.
+    pub fn compute_quality(
+        &self,
+        distance_matrix: &[Vec<f64>],
+        k: usize,
+    ) -> Result<QualityReport, TimecurveError> {
+        QualityReport::new(distance_matrix, &self.positions_by_index()?, k)
+    }
+
+    /// Computes the crossings between the bezier segments of the curves of the set,
+    /// both the self-crossings of each curve and the crossings between different curves.
+    ///
+    /// ### Returns
+    ///
+    /// The crossings, ordered by curves then by segments. See `Crossing`.
+    pub fn compute_crossings(&self) -> Vec<Crossing> {
+        let mut crossings = Vec::new();
+        for (i, first) in self.curves.iter().enumerate() {
+            for (j, second) in self.curves.iter().enumerate().skip(i) {
+                crossings.extend(curve_crossings((first, i), (second, j)));
+            }
         }
+
+        crossings
     }
 
-    /// Normalises all timecurves in the set so that their points are in the range \[0, 1\].
-    fn normalise(&mut self) {
-        let mut x_min = f64::INFINITY;
-        let mut x_max = f64::NEG_INFINITY;
-        let mut y_min = f64::INFINITY;
-        let mut y_max = f64::NEG_INFINITY;
+    /// Adds a new point to one of the curves without projecting all the points again,
+    /// eg. for a new revision of a monitored Wikipedia article.
+    /// The other points don't move, and only the control points that depend on the new point are recomputed,
+    /// see `Interpolation::locality`.
+    /// The new point is inserted chronologically, or at the end of its curve if the points of the set aren't sorted,
+    /// see `TimecurveSetBuilder::with_time_sorting`.
+    ///
+    /// The new point may lie slightly outside of \[0, 1\], as the set is not normalised again.
+    /// The quality report of the set is dropped, as it doesn't describe the new point :
+    /// use `compute_quality` with the extended distance matrix to get a new one.
+    ///
+    /// ### Arguments
+    ///
+    /// * `dataset` - The name of the curve the point is added to.
+    /// * `label` - The time label of the new point.
+    /// * `distances` - The distances from the new point to all the points of the set,
+    ///   in the order of the rows of the distance matrix. It is the new row of the distance matrix, without the last 0.
+    ///   Missing distances (NaN) are ignored.
+    ///
+    /// ### Returns
+    ///
+    /// The updated set, in which the new point corresponds to the next row of the distance matrix.
+    pub fn with_new_point(
+        &self,
+        dataset: &str,
+        label: &str,
+        distances: &[f64],
+    ) -> Result<TimecurveSet, TimecurveError> {
+        let layout = self.positions_by_index()?;
+        let scaled: Vec<f64> = distances.iter().map(|d| d * self.distance_scale).collect();
+        let pos = place_new_point(&layout, &scaled)?;
+
+        let curve_index = self
+            .curves
+            .iter()
+            .position(|c| c.name == dataset)
+            .ok_or_else(|| {
+                TimecurveError::new(
+                    TimecurveErrorKind::InvalidParameter,
+                    Some(&format!("No curve is named \"{}\"", dataset)),
+                )
+            })?;
+
+        // error of the new point, computed like in the quality report
+        let mut error = 0.0;
+        let mut norm = 0.0;
+        for (p, delta) in layout.iter().zip(&scaled).filter(|(_, d)| !d.is_nan()) {
+            error += (delta - pos.distance(p)).powi(2);
+            norm += delta.powi(2);
+        }
 
-        for curve in &self.curves {
-            x_min = curve.points.iter().fold(x_min, |acc, p| acc.min(p.pos.x));
-            x_max = curve.points.iter().fold(x_max, |acc, p| acc.max(p.pos.x));
-            y_min = curve.points.iter().fold(y_min, |acc, p| acc.min(p.pos.y));
-            y_max = curve.points.iter().fold(y_max, |acc, p| acc.max(p.pos.y));
+        let point = TimecurvePoint {
+            label: label.to_owned(),
+            t: label_to_time(label)?,
+            pos,
+            c_prev: None,
+            c_next: None,
+            color: (0, 0, 0),
+            index: layout.len(),
+            local_error: if norm > 0.0 { error / norm } else { 0.0 },
+            anchored: false,
+        };
+
+        let mut timecurves = self.clone();
+        timecurves.quality = None;
+
+        let curve = &mut timecurves.curves[curve_index];
+        let position = if self.sorted {
+            curve.points.partition_point(|p| p.t <= point.t)
+        } else {
+            curve.points.len()
+        };
+        curve.points.insert(position, point);
+        curve.update_control_points_around(position, &*self.interpolation);
+
+        timecurves.update_colors();
+        Ok(timecurves)
+    }
+
+    /// Aligns the set to a reference set with a Procrustes analysis, eg. to compare the curves of an article
+    /// generated with more revisions to the previous ones. See `ProcrustesAlignment` for more details.
+    /// The shape of the curves and the quality report don't change, but the points may lie outside of \[0, 1\].
+    ///
+    /// ### Arguments
+    ///
+    /// * `reference` - The set to align to.
+    ///
+    /// ### Returns
+    ///
+    /// The aligned set, or an error if the two sets share less than 2 distinct points.
+    pub fn aligned_to(&self, reference: &TimecurveSet) -> Result<TimecurveSet, TimecurveError> {
+        let mut timecurves = self.clone();
+        timecurves.apply(&procrustes(self, reference)?);
+        Ok(timecurves)
+    }
+
+    /// Gathers the positions of all points of the set.
+    ///
+    /// ### Returns
+    ///
+    /// The positions in the order of the rows of the distance matrix,
+    /// or an error if the indices of the points don't match these rows.
+    fn positions_by_index(&self) -> Result<Vec<Position>, TimecurveError> {
+        let count = self.curves.iter().map(|c| c.points.len()).sum();
+        let mut points = vec![None; count];
+        for point in self.curves.iter().flat_map(|c| c.points.iter()) {
+            if let Some(slot) = points.get_mut(point.index) {
+                *slot = Some(point.pos);
+            }
         }
 
-        // we want to scale all points by the same factor
-        // on the x and y axis in order to keep the aspect ratio
-        // and not distort distances between points
-        let max = x_max.max(y_max);
-        let min = x_min.min(y_min);
+        points
+            .into_iter()
+            .collect::<Option<Vec<Position>>>()
+            .ok_or_else(|| {
+                TimecurveError::new(
+                    TimecurveErrorKind::InvalidParameter,
+                    Some("The points of the set don't match the rows of the distance matrix"),
+                )
+            })
+    }
 
-        let range = max - min;
-        assert!(
-            !range.is_infinite(),
-            "Overflow in normalisation, range is infinite."
-        );
+    /// Aligns the timecurves in the set with an alignment strategy.
+    fn align(&mut self, alignment: &impl AlignmentStrategy) -> Result<(), TimecurveError> {
+        let similarity = alignment.transformation(self)?;
+        self.apply(&similarity);
+        Ok(())
+    }
+
+    /// Applies a similarity to all points and control points of the set.
+    fn apply(&mut self, similarity: &Similarity) {
         for curve in &mut self.curves {
-            curve.normalise_points(Position::new(x_min, y_min), range);
+            curve.transform_points(|p| similarity.apply(&p));
         }
+        self.distance_scale *= similarity.get_scale();
+        self.frame = self.frame.then(similarity);
     }
 
-    /// Updates the colors of the points in the timecurves.
+    /// Applies a transformation to all points and control points of the set.
+    ///
+    /// ### Arguments
+    ///
+    /// * `transform` - The function giving the new position of a point.
+    #[cfg(test)]
+    fn transform(&mut self, transform: impl Fn(Position) -> Position) {
+        for curve in &mut self.curves {
+            curve.transform_points(&transform);
+        }
+    }
+
+    /// Normalises the timecurves in the set with a normalisation strategy.
+    fn normalise(
+        &mut self,
+        normalisation: &impl NormalisationStrategy,
+    ) -> Result<(), TimecurveError> {
+        let similarity = normalisation.transformation(self)?;
+        self.apply(&similarity);
+        Ok(())
+    }
+
+    /// Updates the colors of the points in the timecurves with the colour scheme of the set.
     fn update_colors(&mut self) {
         for (i, curve) in self.curves.iter_mut().enumerate() {
-            let oldest = curve.points.first().unwrap().t;
-            let newest = curve.points.last().unwrap().t;
+            let (oldest, newest) = match (
+                curve.points.iter().map(|p| p.t).min(),
+                curve.points.iter().map(|p| p.t).max(),
+            ) {
+                (Some(oldest), Some(newest)) => (oldest, newest),
+                _ => continue,
+            };
 
             let range = (newest - oldest) as f32;
             for point in curve.points.iter_mut() {
-                point.color = curve_color_lerp(i, (point.t - oldest) as f32 / range)
+                // if all the points have the same time, the curve has the colour of its start
+                let u = if range > 0.0 {
+                    (point.t - oldest) as f32 / range
+                } else {
+                    0.0
+                };
+                point.color = self.color_scheme.color(i, u)
             }
         }
     }
 }
 
-/// Utility function that calculates the new position of a point after a rotation around the origin.
+/// Builder of a `TimecurveSet`, giving access to each stage of its construction :
+/// the points are projected, sorted chronologically, joined by smooth curves, aligned, normalised and coloured.
+/// The projection, alignment, normalisation and colour stages are traits,
+/// so custom stages can be plugged in without changing this module.
+///
+/// The default stages are those of `TimecurveSet::new`.
+pub struct TimecurveSetBuilder<'a> {
+    /// The projection algorithm placing the points.
+    projection: Box<dyn ProjectionAlgorithm + 'a>,
+    /// The alignment strategy, or `None` for the default one.
+    alignment: Option<Box<dyn AlignmentStrategy + 'a>>,
+    /// The normalisation strategy, applied after the alignment.
+    normalisation: Box<dyn NormalisationStrategy + 'a>,
+    /// The colour scheme of the points.
+    color_scheme: Arc<dyn ColorScheme>,
+    /// The interpolation scheme giving the control points of the curves.
+    interpolation: Arc<dyn Interpolation>,
+    /// Whether the points of the curves are sorted chronologically.
+    sort_by_time: bool,
+    /// How the curves are laid out if each dataset is projected on its own, or `None` to project them together.
+    arrangement: Option<Arrangement>,
+    /// Whether the quality report of the projection is computed.
+    quality: bool,
+}
+
+impl<'a> TimecurveSetBuilder<'a> {
+    /// Creates a new builder with the default stages : the points are sorted chronologically,
+    /// the curves are joined with `BachInterpolation` and a smoothing of 0.3, the first curve is aligned with `FirstToLastAlignment` (or not at all if the input has anchors),
+    /// the set is normalised with `UnitSquareNormalisation` and coloured with `CurveColorScheme`.
+    ///
+    /// ### Arguments
+    ///
+    /// * `projection` - The projection algorithm placing the points.
+    pub fn new(projection: impl ProjectionAlgorithm + 'a) -> Self {
+        Self {
+            projection: Box::new(projection),
+            alignment: None,
+            normalisation: Box::new(UnitSquareNormalisation::new()),
+            color_scheme: Arc::new(CurveColorScheme::new()),
+            interpolation: Arc::new(BachInterpolation::new(SMOOTHING)),
+            sort_by_time: true,
+            arrangement: None,
+            quality: false,
+        }
+    }
+
+    /// Sets the projection algorithm placing the points.
+    ///
+    /// ### Arguments
+    ///
+    /// * `projection` - The projection algorithm.
+    pub fn with_projection(mut self, projection: impl ProjectionAlgorithm + 'a) -> Self {
+        self.projection = Box::new(projection);
+        self
+    }
+
+    /// Sets the strategy choosing the orientation of the curves.
+    ///
+    /// ### Arguments
+    ///
+    /// * `alignment` - The alignment strategy, see the `alignment` module.
+    pub fn with_alignment(mut self, alignment: impl AlignmentStrategy + 'a) -> Self {
+        self.alignment = Some(Box::new(alignment));
+        self
+    }
+
+    /// Sets the strategy choosing the scale and position of the curves after their alignment.
+    /// It is skipped for alignment strategies that bring the set in the frame of another one, like `ProcrustesAlignment`.
+    ///
+    /// ### Arguments
+    ///
+    /// * `normalisation` - The normalisation strategy, see the `normalisation` module.
+    pub fn with_normalisation(mut self, normalisation: impl NormalisationStrategy + 'a) -> Self {
+        self.normalisation = Box::new(normalisation);
+        self
+    }
+
+    /// Sets the colour scheme of the points.
+    ///
+    /// ### Arguments
+    ///
+    /// * `color_scheme` - The colour scheme, see the `color` module.
+    pub fn with_color_scheme(mut self, color_scheme: impl ColorScheme + 'static) -> Self {
+        self.color_scheme = Arc::new(color_scheme);
+        self
+    }
+
+    /// Sets the interpolation scheme giving the control points of the curves.
+    ///
+    /// ### Arguments
+    ///
+    /// * `interpolation` - The interpolation scheme, see the `interpolation` module.
+    pub fn with_interpolation(mut self, interpolation: impl Interpolation + 'static) -> Self {
+        self.interpolation = Arc::new(interpolation);
+        self
+    }
+
+    /// Sets the smoothing parameter of the control points of the curves, with the interpolation of the paper.
+    /// Shorthand for `with_interpolation(BachInterpolation::new(smoothing))`.
+    ///
+    /// ### Arguments
+    ///
+    /// * `smoothing` - 0.0 gives straight lines between the points, larger values give rounder curves. 0.3 by default.
+    pub fn with_smoothing(self, smoothing: f64) -> Self {
+        self.with_interpolation(BachInterpolation::new(smoothing))
+    }
+
+    /// Sets whether the points of the curves are sorted chronologically, which is the default.
+    /// Otherwise, the points are joined in the order of the timelabels of their dataset.
+    ///
+    /// ### Arguments
+    ///
+    /// * `sort_by_time` - Whether the points are sorted.
+    pub fn with_time_sorting(mut self, sort_by_time: bool) -> Self {
+        self.sort_by_time = sort_by_time;
+        self
+    }
+
+    /// Sets whether each dataset is projected on its own, see `TimecurveSet::new_independent`.
+    /// The alignment strategy is then applied to each curve on its own, as the only curve of a set.
+    ///
+    /// ### Arguments
+    ///
+    /// * `arrangement` - How the curves are laid out, or `None` to project all the datasets together, which is the default.
+    pub fn with_arrangement(mut self, arrangement: Option<Arrangement>) -> Self {
+        self.arrangement = arrangement;
+        self
+    }
+
+    /// Sets whether the quality of the projection is computed with the set, see `TimecurveSet::get_quality`,
+    /// which also gives the local error of each point. It is off by default, as it takes O(n²) memory
+    /// and is much slower than the fast projections of large inputs.
+    /// It is never computed if each dataset is projected on its own.
+    ///
+    /// ### Arguments
+    ///
+    /// * `enabled` - Whether the quality report is computed.
+    pub fn with_quality(mut self, enabled: bool) -> Self {
+        self.quality = enabled;
+        self
+    }
+
+    /// Creates the set.
+    ///
+    /// ### Arguments
+    ///
+    /// * `input_data` - The input data containing the datasets and distance matrix.
+    ///
+    /// ### Returns
+    ///
+    /// A new `TimecurveSet` instance, or an error if a stage failed.
+    pub fn build(&self, input_data: &InputData) -> Result<TimecurveSet, TimecurveError> {
+        let mut timecurves = match self.arrangement {
+            Some(arrangement) => self.build_independent(input_data, arrangement)?,
+            None => {
+                let mut timecurves = self.build_joint(input_data)?;
+                //Must be in this order if we want the curve to be around the origin
+                let alignment = self.alignment(input_data);
+                timecurves.align(&alignment)?;
+                if alignment.normalise_after() {
+                    timecurves.normalise(&self.normalisation)?;
+                }
+                timecurves
+            }
+        };
+
+        timecurves.update_colors();
+        Ok(timecurves)
+    }
+
+    /// Returns the alignment strategy of the builder, or the default one for the input.
+    fn alignment(&self, input_data: &InputData) -> Box<dyn AlignmentStrategy + '_> {
+        match &self.alignment {
+            Some(alignment) => Box::new(alignment.as_ref()),
+            // rotating the set would move the anchors away from their coordinates
+            None if input_data.get_anchors().is_empty() => Box::new(FirstToLastAlignment::new(0)),
+            None => Box::new(NoAlignment::new()),
+        }
+    }
+
+    /// Projects all the points together and creates the curves, before their alignment and normalisation.
+    fn build_joint(&self, input_data: &InputData) -> Result<TimecurveSet, TimecurveError> {
+        let context = ProjectionContext::from_input(input_data)?;
+        let projected_points = self
+            .projection
+            .project_with_context(input_data.get_distance_matrix(), &context)?;
+
+        let neighbours = DEFAULT_QUALITY_NEIGHBOURS.min(max_neighbours(projected_points.len()));
+        let quality = if self.quality && neighbours > 0 {
+            Some(QualityReport::new(
+                input_data.get_distance_matrix(),
+                &projected_points,
+                neighbours,
+            )?)
+        } else {
+            None
+        };
+
+        // the projection may not keep the scale of the distances, eg. t-SNE
+        let scale = fit_scale(input_data.get_distance_matrix(), &projected_points);
+        let distance_scale = if scale > 0.0 { 1.0 / scale } else { 1.0 };
+
+        let mut timecurves = self.empty_set();
+        timecurves.quality = quality;
+        timecurves.distance_scale = distance_scale;
+
+        let mut index = 0; // index to keep track of where we are in the projected points
+        for dataset in input_data.get_datasets() {
+            let mut timecurve = Timecurve::new(
+                dataset,
+                &projected_points[index..index + dataset.get_timelabels().len()],
+            )?;
+
+            for point in timecurve.points.iter_mut() {
+                point.index += index;
+                point.anchored = context.get_anchors().iter().any(|(i, _)| *i == point.index);
+                if let Some(quality) = &timecurves.quality {
+                    point.local_error = quality.get_local_errors()[point.index];
+                }
+            }
+
+            if self.sort_by_time {
+                timecurve.points.sort_by_key(|p| p.t);
+            }
+            timecurve.compute_control_points(&*self.interpolation);
+            timecurves.curves.push(timecurve);
+
+            index += dataset.get_timelabels().len();
+        }
+
+        Ok(timecurves)
+    }
+
+    /// Projects each dataset on its own and lays out the curves, see `TimecurveSet::new_independent`.
+    fn build_independent(
+        &self,
+        input_data: &InputData,
+        arrangement: Arrangement,
+    ) -> Result<TimecurveSet, TimecurveError> {
+        let matrix = input_data.get_distance_matrix();
+        let n = check_square_matrix(matrix)?;
+        let sizes: Vec<usize> = input_data
+            .get_datasets()
+            .iter()
+            .map(|d| d.get_timelabels().len())
+            .collect();
+
+        if sizes.iter().sum::<usize>() != n {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::MalformedDistanceMatrix,
+                Some(&format!(
+                    "The {} rows don't match the blocks of the datasets, of sizes {:?}",
+                    n, sizes
+                )),
+            ));
+        }
+        if let Arrangement::SmallMultiples(0) = arrangement {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::InvalidParameter,
+                Some("A grid of small multiples needs at least 1 column"),
+            ));
+        }
+
+        let mut blocks = Vec::new();
+        let mut offset = 0;
+        for (dataset, &size) in input_data.get_datasets().iter().zip(&sizes) {
+            // there is nothing to project for a dataset without timelabels
+            if size == 0 {
+                blocks.push(TimecurveSet::from_curves(vec![Timecurve::new_empty(
+                    dataset.get_name(),
+                )]));
+                continue;
+            }
+
+            let rows = offset..offset + size;
+            let block_input = InputData::from(
+                matrix[rows.clone()]
+                    .iter()
+                    .map(|row| row[rows.clone()].to_vec())
+                    .collect(),
+                vec![dataset.clone()],
+            )
+            .with_features(input_data.get_features().map(|f| f[rows.clone()].to_vec()));
+
+            let mut block = self.build_joint(&block_input)?;
+            block.align(&self.alignment(&block_input))?;
+            // in the unit of the distances, so that all the curves have the same scale
+            block.normalise(&DistanceNormalisation::new())?;
+            for point in block.curves.iter_mut().flat_map(|c| c.points.iter_mut()) {
+                point.index += offset;
+            }
+
+            blocks.push(block);
+            offset += size;
+        }
+
+        // bounding boxes (min, max) of the curves, empty at the origin for curves without points
+        let bounds: Vec<(Position, Position)> = blocks
+            .iter()
+            .map(|block| {
+                let mut points = block.curves.iter().flat_map(|c| c.points.iter()).peekable();
+                if points.peek().is_none() {
+                    return (Position::new(0.0, 0.0), Position::new(0.0, 0.0));
+                }
+                points.fold(
+                    (
+                        Position::new(f64::INFINITY, f64::INFINITY),
+                        Position::new(f64::NEG_INFINITY, f64::NEG_INFINITY),
+                    ),
+                    |(min, max), p| {
+                        (
+                            Position::new(min.x.min(p.pos.x), min.y.min(p.pos.y)),
+                            Position::new(max.x.max(p.pos.x), max.y.max(p.pos.y)),
+                        )
+                    },
+                )
+            })
+            .collect();
+        let width = bounds
+            .iter()
+            .fold(0.0, |acc: f64, (min, max)| acc.max(max.x - min.x));
+        let height = bounds
+            .iter()
+            .fold(0.0, |acc: f64, (min, max)| acc.max(max.y - min.y));
+        let gap = 0.1 * width.max(height);
+
+        let mut timecurves = self.empty_set();
+
+        let mut x = 0.0;
+        for (i, (block, (min, max))) in blocks.into_iter().zip(bounds).enumerate() {
+            let corner = match arrangement {
+                Arrangement::SideBySide => {
+                    let corner = Position::new(x, 0.0);
+                    if block.curves.iter().any(|c| !c.points.is_empty()) {
+                        x += max.x - min.x + gap;
+                    }
+                    corner
+                }
+                Arrangement::SmallMultiples(columns) => Position::new(
+                    (i % columns) as f64 * (width + gap),
+                    -((i / columns) as f64) * (height + gap),
+                ),
+            };
+
+            for mut curve in block.curves {
+                curve.transform_points(|p| {
+                    Position::new_3d(p.x - min.x + corner.x, p.y - min.y + corner.y, p.z)
+                });
+                timecurves.curves.push(curve);
+            }
+        }
+
+        timecurves.normalise(&self.normalisation)?;
+        Ok(timecurves)
+    }
+
+    /// Creates a set without curves, with the settings of the builder.
+    fn empty_set(&self) -> TimecurveSet {
+        let mut timecurves = TimecurveSet::from_curves(Vec::new());
+        timecurves.interpolation = self.interpolation.clone();
+        timecurves.sorted = self.sort_by_time;
+        timecurves.color_scheme = self.color_scheme.clone();
+        timecurves
+    }
+}
+
+/// Utility function that calculates the new position of a point after a rotation around the origin,
+/// ie. around the z axis for 3D points.
 ///
 /// ### Arguments
 ///
@@ -500,14 +1158,14 @@ impl TimecurveSet {
 /// ### Returns
 ///
 /// The transformed point.
-fn rotate_point_around_origin(angle: f64, p: Position) -> Position {
+pub(crate) fn rotate_point_around_origin(angle: f64, p: Position) -> Position {
     let x = p.x;
     let y = p.y;
 
     let x_prime = x * angle.cos() - y * angle.sin();
     let y_prime = x * angle.sin() + y * angle.cos();
 
-    Position::new(x_prime, y_prime)
+    Position::new_3d(x_prime, y_prime, p.z)
 }
 
 /// Utility function that converts a label to a unix timestamp.
@@ -522,7 +1180,7 @@ fn rotate_point_around_origin(angle: f64, p: Position) -> Position {
 ///
 /// ### Note
 /// Please note that the label is assumed to be in UTC time. If it is not, the timestamp will be incorrect.
-fn label_to_time(label: &str) -> Result<i64, TimecurveError> {
+pub(crate) fn label_to_time(label: &str) -> Result<i64, TimecurveError> {
     let mut date;
 
     // label is a time string
@@ -606,12 +1264,13 @@ mod tests {
                 c_prev: None,
                 c_next: None,
                 color: (0, 0, 0),
+                index: i as usize,
+                local_error: 0.0,
+                anchored: false,
             });
         }
-        let mut set = TimecurveSet {
-            curves: vec![timecurve],
-        };
-        set.normalise();
+        let mut set = TimecurveSet::from_curves(vec![timecurve]);
+        set.normalise(&UnitSquareNormalisation::new()).unwrap();
         for curve in set.curves {
             for p in curve.points {
                 assert!(p.pos.x <= 1.0 && p.pos.x >= -1.0);
@@ -640,12 +1299,13 @@ mod tests {
                 c_prev: None,
                 c_next: None,
                 color: (0, 0, 0),
+                index: i,
+                local_error: 0.0,
+                anchored: false,
             });
         }
-        let mut set = TimecurveSet {
-            curves: vec![timecurve],
-        };
-        set.normalise();
+        let mut set = TimecurveSet::from_curves(vec![timecurve]);
+        set.normalise(&UnitSquareNormalisation::new()).unwrap();
         for curve in set.curves {
             for p in curve.points {
                 assert!(p.pos.x <= 1.0 && p.pos.x >= 0.0);
@@ -727,12 +1387,13 @@ mod tests {
                 c_prev: None,
                 c_next: None,
                 color: (0, 0, 0),
+                index: i,
+                local_error: 0.0,
+                anchored: false,
             });
         }
-        let mut set = TimecurveSet {
-            curves: vec![timecurve],
-        };
-        set.align();
+        let mut set = TimecurveSet::from_curves(vec![timecurve]);
+        set.align(&FirstToLastAlignment::new(0)).unwrap();
         for curve in set.curves {
             let p0 = curve.points.first().unwrap();
             let p1 = curve.points.last().unwrap();
@@ -781,4 +1442,481 @@ mod tests {
             InputData::from_filename(&format!("{}/tests/error.json", env!("CARGO_MANIFEST_DIR")));
         assert!(input_data.is_err());
     }
+
+    #[test]
+    fn timecurveset_in_3d() {
+        let input_data = InputData::from_filename(&format!(
+            "{}/tests/psfr100points.json",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+
+        let flat = TimecurveSet::new(&input_data, crate::projection::ClassicalMDS::new()).unwrap();
+        assert!(!flat.is_3d());
+
+        let mds = crate::projection::ClassicalMDS::new().with_dimensions(3);
+        let timecurve_set = TimecurveSet::new(&input_data, mds).unwrap();
+        assert!(timecurve_set.is_3d());
+
+        let points = timecurve_set.get_curves()[0].get_points();
+        for p in points {
+            for coordinate in [p.get_pos_x(), p.get_pos_y(), p.get_pos_z()] {
+                assert!((-1e-9..=1.0 + 1e-9).contains(&coordinate));
+            }
+        }
+
+        // the first and last points are aligned along the x axis
+        let first = points.first().unwrap().get_pos();
+        let last = points.last().unwrap().get_pos();
+        assert!((first.get_y() - last.get_y()).abs() < 1e-9);
+        assert!((first.get_z() - last.get_z()).abs() < 1e-9);
+        assert!(last.get_x() > first.get_x());
+    }
+
+    #[test]
+    fn timecurveset_quality_ignores_alignment_and_normalisation() {
+        let input_data = InputData::from_filename(&format!(
+            "{}/tests/psfr100points.json",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let mds = crate::projection::ClassicalMDS::new();
+        let projected = mds.project(input_data.get_distance_matrix()).unwrap();
+        let set = TimecurveSetBuilder::new(mds)
+            .with_quality(true)
+            .build(&input_data)
+            .unwrap();
+
+        let reference =
+            QualityReport::new(input_data.get_distance_matrix(), &projected, 5).unwrap();
+        let quality = set
+            .compute_quality(input_data.get_distance_matrix(), 5)
+            .unwrap();
+
+        assert!((quality.get_stress() - reference.get_stress()).abs() < 1e-9);
+        assert!((quality.get_trustworthiness() - reference.get_trustworthiness()).abs() < 1e-9);
+        assert!((quality.get_continuity() - reference.get_continuity()).abs() < 1e-9);
+
+        let default = set.get_quality().unwrap();
+        assert_eq!(default.get_k(), 10);
+        for point in set.get_curves()[0].get_points() {
+            assert_eq!(
+                point.get_local_error(),
+                default.get_local_errors()[point.get_index()]
+            );
+        }
+
+        // the report is only computed on demand, but the scale of the distances is the same
+        let lazy = TimecurveSet::new(&input_data, crate::projection::ClassicalMDS::new()).unwrap();
+        assert!(lazy.get_quality().is_none());
+        assert_eq!(lazy.get_distance_scale(), set.get_distance_scale());
+    }
+
+    #[test]
+    fn timecurveset_with_new_point() {
+        let input_data = InputData::from_filename(&format!(
+            "{}/tests/psfr100points.json",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let matrix = input_data.get_distance_matrix();
+        let dataset = &input_data.get_datasets()[0];
+
+        // project the first 99 points, then add the last one
+        let partial = InputData::from(
+            matrix[..99].iter().map(|row| row[..99].to_vec()).collect(),
+            vec![Dataset::new(
+                dataset.get_name(),
+                dataset.get_timelabels()[..99].to_vec(),
+            )],
+        );
+        let set = TimecurveSet::new(&partial, crate::projection::ClassicalMDS::new()).unwrap();
+        let updated = set
+            .with_new_point(
+                dataset.get_name(),
+                &dataset.get_timelabels()[99],
+                &matrix[99][..99],
+            )
+            .unwrap();
+
+        let before = set.get_curves()[0].get_points();
+        let after = updated.get_curves()[0].get_points();
+        assert_eq!(after.len(), 100);
+        assert!(updated.get_quality().is_none());
+
+        let new_point = after.iter().find(|p| p.get_index() == 99).unwrap();
+        assert!(new_point.get_local_error() < 0.05);
+
+        // the other points don't move, and only the control points next to the new one change
+        let position = after.iter().position(|p| p.get_index() == 99).unwrap();
+        for (i, old) in before.iter().enumerate() {
+            let new = &after[if i < position { i } else { i + 1 }];
+            assert_eq!(old.get_index(), new.get_index());
+            assert_eq!(old.get_pos_x(), new.get_pos_x());
+            assert_eq!(old.get_pos_y(), new.get_pos_y());
+            let is_neighbour = i + 1 >= position && i <= position;
+            let is_end = i <= 1 || i + 2 >= before.len();
+            if !is_neighbour && !is_end {
+                assert_eq!(
+                    old.get_c_prev().unwrap().get_x(),
+                    new.get_c_prev().unwrap().get_x()
+                );
+                assert_eq!(
+                    old.get_c_next().unwrap().get_y(),
+                    new.get_c_next().unwrap().get_y()
+                );
+            }
+        }
+
+        // the quality can still be computed with the extended matrix
+        let quality = updated.compute_quality(matrix, 10).unwrap();
+        assert!(quality.get_stress() < 0.05);
+
+        assert!(set
+            .with_new_point("unknown", "0", &matrix[99][..99])
+            .is_err());
+        assert!(set
+            .with_new_point(dataset.get_name(), "0", &matrix[99][..50])
+            .is_err());
+    }
+
+    #[test]
+    fn timecurveset_aligned_to_reference() {
+        let input_data = InputData::from_filename(&format!(
+            "{}/tests/psfr100points.json",
+            env!("CARGO_MANIFEST_DIR")
+        ))
+        .unwrap();
+        let reference =
+            TimecurveSet::new(&input_data, crate::projection::ClassicalMDS::new()).unwrap();
+
+        // a flipped, rotated, scaled and moved copy of the reference, with some points missing
+        let mut moved = reference.clone();
+        moved.transform(|p| {
+            let p = rotate_point_around_origin(1.2, Position::new(-p.x, p.y));
+            Position::new(p.x * 3.0 + 5.0, p.y * 3.0 - 1.0)
+        });
+        moved.distance_scale *= 3.0;
+        moved.curves[0].points.truncate(60);
+
+        let aligned = moved.aligned_to(&reference).unwrap();
+
+        for (a, b) in aligned.curves[0]
+            .points
+            .iter()
+            .zip(&reference.curves[0].points)
+        {
+            assert!(a.pos.distance(&b.pos) < 1e-9);
+            assert!(a.c_next.unwrap().distance(&b.c_next.unwrap()) < 1e-9);
+        }
+        assert!((aligned.distance_scale - reference.distance_scale).abs() < 1e-9);
+
+        moved.curves[0].name = "other".to_owned();
+        assert!(moved.aligned_to(&reference).is_err());
+    }
+
+    #[test]
+    fn timecurveset_keeps_anchors() {
+        // a 3 x 4 rectangle, with its first side pinned
+        let input_data = InputData::from_str(
+            r#"{
+                "distancematrix": [[0, 3, 5, 4], [3, 0, 4, 5], [5, 4, 0, 3], [4, 5, 3, 0]],
+                "data": [{ "name": "a", "timelabels": ["1", "2", "3", "4"] }],
+                "anchors": [
+                    { "dataset": "a", "label": "1", "x": 10.0, "y": 0.0 },
+                    { "index": 1, "x": 13.0, "y": 0.0 }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let timecurve_set =
+            TimecurveSet::new(&input_data, crate::projection::AnchoredSMACOF::new(300, 1e-9))
+                .unwrap();
+
+        let points = timecurve_set.get_curves()[0].get_points();
+        let frame = timecurve_set.get_frame();
+        assert!(points[0].is_anchored() && points[1].is_anchored() && !points[2].is_anchored());
+        assert!(points[0].pos.distance(&frame.apply(&Position::new(10.0, 0.0))) < 1e-9);
+        assert!(points[1].pos.distance(&frame.apply(&Position::new(13.0, 0.0))) < 1e-9);
+        // the set is normalised but not rotated
+        assert_eq!(points[0].pos.y, points[1].pos.y);
+    }
+
+    #[test]
+    fn timecurveset_from_features() {
+        let input_data = InputData::from_str(
+            r#"{
+                "features": [[0, 0, 1], [3, 4, 1], [6, 8, 1]],
+                "metric": "manhattan",
+                "data": [{ "name": "a", "timelabels": ["1", "2", "3"] }]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(input_data.get_distance_matrix()[0][1], 7.0);
+        assert_eq!(input_data.get_distance_matrix()[0][2], 14.0);
+
+        // the PCA uses the features, which are on a line of length 10, instead of the distances
+        let timecurve_set = TimecurveSet::new(&input_data, crate::projection::PCA::new()).unwrap();
+        let points = timecurve_set.get_curves()[0].get_points();
+        let length = points[0].pos.distance(&points[2].pos) / timecurve_set.get_frame().get_scale();
+        assert!((length - 10.0).abs() < 1e-9);
+
+        assert!(InputData::from_str(
+            r#"{ "features": [[0, 0], [1]], "data": [{ "name": "a", "timelabels": ["1", "2"] }] }"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn timecurveset_with_independent_datasets() {
+        // two right triangles whose cross-dataset distances are unknown
+        let input_data = InputData::from_str(
+            r#"{
+                "distancematrix": [
+                    [0, 3, 5, null, null, null],
+                    [3, 0, 4, null, null, null],
+                    [5, 4, 0, null, null, null],
+                    [null, null, null, 0, 6, 10],
+                    [null, null, null, 6, 0, 8],
+                    [null, null, null, 10, 8, 0]
+                ],
+                "data": [
+                    { "name": "a", "timelabels": ["1", "2", "3"] },
+                    { "name": "b", "timelabels": ["1", "2", "3"] }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let timecurve_set = TimecurveSet::new_independent(
+            &input_data,
+            crate::projection::ClassicalMDS::new(),
+            FirstToLastAlignment::new(0),
+            Arrangement::SideBySide,
+        )
+        .unwrap();
+
+        let a = timecurve_set.get_curves()[0].get_points();
+        let b = timecurve_set.get_curves()[1].get_points();
+        assert_eq!(b[2].get_index(), 5);
+        // both curves keep the same scale
+        let scale = timecurve_set.get_frame().get_scale();
+        assert!((a[0].pos.distance(&a[2].pos) / scale - 5.0).abs() < 1e-9);
+        assert!((b[0].pos.distance(&b[2].pos) / scale - 10.0).abs() < 1e-9);
+        // and don't overlap
+        let right_of_a = a.iter().fold(0.0, |acc: f64, p| acc.max(p.pos.x));
+        assert!(b.iter().all(|p| p.pos.x > right_of_a));
+        // each curve is aligned on its own ends
+        assert!((a[0].pos.y - a[2].pos.y).abs() < 1e-9 && a[0].pos.x < a[2].pos.x);
+        assert!((b[0].pos.y - b[2].pos.y).abs() < 1e-9 && b[0].pos.x < b[2].pos.x);
+
+        let grid = TimecurveSet::new_independent(
+            &input_data,
+            crate::projection::ClassicalMDS::new(),
+            FirstToLastAlignment::new(0),
+            Arrangement::SmallMultiples(1),
+        )
+        .unwrap();
+        let top_of_b = grid.get_curves()[1]
+            .get_points()
+            .iter()
+            .fold(0.0, |acc: f64, p| acc.max(p.pos.y));
+        assert!(grid.get_curves()[0].get_points().iter().all(|p| p.pos.y > top_of_b));
+
+        let wrong_blocks = InputData::from(
+            input_data.get_distance_matrix().clone(),
+            vec![Dataset::new("a", vec!["1".to_string(), "2".to_string()])],
+        );
+        let result = TimecurveSet::new_independent(
+            &wrong_blocks,
+            crate::projection::ClassicalMDS::new(),
+            NoAlignment::new(),
+            Arrangement::SideBySide,
+        );
+        assert!(matches!(
+            result.map_err(|e| e.kind),
+            Err(TimecurveErrorKind::MalformedDistanceMatrix)
+        ));
+    }
+
+    #[test]
+    fn timecurveset_builder_stages() {
+        let input_data = InputData::from_str(
+            r#"{
+                "distancematrix": [
+                    [0, 3, 5, 4],
+                    [3, 0, 4, 5],
+                    [5, 4, 0, 3],
+                    [4, 5, 3, 0]
+                ],
+                "data": [{ "name": "test", "timelabels": ["3", "1", "2", "4"] }]
+            }"#,
+        )
+        .unwrap();
+
+        let set = TimecurveSetBuilder::new(crate::projection::ClassicalMDS::new())
+            .with_smoothing(0.0)
+            .with_time_sorting(false)
+            .with_normalisation(DistanceNormalisation::new())
+            .with_color_scheme(crate::color::SingleColorScheme::new((1, 2, 3)))
+            .build(&input_data)
+            .unwrap();
+
+        let points = set.get_curves()[0].get_points();
+        // the points keep the order of the timelabels
+        let labels: Vec<&str> = points.iter().map(|p| p.get_label()).collect();
+        assert_eq!(labels, vec!["3", "1", "2", "4"]);
+        // without smoothing, the control points are on the points
+        let c_next = points[1].get_c_next().unwrap();
+        assert!((c_next.x - points[1].get_pos_x()).abs() < 1e-9);
+        assert!((c_next.y - points[1].get_pos_y()).abs() < 1e-9);
+        // the set is in the unit of the distances
+        assert!((points[0].pos.distance(&points[2].pos) - 5.0).abs() < 1e-6);
+        assert!(points.iter().all(|p| p.get_color() == (1, 2, 3)));
+
+        // and the added points keep that order too
+        let set = set
+            .with_new_point("test", "0", &[4.0, 3.0, 5.0, 3.0])
+            .unwrap();
+        assert_eq!(set.get_curves()[0].get_points()[4].get_label(), "0");
+    }
+
+    #[test]
+    fn timecurveset_with_missing_distances() {
+        let input_data = InputData::from_str(
+            r#"{
+                "distancematrix": [
+                    [0, 1, null, 1],
+                    [1, 0, 1, 1.4],
+                    [null, 1, 0, 1],
+                    [1, 1.4, 1, 0]
+                ],
+                "data": [{ "name": "test", "timelabels": ["0", "1", "2", "3"] }]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(input_data.get_missing_distances(), vec![(0, 2), (2, 0)]);
+        assert_eq!(input_data.get_weights()[2], vec![0.0, 1.0, 1.0, 1.0]);
+
+        // the classical MDS needs all distances
+        match TimecurveSet::new(&input_data, crate::projection::ClassicalMDS::new()) {
+            Err(e) => {
+                assert!(matches!(
+                    e.kind,
+                    TimecurveErrorKind::MalformedDistanceMatrix
+                ));
+                assert!(e.info.unwrap().contains("(0, 2), (2, 0)"));
+            }
+            Ok(_) => panic!("the classical MDS should not accept missing distances"),
+        }
+
+        // unless the matrix is completed first
+        let completed =
+            crate::projection::ShortestPathCompletion::new(crate::projection::ClassicalMDS::new());
+        assert!(TimecurveSet::new(&input_data, completed).is_ok());
+
+        // while SMACOF ignores them
+        let smacof = crate::projection::SMACOF::new(300, 1e-9, None);
+        let set = TimecurveSet::new(&input_data, smacof).unwrap();
+        assert_eq!(set.get_curves()[0].get_points().len(), 4);
+    }
+
+    #[test]
+    fn timecurveset_with_degenerate_curves() {
+        let input_data = InputData::from_str(
+            r#"{
+                "distancematrix": [
+                    [0, 1, 2, 3, 4],
+                    [1, 0, 1, 2, 3],
+                    [2, 1, 0, 1, 2],
+                    [3, 2, 1, 0, 1],
+                    [4, 3, 2, 1, 0]
+                ],
+                "data": [
+                    { "name": "empty", "timelabels": [] },
+                    { "name": "single", "timelabels": ["1"] },
+                    { "name": "pair", "timelabels": ["1", "2"] },
+                    { "name": "simultaneous", "timelabels": ["5", "5"] }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        for arrangement in [None, Some(Arrangement::SideBySide)] {
+            let set = TimecurveSetBuilder::new(crate::projection::ClassicalMDS::new())
+                .with_arrangement(arrangement)
+                .build(&input_data)
+                .unwrap();
+            let curves = set.get_curves();
+
+            // a curve without points
+            assert!(curves[0].get_points().is_empty());
+            assert!(curves[0].evaluate(0.0).is_err());
+
+            // a single dot, without control points
+            let single = &curves[1].get_points()[0];
+            assert!(single.get_c_prev().is_none() && single.get_c_next().is_none());
+            assert!(single.get_pos_x().is_finite() && single.get_pos_y().is_finite());
+            assert!(curves[1].evaluate(0.0).is_ok());
+            assert!(curves[1].evaluate(0.5).is_err());
+
+            // a straight segment
+            let pair = curves[2].get_points();
+            let middle = curves[2].evaluate(0.5).unwrap();
+            let expected = pair[0].pos.lerp(&pair[1].pos, 0.5);
+            assert!(middle.distance(&expected) < 1e-9);
+            assert!(pair[0].get_c_prev().is_none() && pair[1].get_c_next().is_none());
+
+            // a uniform colour
+            let simultaneous = curves[3].get_points();
+            assert_eq!(simultaneous[0].get_color(), simultaneous[1].get_color());
+        }
+    }
+
+    #[test]
+    fn degenerate_curves_can_be_exported() {
+        use crate::exporters::{
+            CSVExporter, Exporter, OBJExporter, SVGExporter, TikzExporter, VegaLiteExporter,
+        };
+
+        let inputs = [
+            r#"{ "distancematrix": [[0]], "data": [{ "name": "single", "timelabels": ["1"] }] }"#,
+            r#"{
+                "distancematrix": [[0, 1], [1, 0]],
+                "data": [{ "name": "empty", "timelabels": [] }, { "name": "pair", "timelabels": ["1", "1"] }]
+            }"#,
+            r#"{
+                "distancematrix": [[0, 0, 0], [0, 0, 0], [0, 0, 0]],
+                "data": [{ "name": "still", "timelabels": ["1", "2", "3"] }]
+            }"#,
+        ];
+        let exporters: Vec<Box<dyn Exporter>> = vec![
+            Box::new(CSVExporter::new()),
+            Box::new(TikzExporter::new(10.0, 1.0)),
+            Box::new(SVGExporter::new(1.0)),
+            Box::new(VegaLiteExporter::new(400)),
+            Box::new(OBJExporter::new(16)),
+        ];
+
+        for input in inputs {
+            let input_data = InputData::from_str(input).unwrap();
+            let set =
+                TimecurveSet::new(&input_data, crate::projection::ClassicalMDS::new()).unwrap();
+            for exporter in &exporters {
+                let output = exporter.export(&set);
+                assert!(!output.is_empty());
+                assert!(!output.contains("NaN"));
+            }
+        }
+
+        // the single dot is drawn, without any line
+        let single = InputData::from_str(inputs[0]).unwrap();
+        let set = TimecurveSet::new(&single, crate::projection::ClassicalMDS::new()).unwrap();
+        let svg = SVGExporter::new(1.0).export(&set);
+        assert_eq!(svg.matches("<circle").count(), 1);
+        assert_eq!(svg.matches("<path").count(), 0);
+    }
 }
@@ -0,0 +1,422 @@
+/*
+* Copyright (c) 2024, Kevin Jourdain
+*
+* SPDX-License-Identifier: BSD-3-Clause
+*/
+
+use crate::error::{TimecurveError, TimecurveErrorKind};
+
+/// An issue found in a distance matrix by `validate`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MatrixIssue {
+    /// The row doesn't have as many columns as the matrix has rows.
+    WrongRowLength { row: usize, length: usize },
+    /// The distance is infinite.
+    Infinite { row: usize, column: usize },
+    /// The distance is missing, ie. NaN or `null` in the input file. Only some projection algorithms accept it.
+    Missing { row: usize, column: usize },
+    /// The distance is negative.
+    Negative { row: usize, column: usize },
+    /// The distance of a point to itself is not 0.
+    NonZeroDiagonal { index: usize },
+    /// The distance from `row` to `column` differs from the distance from `column` to `row`.
+    /// Only reported once per pair, with `row` < `column`.
+    Asymmetric { row: usize, column: usize },
+    /// The distance between `i` and `j` is greater than the path through `k`, ie. δij > δik + δkj.
+    /// Only reported once per pair, with `i` < `j`, and for the `k` giving the shortest path.
+    TriangleInequality { i: usize, j: usize, k: usize },
+}
+
+impl std::fmt::Display for MatrixIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MatrixIssue::WrongRowLength { row, length } => {
+                write!(f, "row {} has {} columns", row, length)
+            }
+            MatrixIssue::Infinite { row, column } => {
+                write!(f, "distance ({}, {}) is infinite", row, column)
+            }
+            MatrixIssue::Missing { row, column } => {
+                write!(f, "distance ({}, {}) is missing", row, column)
+            }
+            MatrixIssue::Negative { row, column } => {
+                write!(f, "distance ({}, {}) is negative", row, column)
+            }
+            MatrixIssue::NonZeroDiagonal { index } => {
+                write!(f, "distance ({}, {}) is not 0", index, index)
+            }
+            MatrixIssue::Asymmetric { row, column } => write!(
+                f,
+                "distances ({}, {}) and ({}, {}) differ",
+                row, column, column, row
+            ),
+            MatrixIssue::TriangleInequality { i, j, k } => write!(
+                f,
+                "distance ({}, {}) is longer than the path through {}",
+                i, j, k
+            ),
+        }
+    }
+}
+
+/// The result of the validation of a distance matrix.
+pub struct ValidationReport {
+    /// The issues found, in the order of the checks then of the cells.
+    issues: Vec<MatrixIssue>,
+}
+
+impl ValidationReport {
+    /// Returns true if no issue was found.
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// Returns the issues found in the matrix.
+    pub fn get_issues(&self) -> &[MatrixIssue] {
+        &self.issues
+    }
+}
+
+impl std::fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.issues.is_empty() {
+            return write!(f, "The distance matrix is valid.");
+        }
+        write!(f, "The distance matrix has {} issues :", self.issues.len())?;
+        for issue in &self.issues {
+            write!(f, "\n  - {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// Checks that a distance matrix is a proper metric, ie. square, finite, non-negative, with a zero diagonal,
+/// symmetric and satisfying the triangle inequality.
+/// The projection algorithms give meaningless curves for most of these issues without failing.
+///
+/// Small differences due to rounding are tolerated. The triangle inequality is checked for all triples of points,
+/// so this takes a few seconds for thousands of points. The pairs involving a missing distance are not checked.
+///
+/// ### Arguments
+///
+/// * `distance_matrix` - The distance matrix to check.
+///
+/// ### Returns
+///
+/// The report of the issues found.
+pub fn validate(distance_matrix: &[Vec<f64>]) -> ValidationReport {
+    let n = distance_matrix.len();
+    let mut issues = Vec::new();
+
+    for (row, values) in distance_matrix.iter().enumerate() {
+        if values.len() != n {
+            issues.push(MatrixIssue::WrongRowLength {
+                row,
+                length: values.len(),
+            });
+        }
+    }
+    // the other checks need a square matrix
+    if !issues.is_empty() {
+        return ValidationReport { issues };
+    }
+
+    let tolerance = 1e-9
+        * distance_matrix
+            .iter()
+            .flatten()
+            .filter(|d| d.is_finite())
+            .fold(0.0, |acc: f64, d| acc.max(d.abs()));
+
+    for (row, values) in distance_matrix.iter().enumerate() {
+        for (column, &d) in values.iter().enumerate() {
+            if d.is_nan() {
+                issues.push(MatrixIssue::Missing { row, column });
+            } else if d.is_infinite() {
+                issues.push(MatrixIssue::Infinite { row, column });
+            } else if d < 0.0 {
+                issues.push(MatrixIssue::Negative { row, column });
+            }
+        }
+    }
+
+    for (index, values) in distance_matrix.iter().enumerate() {
+        if values[index].abs() > tolerance {
+            issues.push(MatrixIssue::NonZeroDiagonal { index });
+        }
+    }
+
+    for (row, values) in distance_matrix.iter().enumerate() {
+        for (column, &a) in values.iter().enumerate().skip(row + 1) {
+            let b = distance_matrix[column][row];
+            if a.is_finite() && b.is_finite() && (a - b).abs() > tolerance {
+                issues.push(MatrixIssue::Asymmetric { row, column });
+            }
+        }
+    }
+
+    for i in 0..n {
+        for j in (i + 1)..n {
+            let direct = distance_matrix[i][j];
+            if !direct.is_finite() {
+                continue;
+            }
+            let shortest = (0..n)
+                .filter(|&k| k != i && k != j)
+                .map(|k| (k, distance_matrix[i][k] + distance_matrix[k][j]))
+                .filter(|(_, d)| d.is_finite())
+                .min_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((k, path)) = shortest {
+                if direct > path + tolerance {
+                    issues.push(MatrixIssue::TriangleInequality { i, j, k });
+                }
+            }
+        }
+    }
+
+    ValidationReport { issues }
+}
+
+/// The way `Repair` makes a matrix symmetric.
+#[derive(Clone, Copy)]
+pub enum Symmetrisation {
+    /// Both distances are replaced by their mean.
+    Mean,
+    /// Both distances are replaced by the smallest one.
+    Min,
+    /// Both distances are replaced by the largest one.
+    Max,
+}
+
+/// Structure representing the repairs applied to a distance matrix to fix the issues found by `validate`.
+/// No repair is applied by default, each one must be enabled.
+///
+/// The repairs are applied in this order : clamping the negative distances, zeroing the diagonal,
+/// making the matrix symmetric, then the metric repair. Missing distances stay missing, unless the symmetric one is known.
+///
+/// ### Example
+///
+/// ```rust
+/// use timecurves_rs::validation::{validate, Repair, Symmetrisation};
+///
+/// let distance_matrix = vec![
+///     vec![0.0, 1.0, 5.0],
+///     vec![3.0, 0.0, 1.0],
+///     vec![5.0, 1.0, 0.0],
+/// ];
+/// assert!(!validate(&distance_matrix).is_valid());
+///
+/// let repaired = Repair::new()
+///     .with_symmetrisation(Some(Symmetrisation::Mean))
+///     .with_metric_repair(true)
+///     .apply(&distance_matrix)
+///     .unwrap();
+/// assert!(validate(&repaired).is_valid());
+/// ```
+#[derive(Clone, Copy, Default)]
+pub struct Repair {
+    /// How the matrix is made symmetric, if it is.
+    symmetrisation: Option<Symmetrisation>,
+    /// If true, the diagonal is set to 0.
+    zero_diagonal: bool,
+    /// If true, the negative distances are set to 0.
+    clamp_negatives: bool,
+    /// If true, each distance is replaced by the shortest path between the two points.
+    metric_repair: bool,
+}
+
+impl Repair {
+    /// Creates a new instance of the repair, which doesn't change the matrix until some repairs are enabled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how the matrix is made symmetric.
+    ///
+    /// ### Arguments
+    ///
+    /// * `symmetrisation` - How the two distances of each pair are combined. `None` leaves them as they are.
+    pub fn with_symmetrisation(mut self, symmetrisation: Option<Symmetrisation>) -> Self {
+        self.symmetrisation = symmetrisation;
+        self
+    }
+
+    /// Sets whether the distance of each point to itself is set to 0.
+    pub fn with_zero_diagonal(mut self, enabled: bool) -> Self {
+        self.zero_diagonal = enabled;
+        self
+    }
+
+    /// Sets whether the negative distances are set to 0.
+    pub fn with_clamped_negatives(mut self, enabled: bool) -> Self {
+        self.clamp_negatives = enabled;
+        self
+    }
+
+    /// Sets whether the metric repair is applied, ie. each distance is replaced by the length of the shortest path
+    /// between the two points (the metric closure), so that the triangle inequality holds. Distances only decrease.
+    /// It takes O(n³) time and needs a non-negative matrix.
+    pub fn with_metric_repair(mut self, enabled: bool) -> Self {
+        self.metric_repair = enabled;
+        self
+    }
+
+    /// Applies the enabled repairs to a distance matrix.
+    ///
+    /// ### Arguments
+    ///
+    /// * `distance_matrix` - The distance matrix to repair. It must be square.
+    ///
+    /// ### Returns
+    ///
+    /// The repaired matrix, or a `MalformedDistanceMatrix` error if the matrix is not square,
+    /// or has negative distances when the metric repair is enabled.
+    pub fn apply(&self, distance_matrix: &[Vec<f64>]) -> Result<Vec<Vec<f64>>, TimecurveError> {
+        let n = distance_matrix.len();
+        if let Some(row) = distance_matrix.iter().position(|r| r.len() != n) {
+            return Err(TimecurveError::new(
+                TimecurveErrorKind::MalformedDistanceMatrix,
+                Some(&format!(
+                    "Has {} rows != {} columns (row {})",
+                    n,
+                    distance_matrix[row].len(),
+                    row
+                )),
+            ));
+        }
+
+        let mut matrix = distance_matrix.to_vec();
+
+        if self.clamp_negatives {
+            for d in matrix.iter_mut().flatten() {
+                if *d < 0.0 {
+                    *d = 0.0;
+                }
+            }
+        }
+
+        if self.zero_diagonal {
+            for (i, row) in matrix.iter_mut().enumerate() {
+                row[i] = 0.0;
+            }
+        }
+
+        if let Some(symmetrisation) = self.symmetrisation {
+            for (i, j) in (0..n).flat_map(|i| ((i + 1)..n).map(move |j| (i, j))) {
+                let (a, b) = (matrix[i][j], matrix[j][i]);
+                // a missing distance is replaced by the known one
+                let value = if a.is_nan() {
+                    b
+                } else if b.is_nan() {
+                    a
+                } else {
+                    match symmetrisation {
+                        Symmetrisation::Mean => (a + b) / 2.0,
+                        Symmetrisation::Min => a.min(b),
+                        Symmetrisation::Max => a.max(b),
+                    }
+                };
+                matrix[i][j] = value;
+                matrix[j][i] = value;
+            }
+        }
+
+        if self.metric_repair {
+            if let Some((i, j)) = (0..n)
+                .flat_map(|i| (0..n).map(move |j| (i, j)))
+                .find(|&(i, j)| matrix[i][j] < 0.0)
+            {
+                return Err(TimecurveError::new(
+                    TimecurveErrorKind::MalformedDistanceMatrix,
+                    Some(&format!(
+                        "Distance ({}, {}) is negative, the metric repair needs non-negative distances",
+                        i, j
+                    )),
+                ));
+            }
+
+            // Floyd-Warshall, the missing distances are not used as paths and stay missing
+            for k in 0..n {
+                let through = matrix[k].clone();
+                for row in matrix.iter_mut() {
+                    let to_k = row[k];
+                    if to_k.is_nan() {
+                        continue;
+                    }
+                    for (d, from_k) in row.iter_mut().zip(&through) {
+                        if !d.is_nan() && to_k + from_k < *d {
+                            *d = to_k + from_k;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(matrix)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validation_reports_each_issue() {
+        let distance_matrix = vec![
+            vec![0.5, 1.0, 10.0, f64::NAN],
+            vec![1.0, 0.0, 1.0, -1.0],
+            vec![10.0, 2.0, 0.0, 1.0],
+            vec![1.0, 1.0, f64::INFINITY, 0.0],
+        ];
+
+        let report = validate(&distance_matrix);
+        let issues = report.get_issues();
+
+        assert!(!report.is_valid());
+        assert!(issues.contains(&MatrixIssue::Missing { row: 0, column: 3 }));
+        assert!(issues.contains(&MatrixIssue::Negative { row: 1, column: 3 }));
+        assert!(issues.contains(&MatrixIssue::Infinite { row: 3, column: 2 }));
+        assert!(issues.contains(&MatrixIssue::NonZeroDiagonal { index: 0 }));
+        assert!(issues.contains(&MatrixIssue::Asymmetric { row: 1, column: 2 }));
+        assert!(issues.contains(&MatrixIssue::TriangleInequality { i: 0, j: 2, k: 1 }));
+
+        let wrong_size = validate(&[vec![0.0, 1.0], vec![1.0]]);
+        assert_eq!(
+            wrong_size.get_issues(),
+            [MatrixIssue::WrongRowLength { row: 1, length: 1 }]
+        );
+    }
+
+    #[test]
+    fn repairs_fix_the_issues() {
+        let distance_matrix = vec![
+            vec![0.5, 1.0, 10.0],
+            vec![3.0, 0.0, -1.0],
+            vec![10.0, 1.0, 0.0],
+        ];
+
+        let repaired = Repair::new()
+            .with_clamped_negatives(true)
+            .with_zero_diagonal(true)
+            .with_symmetrisation(Some(Symmetrisation::Max))
+            .with_metric_repair(true)
+            .apply(&distance_matrix)
+            .unwrap();
+
+        assert_eq!(
+            repaired,
+            vec![
+                vec![0.0, 3.0, 4.0],
+                vec![3.0, 0.0, 1.0],
+                vec![4.0, 1.0, 0.0],
+            ]
+        );
+        assert!(validate(&repaired).is_valid());
+
+        // nothing is changed by default
+        assert_eq!(
+            Repair::new().apply(&distance_matrix).unwrap(),
+            distance_matrix
+        );
+    }
+}